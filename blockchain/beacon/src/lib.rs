@@ -13,7 +13,7 @@ use clock::ChainEpoch;
 use std::error::Error;
 
 pub async fn beacon_entries_for_block<B: Beacon>(
-    beacon: B,
+    beacon: &B,
     round: ChainEpoch,
     prev: &BeaconEntry,
 ) -> Result<Vec<BeaconEntry>, Box<dyn Error>> {
@@ -1,8 +1,9 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use byteorder::{BigEndian, ByteOrder};
 use crypto::VRFProof;
-use encoding::{serde_bytes, tuple::*};
+use encoding::{blake2b_256, serde_bytes, tuple::*};
 use fil_types::PoStProof;
 
 /// A Ticket is a marker of a tick of the blockchain's clock.  It is the source
@@ -19,6 +20,14 @@ impl Ticket {
     pub fn new(vrfproof: VRFProof) -> Self {
         Self { vrfproof }
     }
+
+    /// Returns the quality of the ticket, between 0 and 1. Higher quality tickets are more likely
+    /// to be the minimum ticket of a tipset.
+    pub fn quality(&self) -> f64 {
+        let ticket_hash = blake2b_256(self.vrfproof.as_bytes());
+        let ticket_num = BigEndian::read_u64(&ticket_hash[..8]) as f64;
+        1.0 - ticket_num / 2f64.powi(64)
+    }
 }
 
 /// PoSt election candidates
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::{Error, TipIndex, TipsetMetadata};
-use actor::{power::State as PowerState, STORAGE_POWER_ACTOR_ADDR};
+use actor::{power::State as PowerState, EPOCHS_IN_DAY, STORAGE_POWER_ACTOR_ADDR};
 use address::Address;
 use async_std::sync::RwLock;
 use async_std::task;
@@ -10,12 +10,13 @@ use beacon::{BeaconEntry, IGNORE_DRAND_VAR};
 use blake2b_simd::Params;
 use blocks::{Block, BlockHeader, FullTipset, Tipset, TipsetKeys, TxMeta};
 use byteorder::{BigEndian, WriteBytesExt};
-use cid::Cid;
-use cid::Code::Blake2b256;
+use cid::{Cid, Code::Blake2b256, Codec};
 use clock::ChainEpoch;
 use crypto::DomainSeparationTag;
 use encoding::{blake2b_256, de::DeserializeOwned, from_slice, Cbor};
 use flo_stream::{MessagePublisher, Publisher, Subscriber};
+use forest_car::{CarHeader, CarWriter};
+use forest_ipld::Ipld;
 use futures::{future, StreamExt};
 use interpreter::BlockMessages;
 use ipld_amt::Amt;
@@ -26,7 +27,8 @@ use num_bigint::{BigInt, Integer};
 use num_traits::Zero;
 use serde::Serialize;
 use state_tree::StateTree;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error as StdError;
 use std::io::Write;
 use std::sync::Arc;
 use types::WINNING_POST_SECTOR_SET_LOOKBACK;
@@ -45,6 +47,13 @@ const BLOCKS_PER_EPOCH: u64 = 5;
 // A cap on the size of the future_sink
 const SINK_CAP: usize = 1000;
 
+/// Default maximum number of tipsets reverted when switching to a heavier fork.
+pub const DEFAULT_FORK_LENGTH_THRESHOLD: u64 = 500;
+
+// A cap on the number of tipsets applied individually on a head change; longer runs are
+// collapsed into applying the new head only
+const APPLY_CHANGES_CAP: usize = 100;
+
 /// Enum for pubsub channel that defines message type variant and data contained in message type.
 #[derive(Clone, Debug)]
 pub enum HeadChange {
@@ -93,6 +102,9 @@ pub struct ChainStore<DB> {
 
     /// tip_index tracks tipsets by epoch/parentset for use by expected consensus.
     tip_index: RwLock<TipIndex>,
+
+    /// Maximum number of tipsets of the current chain reverted when switching to a heavier fork.
+    fork_length_threshold: u64,
 }
 
 impl<DB> ChainStore<DB>
@@ -105,6 +117,7 @@ where
             publisher: RwLock::new(Publisher::new(SINK_CAP)),
             tip_index: RwLock::new(TipIndex::new()),
             heaviest: Default::default(),
+            fork_length_threshold: DEFAULT_FORK_LENGTH_THRESHOLD,
         };
 
         // Result intentionally ignored, doesn't matter if heaviest doesn't exist in store yet
@@ -113,16 +126,85 @@ where
         cs
     }
 
-    /// Sets heaviest tipset within ChainStore and store its tipset cids under HEAD_KEY
+    /// Sets the maximum number of tipsets reverted when switching to a heavier fork.
+    pub fn with_fork_length_threshold(mut self, threshold: u64) -> Self {
+        self.fork_length_threshold = threshold;
+        self
+    }
+
+    /// Returns the maximum number of tipsets reverted when switching to a heavier fork.
+    pub fn fork_length_threshold(&self) -> u64 {
+        self.fork_length_threshold
+    }
+
+    /// Sets heaviest tipset within ChainStore and store its tipset cids under HEAD_KEY.
+    /// If a head was already set, `Revert` changes are published for the tipsets of the previous
+    /// head's chain down to the common ancestor, followed by `Apply` changes for the tipsets of
+    /// the new chain. Reorgs reverting more tipsets than the fork length threshold are refused.
     pub async fn set_heaviest_tipset(&self, ts: Arc<Tipset>) -> Result<(), Error> {
-        self.db.write(HEAD_KEY, ts.key().marshal_cbor()?)?;
-        *self.heaviest.write().await = Some(ts.clone());
-        self.publisher
-            .write()
-            .await
-            .publish(HeadChange::Current(ts))
-            .await;
-        Ok(())
+        loop {
+            // The chains are walked without holding any lock, the head is only swapped if it
+            // did not change in the meantime.
+            let old = self.heaviest.read().await.clone();
+            let changes = match old.as_ref() {
+                Some(old) => self.reorg_changes(old, &ts)?,
+                None => vec![HeadChange::Current(ts.clone())],
+            };
+
+            // Holding the publisher lock keeps the changes of concurrent head updates in order.
+            let mut publisher = self.publisher.write().await;
+            {
+                let mut heaviest = self.heaviest.write().await;
+                if heaviest.as_ref().map(|h| h.key()) != old.as_ref().map(|h| h.key()) {
+                    continue;
+                }
+                self.db.write(HEAD_KEY, ts.key().marshal_cbor()?)?;
+                *heaviest = Some(ts);
+            }
+            for change in changes {
+                publisher.publish(change).await;
+            }
+            return Ok(());
+        }
+    }
+
+    /// Returns the head changes switching from the `from` head to the `to` head, reverting the
+    /// tipsets of `from`'s chain from the head down and applying the tipsets of `to`'s chain
+    /// from the common ancestor up. If more than `APPLY_CHANGES_CAP` tipsets would be applied,
+    /// only `to` is.
+    fn reorg_changes(
+        &self,
+        from: &Arc<Tipset>,
+        to: &Arc<Tipset>,
+    ) -> Result<Vec<HeadChange>, Error> {
+        let mut left = from.clone();
+        let mut right = to.clone();
+        let mut reverts = Vec::new();
+        let mut applies = Vec::new();
+        let mut collapsed = false;
+        while left.key() != right.key() {
+            if left.epoch() > right.epoch() {
+                if reverts.len() as u64 >= self.fork_length_threshold {
+                    return Err(Error::ForkLengthExceeded(self.fork_length_threshold));
+                }
+                let parent = Arc::new(self.tipset_from_keys(left.parents())?);
+                reverts.push(HeadChange::Revert(left));
+                left = parent;
+            } else {
+                let parent = Arc::new(self.tipset_from_keys(right.parents())?);
+                if applies.len() < APPLY_CHANGES_CAP {
+                    applies.push(HeadChange::Apply(right));
+                } else {
+                    collapsed = true;
+                }
+                right = parent;
+            }
+        }
+        if collapsed {
+            applies = vec![HeadChange::Apply(to.clone())];
+        }
+        reverts.extend(applies.into_iter().rev());
+        Ok(reverts)
     }
 
     // subscribing returns a future sink that we can essentially iterate over using future streams
@@ -229,12 +311,13 @@ where
 
     /// Determines if provided tipset is heavier than existing known heaviest tipset
     async fn update_heaviest(&self, ts: &Tipset) -> Result<(), Error> {
-        match self.heaviest.read().await.as_ref() {
+        // Read lock is released before setting the heaviest tipset.
+        let heaviest = self.heaviest.read().await.clone();
+        match heaviest {
             Some(heaviest) => {
                 let new_weight = weight(self.blockstore(), ts)?;
                 let curr_weight = weight(self.blockstore(), &heaviest)?;
                 if new_weight > curr_weight {
-                    // TODO potentially need to deal with re-orgs here
                     info!("New heaviest tipset");
                     self.set_heaviest_tipset(Arc::new(ts.clone())).await?;
                 }
@@ -507,6 +590,207 @@ where
         let bmsgs = self.block_msgs_for_tipset(ts)?;
         Ok(bmsgs.into_iter().map(|bm| bm.messages).flatten().collect())
     }
+
+    /// Exports a range of tipsets, as well as the state roots based on the `recent_roots`,
+    /// to a CAR file which can be imported as a snapshot.
+    pub fn export<W>(
+        &self,
+        tipset: &Tipset,
+        recent_roots: ChainEpoch,
+        skip_old_msgs: bool,
+        writer: W,
+    ) -> Result<W, Error>
+    where
+        W: Write,
+    {
+        let header = CarHeader::new(tipset.key().cids().to_vec(), 1);
+        let mut car_writer =
+            CarWriter::new(writer, &header).map_err(|e| Error::Other(e.to_string()))?;
+
+        self.walk_snapshot(tipset, recent_roots, skip_old_msgs, |cid| {
+            let block = self
+                .blockstore()
+                .get_bytes(cid)?
+                .ok_or_else(|| format!("Cid {} not found in blockstore", cid))?;
+            car_writer.write_block(cid, &block)?;
+            Ok(())
+        })?;
+        car_writer
+            .flush()
+            .map_err(|e| Error::Other(e.to_string()))?;
+
+        Ok(car_writer.into_inner())
+    }
+
+    /// Walks the chain from the given tipset back to genesis, calling the callback with the Cid
+    /// of every header, and of the messages, receipts and state trees of the tipsets within
+    /// `recent_roots` epochs of the starting tipset. If `skip_old_msgs` is false, messages are
+    /// included for the entire chain.
+    pub fn walk_snapshot<F>(
+        &self,
+        tipset: &Tipset,
+        recent_roots: ChainEpoch,
+        skip_old_msgs: bool,
+        callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Cid) -> Result<(), Box<dyn StdError>>,
+    {
+        self.walk_snapshot_from(
+            tipset,
+            recent_roots,
+            skip_old_msgs,
+            &mut HashSet::new(),
+            callback,
+        )
+    }
+
+    /// Walks the chain like `walk_snapshot`, but skips the Cids already in `walked` along with
+    /// the blocks only reachable through them, and adds every Cid walked to it. This lets
+    /// several walks share a single set of walked Cids.
+    pub fn walk_snapshot_from<F>(
+        &self,
+        tipset: &Tipset,
+        recent_roots: ChainEpoch,
+        skip_old_msgs: bool,
+        walked: &mut HashSet<Cid>,
+        mut callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Cid) -> Result<(), Box<dyn StdError>>,
+    {
+        // Identity and Filecoin commitment Cids are not stored as blocks
+        let mut cbor_callback = |cid: &Cid| {
+            if cid.codec == Codec::DagCBOR {
+                callback(cid)
+            } else {
+                Ok(())
+            }
+        };
+        let mut blocks_to_walk: VecDeque<Cid> = tipset.cids().iter().copied().collect();
+        let mut current_min_height = tipset.epoch();
+        let incl_roots_epoch = tipset.epoch() - recent_roots;
+
+        while let Some(next) = blocks_to_walk.pop_front() {
+            if !walked.insert(next) {
+                continue;
+            }
+            cbor_callback(&next).map_err(|e| Error::Other(e.to_string()))?;
+
+            let header: BlockHeader = self
+                .blockstore()
+                .get(&next)
+                .map_err(|e| Error::Other(e.to_string()))?
+                .ok_or_else(|| Error::NotFound("Block header"))?;
+
+            if current_min_height > header.epoch() {
+                current_min_height = header.epoch();
+                if current_min_height % EPOCHS_IN_DAY == 0 {
+                    info!("export at: {}", current_min_height);
+                }
+            }
+
+            if !skip_old_msgs || header.epoch() > incl_roots_epoch {
+                recurse_links(
+                    self.blockstore(),
+                    walked,
+                    header.messages(),
+                    &mut cbor_callback,
+                )?;
+            }
+
+            if header.epoch() > 0 {
+                blocks_to_walk.extend(header.parents().cids());
+            }
+
+            if header.epoch() == 0 || header.epoch() > incl_roots_epoch {
+                recurse_links(
+                    self.blockstore(),
+                    walked,
+                    header.state_root(),
+                    &mut cbor_callback,
+                )?;
+                recurse_links(
+                    self.blockstore(),
+                    walked,
+                    header.message_receipts(),
+                    &mut cbor_callback,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls the callback with the Cid of every IPLD block reachable from the `root`, including
+    /// the `root` itself.
+    pub fn walk_links<F>(&self, root: &Cid, callback: F) -> Result<(), Error>
+    where
+        F: FnMut(&Cid) -> Result<(), Box<dyn StdError>>,
+    {
+        self.walk_links_from(root, &mut HashSet::new(), callback)
+    }
+
+    /// Walks the links of the `root` like `walk_links`, but skips the Cids already in `walked`
+    /// along with the blocks only reachable through them, and adds every Cid walked to it.
+    pub fn walk_links_from<F>(
+        &self,
+        root: &Cid,
+        walked: &mut HashSet<Cid>,
+        mut callback: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Cid) -> Result<(), Box<dyn StdError>>,
+    {
+        recurse_links(self.blockstore(), walked, root, &mut callback)
+    }
+}
+
+/// Recursively walks the links of an Ipld node, calling the callback with every Cid reachable
+/// from the `root` (including the `root` itself) as it is visited, before its links. Links
+/// already in `walked` are not traversed again.
+fn recurse_links<DB, F>(
+    db: &DB,
+    walked: &mut HashSet<Cid>,
+    root: &Cid,
+    callback: &mut F,
+) -> Result<(), Error>
+where
+    DB: BlockStore,
+    F: FnMut(&Cid) -> Result<(), Box<dyn StdError>>,
+{
+    if !walked.insert(*root) {
+        return Ok(());
+    }
+    callback(root).map_err(|e| Error::Other(e.to_string()))?;
+
+    // Skip identity and Filecoin commitment Cids
+    if root.codec != Codec::DagCBOR {
+        return Ok(());
+    }
+
+    let ipld: Ipld = db
+        .get(root)
+        .map_err(|e| Error::Other(e.to_string()))?
+        .ok_or_else(|| Error::Other(format!("Cid {} not found in blockstore", root)))?;
+
+    let mut links = Vec::new();
+    collect_links(&ipld, &mut links);
+    for link in links {
+        recurse_links(db, walked, &link, callback)?;
+    }
+
+    Ok(())
+}
+
+/// Collects all links contained in an Ipld node.
+fn collect_links(ipld: &Ipld, links: &mut Vec<Cid>) {
+    match ipld {
+        Ipld::Link(c) => links.push(*c),
+        Ipld::List(arr) => arr.iter().for_each(|item| collect_links(item, links)),
+        Ipld::Map(map) => map.values().for_each(|v| collect_links(v, links)),
+        _ => (),
+    }
 }
 
 /// Helper to ensure consistent Cid -> db key translation.
@@ -735,6 +1019,25 @@ where
     Ok(receipts.cloned())
 }
 
+/// Returns the receipts of all messages of the parent tipset of the given block header, in
+/// the order the messages were applied.
+pub fn get_parent_receipts<DB>(
+    db: &DB,
+    block_header: &BlockHeader,
+) -> Result<Vec<MessageReceipt>, Error>
+where
+    DB: BlockStore,
+{
+    let amt = Amt::load(block_header.message_receipts(), db)?;
+    let mut receipts = Vec::new();
+    amt.for_each(|_, receipt: &MessageReceipt| {
+        receipts.push(receipt.clone());
+        Ok(())
+    })
+    .map_err(|e| Error::State(e.to_string()))?;
+    Ok(receipts)
+}
+
 /// Returns the weight of provided tipset
 pub fn weight<DB>(db: &DB, ts: &Tipset) -> Result<BigInt, String>
 where
@@ -890,4 +1193,78 @@ mod tests {
         cs.mark_block_as_validated(&cid).unwrap();
         assert_eq!(cs.is_block_validated(&cid).unwrap(), true);
     }
+
+    fn child_tipset<DB: BlockStore>(
+        cs: &ChainStore<DB>,
+        parent: &Tipset,
+        miner: u64,
+    ) -> Arc<Tipset> {
+        let header = BlockHeader::builder()
+            .parents(parent.key().clone())
+            .epoch(parent.epoch() + 1)
+            .miner_address(Address::new_id(miner))
+            .build_and_validate()
+            .unwrap();
+        persist_objects(cs.blockstore(), &[header.clone()]).unwrap();
+        Arc::new(Tipset::new(vec![header]).unwrap())
+    }
+
+    async fn next_change(sub: &mut Subscriber<HeadChange>) -> (&'static str, TipsetKeys) {
+        match sub.next().await.unwrap() {
+            HeadChange::Current(ts) => ("current", ts.key().clone()),
+            HeadChange::Apply(ts) => ("apply", ts.key().clone()),
+            HeadChange::Revert(ts) => ("revert", ts.key().clone()),
+        }
+    }
+
+    #[test]
+    fn set_heaviest_reorg_changes() {
+        let cs = ChainStore::new(Arc::new(db::MemoryDB::default())).with_fork_length_threshold(2);
+        let gen = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        persist_objects(cs.blockstore(), &[gen.clone()]).unwrap();
+        let gen = Arc::new(Tipset::new(vec![gen]).unwrap());
+
+        let a1 = child_tipset(&cs, &gen, 1);
+        let a2 = child_tipset(&cs, &a1, 1);
+        let b1 = child_tipset(&cs, &gen, 2);
+        let b2 = child_tipset(&cs, &b1, 2);
+        let b3 = child_tipset(&cs, &b2, 2);
+
+        task::block_on(async {
+            let mut sub = cs.subscribe().await;
+            cs.set_heaviest_tipset(gen.clone()).await.unwrap();
+            assert_eq!(next_change(&mut sub).await, ("current", gen.key().clone()));
+
+            // Extending the head only applies the new tipsets.
+            cs.set_heaviest_tipset(a2.clone()).await.unwrap();
+            assert_eq!(next_change(&mut sub).await, ("apply", a1.key().clone()));
+            assert_eq!(next_change(&mut sub).await, ("apply", a2.key().clone()));
+
+            cs.set_heaviest_tipset(b3.clone()).await.unwrap();
+            assert_eq!(next_change(&mut sub).await, ("revert", a2.key().clone()));
+            assert_eq!(next_change(&mut sub).await, ("revert", a1.key().clone()));
+            assert_eq!(next_change(&mut sub).await, ("apply", b1.key().clone()));
+            assert_eq!(next_change(&mut sub).await, ("apply", b2.key().clone()));
+            assert_eq!(next_change(&mut sub).await, ("apply", b3.key().clone()));
+
+            // Switching back would revert 3 tipsets, over the threshold.
+            match cs.set_heaviest_tipset(a2.clone()).await {
+                Err(Error::ForkLengthExceeded(2)) => {}
+                res => panic!("unexpected result {:?}", res),
+            }
+            assert_eq!(cs.heaviest_tipset().await, Some(b3.clone()));
+
+            // Long apply runs only apply the new head.
+            let mut head = b3.clone();
+            for _ in 0..=APPLY_CHANGES_CAP {
+                head = child_tipset(&cs, &head, 2);
+            }
+            cs.set_heaviest_tipset(head.clone()).await.unwrap();
+            assert_eq!(next_change(&mut sub).await, ("apply", head.key().clone()));
+            assert_eq!(cs.heaviest_tipset().await, Some(head));
+        });
+    }
 }
@@ -35,6 +35,9 @@ pub enum Error {
     /// Amt error
     #[error("State error: {0}")]
     State(String),
+    /// Switching to a fork would revert more tipsets than the fork length threshold
+    #[error("Fork longer than threshold of {0} tipsets")]
+    ForkLengthExceeded(u64),
     /// Other chain error
     #[error("{0}")]
     Other(String),
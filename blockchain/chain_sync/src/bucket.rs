@@ -71,6 +71,12 @@ impl SyncBucketSet {
         Some(self.buckets.swap_remove(i))
     }
 
+    /// Removes the first SyncBucket related to the tipset from SyncBucketSet.
+    pub(crate) fn pop_related(&mut self, ts: &Tipset) -> Option<SyncBucket> {
+        let i = self.buckets.iter().position(|b| b.is_same_chain_as(ts))?;
+        Some(self.buckets.swap_remove(i))
+    }
+
     /// Returns true if tipset is related to any tipset in the bucket set.
     pub(crate) fn related_to_any(&self, ts: &Tipset) -> bool {
         for b in self.buckets.iter() {
@@ -170,4 +176,19 @@ mod tests {
         assert_eq!(set.buckets.len(), 2);
         assert_eq!(set.buckets[0].tips.len(), 2);
     }
+
+    #[test]
+    fn sync_bucket_pop_related() {
+        let mut set = SyncBucketSet::default();
+        let tipset1 = Arc::new(Tipset::new(vec![create_header(1, b"1", b"1")]).unwrap());
+        let tipset2 = Arc::new(Tipset::new(vec![create_header(2, b"2", b"2")]).unwrap());
+        set.insert(tipset1.clone());
+        set.insert(tipset2.clone());
+
+        let bucket = set.pop_related(&tipset2).unwrap();
+        assert_eq!(bucket.heaviest_tipset(), Some(tipset2.clone()));
+        assert!(set.pop_related(&tipset2).is_none());
+        assert_eq!(set.buckets.len(), 1);
+        assert!(set.related_to_any(&tipset1));
+    }
 }
@@ -4,27 +4,41 @@
 use super::peer_manager::PeerManager;
 use async_std::future;
 use async_std::sync::Sender;
+use async_std::task;
 use blocks::{FullTipset, Tipset, TipsetKeys};
 use cid::Cid;
 use encoding::de::DeserializeOwned;
 use forest_libp2p::{
     blocksync::{
-        BlockSyncRequest, BlockSyncResponse, CompactedMessages, TipsetBundle, BLOCKS, MESSAGES,
+        BlockSyncRequest, BlockSyncResponseStatus, CompactedMessages, TipsetBundle, BLOCKS,
+        MESSAGES,
     },
     hello::HelloRequest,
-    NetworkMessage,
+    Misbehaviour, NetworkMessage,
 };
 use futures::channel::oneshot::channel as oneshot_channel;
+use futures::future::{BoxFuture, FutureExt, Shared};
 use ipld_blockstore::BlockStore;
 use libp2p::core::PeerId;
 use log::{trace, warn};
+use std::cmp::min;
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime};
 
 /// Timeout for response from an RPC request
 const RPC_TIMEOUT: u64 = 20;
 
+/// Maximum number of peers a blocksync request is sent to before failing.
+const MAX_REQUEST_ATTEMPTS: usize = 5;
+
+/// Delay before retrying a failed blocksync request on another peer, doubled after each attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Header request which can be awaited by several sync workers.
+type SharedHeadersRequest = Shared<BoxFuture<'static, Result<Vec<Tipset>, String>>>;
+
 /// Context used in chain sync to handle network requests
 pub struct SyncNetworkContext<DB> {
     /// Channel to send network messages through p2p service
@@ -33,6 +47,9 @@ pub struct SyncNetworkContext<DB> {
     /// Manages peers to send requests to and updates request stats for the respective peers.
     peer_manager: Arc<PeerManager>,
     db: Arc<DB>,
+
+    /// Header requests in flight, keyed by the tipset the headers are requested from.
+    header_requests: Arc<Mutex<HashMap<TipsetKeys, SharedHeadersRequest>>>,
 }
 
 impl<DB> Clone for SyncNetworkContext<DB> {
@@ -41,6 +58,7 @@ impl<DB> Clone for SyncNetworkContext<DB> {
             network_send: self.network_send.clone(),
             peer_manager: self.peer_manager.clone(),
             db: self.db.clone(),
+            header_requests: self.header_requests.clone(),
         }
     }
 }
@@ -58,6 +76,7 @@ where
             network_send,
             peer_manager,
             db,
+            header_requests: Default::default(),
         }
     }
 
@@ -79,9 +98,55 @@ where
         tsk: &TipsetKeys,
         count: u64,
     ) -> Result<Vec<Tipset>, String> {
-        self.handle_blocksync_request(peer_id, tsk, count, BLOCKS)
+        self.handle_blocksync_request(peer_id, tsk, count, BLOCKS, false)
             .await
     }
+
+    /// Send a blocksync request for block headers to a set of shuffled peers. Concurrent requests
+    /// from the same tipset share the response of the first, so that a chain segment shared by
+    /// the targets of several sync workers is only downloaded once. At most the request window of
+    /// the peer is requested, and a shared request may have asked for fewer tipsets, so the
+    /// response may contain fewer than `count` tipsets. It never contains more.
+    pub async fn shared_blocksync_headers(
+        &self,
+        tsk: &TipsetKeys,
+        count: u64,
+    ) -> Result<Vec<Tipset>, String> {
+        let request = {
+            let mut requests = self.header_requests.lock().unwrap();
+            match requests.get(tsk) {
+                Some(request) => request.clone(),
+                None => {
+                    let ctx = self.clone();
+                    let start = tsk.clone();
+                    let request = async move {
+                        ctx.handle_blocksync_request(None, &start, count, BLOCKS, true)
+                            .await
+                    }
+                    .boxed()
+                    .shared();
+                    requests.insert(tsk.clone(), request.clone());
+                    request
+                }
+            }
+        };
+
+        let res = request.clone().await;
+        // Whichever waiter finishes first removes the request, unless a new request from the
+        // same tipset already replaced it. Any waiter may have been dropped before completion.
+        let mut requests = self.header_requests.lock().unwrap();
+        if requests
+            .get(tsk)
+            .map_or(false, |pending| Shared::ptr_eq(pending, &request))
+        {
+            requests.remove(tsk);
+        }
+        res.map(|mut tipsets| {
+            tipsets.truncate(count as usize);
+            tipsets
+        })
+    }
+
     /// Send a blocksync request for only messages (ignore block headers).
     /// If `peer_id` is `None`, requests will be sent to a set of shuffled peers.
     pub async fn blocksync_messages(
@@ -90,7 +155,7 @@ where
         tsk: &TipsetKeys,
         count: u64,
     ) -> Result<Vec<CompactedMessages>, String> {
-        self.handle_blocksync_request(peer_id, tsk, count, MESSAGES)
+        self.handle_blocksync_request(peer_id, tsk, count, MESSAGES, false)
             .await
     }
 
@@ -102,7 +167,7 @@ where
         tsk: &TipsetKeys,
     ) -> Result<FullTipset, String> {
         let mut fts = self
-            .handle_blocksync_request(peer_id, tsk, 1, BLOCKS | MESSAGES)
+            .handle_blocksync_request(peer_id, tsk, 1, BLOCKS | MESSAGES, false)
             .await?;
 
         if fts.len() != 1 {
@@ -151,41 +216,43 @@ where
     }
 
     /// Helper function to handle the peer retrieval if no peer supplied as well as the logging
-    /// and updating of the peer info in the `PeerManager`.
+    /// and updating of the peer info in the `PeerManager`. Without a peer supplied, peers are
+    /// tried by score, retrying failed requests on the next peer with an exponential backoff.
+    /// With `adaptive_window`, at most the request window of the peer is requested.
     async fn handle_blocksync_request<T>(
         &self,
         peer_id: Option<PeerId>,
         tsk: &TipsetKeys,
         request_len: u64,
         options: u64,
+        adaptive_window: bool,
     ) -> Result<Vec<T>, String>
     where
         T: TryFrom<TipsetBundle, Error = String>,
     {
-        let request = BlockSyncRequest {
-            start: tsk.cids().to_vec(),
-            request_len,
-            options,
-        };
-
         let global_pre_time = SystemTime::now();
-        let bs_res = match peer_id {
-            Some(id) => self.blocksync_request(id, request).await?.into_result()?,
+        let res = match peer_id {
+            Some(id) => {
+                let request = self
+                    .new_request(&id, tsk, request_len, options, adaptive_window)
+                    .await;
+                self.blocksync_request(id, request).await?
+            }
             None => {
-                let peers = self.peer_manager.top_peers_shuffled().await;
+                let peers = self.peer_manager.peers_by_score().await;
                 let mut res = None;
-                for p in peers.into_iter() {
-                    match self.blocksync_request(p.clone(), request.clone()).await {
-                        Ok(bs_res) => match bs_res.into_result() {
-                            Ok(r) => {
-                                res = Some(r);
-                                break;
-                            }
-                            Err(e) => {
-                                warn!("Failed blocksync response: {}", e);
-                                continue;
-                            }
-                        },
+                for (attempt, p) in peers.into_iter().take(MAX_REQUEST_ATTEMPTS).enumerate() {
+                    if attempt > 0 {
+                        task::sleep(RETRY_BACKOFF * 2u32.pow(attempt as u32 - 1)).await;
+                    }
+                    let request = self
+                        .new_request(&p, tsk, request_len, options, adaptive_window)
+                        .await;
+                    match self.blocksync_request(p.clone(), request).await {
+                        Ok(r) => {
+                            res = Some(r);
+                            break;
+                        }
                         Err(e) => {
                             warn!("Failed blocksync request to peer {:?}: {}", p, e);
                             continue;
@@ -202,18 +269,45 @@ where
             Err(e) => warn!("logged time less than before request: {}", e),
         }
 
-        Ok(bs_res)
+        Ok(res)
+    }
+
+    /// Creates a blocksync request to the peer, limited to the peer's request window if
+    /// `adaptive_window` is set.
+    async fn new_request(
+        &self,
+        peer_id: &PeerId,
+        tsk: &TipsetKeys,
+        request_len: u64,
+        options: u64,
+        adaptive_window: bool,
+    ) -> BlockSyncRequest {
+        let request_len = if adaptive_window {
+            min(request_len, self.peer_manager.request_window(peer_id).await)
+        } else {
+            request_len
+        };
+        BlockSyncRequest {
+            start: tsk.cids().to_vec(),
+            request_len,
+            options,
+        }
     }
 
-    /// Send a blocksync request to the network and await response.
-    async fn blocksync_request(
+    /// Send a blocksync request to the network and await response. Responses with an error
+    /// status are logged as failures of the peer, and responses with invalid data as invalid.
+    async fn blocksync_request<T>(
         &self,
         peer_id: PeerId,
         request: BlockSyncRequest,
-    ) -> Result<BlockSyncResponse, String> {
+    ) -> Result<Vec<T>, String>
+    where
+        T: TryFrom<TipsetBundle, Error = String>,
+    {
         trace!("Sending BlockSync Request {:?}", request);
 
         let req_pre_time = SystemTime::now();
+        let request_len = request.request_len;
 
         let (tx, rx) = oneshot_channel();
         self.network_send
@@ -228,20 +322,63 @@ where
         let res_duration = SystemTime::now()
             .duration_since(req_pre_time)
             .unwrap_or_default();
-        match res {
-            Ok(Ok(bs_res)) => {
-                self.peer_manager.log_success(&peer_id, res_duration).await;
-                Ok(bs_res)
-            }
+        let bs_res = match res {
+            Ok(Ok(bs_res)) => bs_res,
             Ok(Err(e)) => {
                 self.peer_manager.log_failure(&peer_id, res_duration).await;
-                Err(format!("RPC error: {}", e.to_string()))
+                return Err(format!("RPC error: {}", e.to_string()));
             }
             Err(_) => {
                 self.peer_manager.log_failure(&peer_id, res_duration).await;
-                Err("Connection timed out".to_string())
+                return Err("Connection timed out".to_string());
             }
+        };
+
+        if bs_res.status != BlockSyncResponseStatus::Success
+            && bs_res.status != BlockSyncResponseStatus::PartialResponse
+        {
+            self.peer_manager.log_failure(&peer_id, res_duration).await;
+            return Err(format!(
+                "Failed blocksync response: Status {:?}: {}",
+                bs_res.status, bs_res.message
+            ));
         }
+        if bs_res.chain.len() as u64 > request_len {
+            self.log_invalid_response(peer_id, res_duration).await;
+            return Err(format!(
+                "Invalid blocksync response: {} tipsets returned for {} requested",
+                bs_res.chain.len(),
+                request_len
+            ));
+        }
+        match bs_res.into_result() {
+            Ok(chain) => {
+                self.peer_manager.log_success(&peer_id, res_duration).await;
+                Ok(chain)
+            }
+            Err(e) => {
+                self.log_invalid_response(peer_id, res_duration).await;
+                Err(format!("Invalid blocksync response: {}", e))
+            }
+        }
+    }
+
+    /// Logs an invalid blocksync response of the peer, and reports the peer to the network.
+    async fn log_invalid_response(&self, peer_id: PeerId, dur: Duration) {
+        self.peer_manager.log_invalid_response(&peer_id, dur).await;
+        self.report_peer(peer_id, Misbehaviour::InvalidBlockSyncResponse)
+            .await;
+    }
+
+    /// Reports a misbehaving peer to the network, which bans the peer once its penalties
+    /// reach the ban threshold.
+    pub async fn report_peer(&self, peer_id: PeerId, misbehaviour: Misbehaviour) {
+        self.network_send
+            .send(NetworkMessage::ReportPeer {
+                peer_id,
+                misbehaviour,
+            })
+            .await;
     }
 
     /// Send a hello request to the network (does not await response)
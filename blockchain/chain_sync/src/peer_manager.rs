@@ -6,8 +6,7 @@ use blocks::Tipset;
 use libp2p::core::PeerId;
 use log::{debug, trace, warn};
 use rand::seq::SliceRandom;
-use smallvec::SmallVec;
-use std::cmp::Ordering;
+use std::cmp::{max, min, Ordering};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
@@ -22,16 +21,32 @@ const LOCAL_INV_ALPHA: u32 = 5;
 /// Global duration multiplier, affects duration delta change.
 const GLOBAL_INV_ALPHA: u32 = 20;
 
+/// Multiplier of the rate of invalid responses of a peer, penalizing invalid data more than
+/// requests which failed or timed out.
+const INVALID_RESPONSE_MUL: f64 = 4.0;
+
+/// Number of tipsets requested at once from a new peer.
+const INITIAL_REQUEST_WINDOW: u64 = 10;
+/// Minimum number of tipsets requested at once from a peer.
+const MIN_REQUEST_WINDOW: u64 = 1;
+/// Maximum number of tipsets requested at once from a peer.
+pub(crate) const MAX_REQUEST_WINDOW: u64 = 200;
+
 #[derive(Debug)]
 struct PeerInfo {
     /// Head tipset received from hello message.
     head: Option<Arc<Tipset>>,
     /// Number of successful requests.
     successes: u32,
-    /// Number of failed requests.
+    /// Number of failed requests, not counting those answered with invalid data.
     failures: u32,
+    /// Number of requests which were answered with invalid data.
+    invalid_responses: u32,
     /// Average response time for the peer.
     average_time: Duration,
+    /// Number of tipsets requested at once from the peer. Doubled after each successful request
+    /// and halved after each failed request.
+    window: u64,
 }
 
 impl PeerInfo {
@@ -40,8 +55,42 @@ impl PeerInfo {
             head,
             successes: 0,
             failures: 0,
+            invalid_responses: 0,
             average_time: Default::default(),
+            window: INITIAL_REQUEST_WINDOW,
+        }
+    }
+
+    /// Scores the peer by the expected cost of a request, based on its latency, failure rate and
+    /// rate of invalid responses. Lower is better.
+    fn cost(&self, global_average: Duration) -> f64 {
+        let requests = self.successes + self.failures + self.invalid_responses;
+        if requests == 0 {
+            // There have been no failures or successes
+            return global_average.as_secs_f64() * NEW_PEER_MUL;
         }
+        let fail_rate = f64::from(self.failures) / f64::from(requests);
+        let invalid_rate = f64::from(self.invalid_responses) / f64::from(requests);
+        self.average_time.as_secs_f64()
+            + (fail_rate + INVALID_RESPONSE_MUL * invalid_rate) * global_average.as_secs_f64()
+    }
+
+    fn log_success(&mut self, dur: Duration) {
+        self.successes += 1;
+        self.window = min(self.window * 2, MAX_REQUEST_WINDOW);
+        log_time(self, dur);
+    }
+
+    fn log_failure(&mut self, dur: Duration) {
+        self.failures += 1;
+        self.window = max(self.window / 2, MIN_REQUEST_WINDOW);
+        log_time(self, dur);
+    }
+
+    fn log_invalid_response(&mut self, dur: Duration) {
+        self.invalid_responses += 1;
+        self.window = max(self.window / 2, MIN_REQUEST_WINDOW);
+        log_time(self, dur);
     }
 }
 
@@ -73,23 +122,14 @@ impl PeerManager {
         self.full_peers.read().await.is_empty()
     }
 
-    /// Sort peers based on a score function with the success rate and latency of requests.
+    /// Sort peers based on a score function with the success rate, invalid responses and latency
+    /// of requests.
     pub(crate) async fn sorted_peers(&self) -> Vec<PeerId> {
         let peer_lk = self.full_peers.read().await;
         let average_time = self.avg_global_time.read().await;
         let mut peers: Vec<_> = peer_lk
             .iter()
-            .map(|(p, info)| {
-                let cost = if (info.successes + info.failures) > 0 {
-                    // Calculate cost based on fail rate and latency
-                    let fail_rate = f64::from(info.failures) / f64::from(info.successes);
-                    info.average_time.as_secs_f64() + fail_rate * average_time.as_secs_f64()
-                } else {
-                    // There have been no failures or successes
-                    average_time.as_secs_f64() * NEW_PEER_MUL
-                };
-                (p, cost)
-            })
+            .map(|(p, info)| (p, info.cost(*average_time)))
             .collect();
 
         // Unstable sort because hashmap iter order doesn't need to be preserved.
@@ -97,22 +137,24 @@ impl PeerManager {
         peers.into_iter().map(|(p, _)| p).cloned().collect()
     }
 
-    /// Return shuffled slice of ordered peers from the peer manager. Ordering is based
-    /// on failure rate and latency of the peer.
-    pub async fn top_peers_shuffled(&self) -> SmallVec<[PeerId; SHUFFLE_PEERS_PREFIX]> {
-        let mut peers: SmallVec<_> = self
-            .sorted_peers()
-            .await
-            .into_iter()
-            .take(SHUFFLE_PEERS_PREFIX)
-            .collect();
-
-        // Shuffle top peers, to avoid sending all requests to same predictable peer.
-        peers.shuffle(&mut rand::thread_rng());
-
+    /// Returns all peers ordered by score, with the top peers shuffled to avoid sending all
+    /// requests to the same peer.
+    pub async fn peers_by_score(&self) -> Vec<PeerId> {
+        let mut peers = self.sorted_peers().await;
+        let top = min(peers.len(), SHUFFLE_PEERS_PREFIX);
+        peers[..top].shuffle(&mut rand::thread_rng());
         peers
     }
 
+    /// Returns the number of tipsets to request at once from the peer.
+    pub async fn request_window(&self, peer: &PeerId) -> u64 {
+        self.full_peers
+            .read()
+            .await
+            .get(peer)
+            .map_or(INITIAL_REQUEST_WINDOW, |p| p.window)
+    }
+
     /// Retrieves all head tipsets from current peer set.
     pub async fn get_peer_heads(&self) -> Vec<Arc<Tipset>> {
         self.full_peers
@@ -142,10 +184,7 @@ impl PeerManager {
     pub async fn log_success(&self, peer: &PeerId, dur: Duration) {
         debug!("logging success for {:?}", peer);
         match self.full_peers.write().await.get_mut(peer) {
-            Some(p) => {
-                p.successes += 1;
-                log_time(p, dur);
-            }
+            Some(p) => p.log_success(dur),
             None => warn!("log success called for peer not in peer manager ({})", peer),
         }
     }
@@ -154,18 +193,26 @@ impl PeerManager {
     pub async fn log_failure(&self, peer: &PeerId, dur: Duration) {
         debug!("logging failure for {:?}", peer);
         match self.full_peers.write().await.get_mut(peer) {
-            Some(p) => {
-                p.failures += 1;
-                log_time(p, dur);
-            }
-            None => warn!("log success called for peer not in peer manager ({})", peer),
+            Some(p) => p.log_failure(dur),
+            None => warn!("log failure called for peer not in peer manager ({})", peer),
+        }
+    }
+
+    /// Logs an invalid response for the given peer, and updates the average request duration.
+    pub async fn log_invalid_response(&self, peer: &PeerId, dur: Duration) {
+        debug!("logging invalid response for {:?}", peer);
+        match self.full_peers.write().await.get_mut(peer) {
+            Some(p) => p.log_invalid_response(dur),
+            None => warn!(
+                "log invalid response called for peer not in peer manager ({})",
+                peer
+            ),
         }
     }
 
-    /// Removes a peer from the set and returns true if the value was present previously
-    // TODO peers should only be removed after certain criteria is hit, this doesn't matter as much
-    // because a faulty peer will just be ignored.
-    pub async fn _remove_peer(&self, peer_id: &PeerId) -> bool {
+    /// Removes a peer from the set and returns true if the value was present previously.
+    /// Peers are removed once banned for misbehaving.
+    pub async fn remove_peer(&self, peer_id: &PeerId) -> bool {
         self.full_peers.write().await.remove(peer_id).is_some()
     }
 
@@ -186,3 +233,67 @@ fn log_time(info: &mut PeerInfo, dur: Duration) {
         info.average_time += delta
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+
+    #[test]
+    fn request_window_adapts() {
+        let pm = PeerManager::default();
+        let peer = PeerId::random();
+        task::block_on(async {
+            pm.update_peer_head(peer.clone(), None).await;
+            assert_eq!(pm.request_window(&peer).await, INITIAL_REQUEST_WINDOW);
+
+            pm.log_success(&peer, Duration::from_millis(10)).await;
+            assert_eq!(pm.request_window(&peer).await, INITIAL_REQUEST_WINDOW * 2);
+            for _ in 0..10 {
+                pm.log_success(&peer, Duration::from_millis(10)).await;
+            }
+            assert_eq!(pm.request_window(&peer).await, MAX_REQUEST_WINDOW);
+
+            pm.log_failure(&peer, Duration::from_millis(10)).await;
+            assert_eq!(pm.request_window(&peer).await, MAX_REQUEST_WINDOW / 2);
+            for _ in 0..10 {
+                pm.log_invalid_response(&peer, Duration::from_millis(10))
+                    .await;
+            }
+            assert_eq!(pm.request_window(&peer).await, MIN_REQUEST_WINDOW);
+        });
+    }
+
+    #[test]
+    fn peers_sorted_by_score() {
+        let pm = PeerManager::default();
+        let (fast, failing, invalid) = (PeerId::random(), PeerId::random(), PeerId::random());
+        task::block_on(async {
+            for p in &[&fast, &failing, &invalid] {
+                pm.update_peer_head((*p).clone(), None).await;
+            }
+            pm.log_global_success(Duration::from_millis(100)).await;
+
+            pm.log_success(&fast, Duration::from_millis(50)).await;
+            pm.log_success(&failing, Duration::from_millis(50)).await;
+            pm.log_failure(&failing, Duration::from_millis(50)).await;
+            pm.log_success(&invalid, Duration::from_millis(50)).await;
+            pm.log_invalid_response(&invalid, Duration::from_millis(50))
+                .await;
+
+            assert_eq!(pm.sorted_peers().await, vec![fast, failing, invalid]);
+        });
+    }
+
+    #[test]
+    fn invalid_responses_not_counted_as_failures() {
+        let mut info = PeerInfo::new(None);
+        let global_average = Duration::from_millis(100);
+        info.log_success(Duration::from_millis(50));
+        info.log_invalid_response(Duration::from_millis(50));
+
+        assert_eq!(info.failures, 0);
+        let expected = 0.05 + INVALID_RESPONSE_MUL * 0.5 * 0.1;
+        assert!((info.cost(global_average) - expected).abs() < 1e-9);
+    }
+}
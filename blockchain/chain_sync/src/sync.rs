@@ -10,15 +10,16 @@ use super::sync_state::SyncState;
 use super::sync_worker::SyncWorker;
 use super::{Error, SyncNetworkContext};
 use amt::Amt;
-use async_std::sync::{channel, Receiver, RwLock, Sender};
+use async_std::sync::{channel, Receiver, RwLock, Sender, TrySendError};
 use async_std::task::{self, JoinHandle};
 use beacon::Beacon;
 use blocks::{Block, FullTipset, Tipset, TipsetKeys, TxMeta};
 use chain::ChainStore;
 use cid::{Cid, Code::Blake2b256};
+use clock::ChainEpoch;
 use encoding::{Cbor, Error as EncodingError};
 use fil_types::verifier::ProofVerifier;
-use forest_libp2p::{hello::HelloRequest, NetworkEvent, NetworkMessage};
+use forest_libp2p::{hello::HelloRequest, Misbehaviour, NetworkEvent, NetworkMessage};
 use futures::future::try_join_all;
 use futures::select;
 use futures::stream::StreamExt;
@@ -28,6 +29,7 @@ use log::{debug, info, trace, warn};
 use message::{SignedMessage, UnsignedMessage};
 use message_pool::{MessagePool, Provider};
 use state_manager::StateManager;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
@@ -36,14 +38,18 @@ use std::sync::Arc;
 // on the RPC API thread and mutated on this thread.
 type WorkerState = Arc<RwLock<Vec<Arc<RwLock<SyncState>>>>>;
 
+/// Maximum number of epochs a new head can be ahead of the heaviest tipset to be followed. Heads
+/// further ahead are synced by the sync workers, catching up with the chain.
+const FOLLOW_MAX_EPOCH_GAP: ChainEpoch = 10;
+
 #[derive(Debug, PartialEq)]
 enum ChainSyncState {
     /// Bootstrapping peers before starting sync.
     Bootstrap,
-    /// Syncing chain with BlockSync protocol.
+    /// Catching up with the heads of peers with BlockSync protocol.
     Initial,
     /// Following chain with blocks received over gossipsub.
-    _Follow,
+    Follow,
 }
 
 /// Struct that handles the ChainSync logic. This handles incoming network events such as
@@ -66,6 +72,8 @@ pub struct ChainSyncer<DB, TBeacon, V, M> {
     sync_queue: SyncBucketSet,
     /// Represents tipsets related to ones already being synced to avoid duplicate work.
     active_sync_tipsets: SyncBucketSet,
+    /// Targets currently assigned to a sync worker, each target is only synced by one worker.
+    active_syncs: HashMap<TipsetKeys, Arc<Tipset>>,
 
     /// Represents next tipset to be synced.
     next_sync_target: Option<SyncBucket>,
@@ -83,6 +91,10 @@ pub struct ChainSyncer<DB, TBeacon, V, M> {
     ///  incoming network events to be handled by syncer
     net_handler: Receiver<NetworkEvent>,
 
+    /// Tipsets to be synced by the follower while following the chain
+    follow_tx: Sender<(PeerId, Arc<Tipset>)>,
+    follow_rx: Receiver<(PeerId, Arc<Tipset>)>,
+
     /// Proof verification implementation.
     verifier: PhantomData<V>,
 
@@ -109,6 +121,7 @@ where
             Default::default(),
             state_manager.blockstore_cloned(),
         );
+        let (follow_tx, follow_rx) = channel(10);
 
         Ok(Self {
             state: ChainSyncState::Bootstrap,
@@ -119,8 +132,11 @@ where
             state_manager,
             bad_blocks: Arc::new(BadBlockCache::default()),
             net_handler: network_rx,
+            follow_tx,
+            follow_rx,
             sync_queue: SyncBucketSet::default(),
             active_sync_tipsets: SyncBucketSet::default(),
+            active_syncs: HashMap::new(),
             next_sync_target: None,
             verifier: Default::default(),
             mpool,
@@ -137,29 +153,30 @@ where
         self.worker_state.clone()
     }
 
-    /// Spawns a network handler and begins the syncing process.
+    /// Spawns a network handler and begins the syncing process, with `num_workers` workers
+    /// syncing separate targets in parallel.
     pub async fn start(mut self, num_workers: usize) {
-        let (worker_tx, worker_rx) = channel(5);
+        let num_workers = num_workers.max(1);
+        let (worker_tx, worker_rx) = channel(num_workers);
+        let (done_tx, done_rx) = channel(num_workers);
         for _ in 0..num_workers {
-            self.spawn_worker(worker_rx.clone()).await;
+            self.spawn_worker(worker_rx.clone(), done_tx.clone()).await;
         }
+        let follower = self.new_worker().await;
+        follower
+            .spawn_follower(self.follow_rx.clone(), FOLLOW_MAX_EPOCH_GAP)
+            .await;
 
         // Channels to handle fetching hello tipsets in separate task and return tipset.
         let (new_ts_tx, new_ts_rx) = channel(10);
 
         let mut fused_handler = self.net_handler.clone().fuse();
         let mut fused_inform_channel = new_ts_rx.fuse();
+        let mut fused_done_channel = done_rx.fuse();
 
         loop {
-            // TODO would be ideal if this is a future attached to the select
-            if worker_tx.is_empty() {
-                if let Some(tar) = self.next_sync_target.take() {
-                    if let Some(ts) = tar.heaviest_tipset() {
-                        self.active_sync_tipsets.insert(ts.clone());
-                        worker_tx.send(ts).await;
-                    }
-                }
-            }
+            self.update_state().await;
+            self.dispatch_sync_targets(&worker_tx, num_workers).await;
             select! {
                 network_event = fused_handler.next() => match network_event {
                     Some(NetworkEvent::HelloRequest { request, channel }) => {
@@ -185,6 +202,10 @@ where
                             .await;
                         });
                     }
+                    Some(NetworkEvent::PeerBanned { peer_id }) => {
+                        debug!("Removing banned peer {}", peer_id);
+                        self.network.peer_manager().remove_peer(&peer_id).await;
+                    }
                     Some(NetworkEvent::PeerDialed { peer_id }) => {
                         let heaviest = self.state_manager.chain_store().heaviest_tipset().await.unwrap();
                         self.network
@@ -255,6 +276,10 @@ where
                         }
                     }
                     None => break,
+                },
+                done_target = fused_done_channel.next() => match done_target {
+                    Some(target) => self.complete_sync(&target).await,
+                    None => break,
                 }
             }
         }
@@ -280,7 +305,16 @@ where
     }
 
     /// Spawns a new sync worker and pushes the state to the `ChainSyncer`
-    async fn spawn_worker(&mut self, channel: Receiver<Arc<Tipset>>) -> JoinHandle<()> {
+    async fn spawn_worker(
+        &mut self,
+        channel: Receiver<Arc<Tipset>>,
+        done_channel: Sender<Arc<Tipset>>,
+    ) -> JoinHandle<()> {
+        self.new_worker().await.spawn(channel, done_channel).await
+    }
+
+    /// Creates a new sync worker and pushes the state to the `ChainSyncer`
+    async fn new_worker(&mut self) -> SyncWorker<DB, TBeacon, V> {
         let state = Arc::new(RwLock::new(SyncState::default()));
 
         // push state to managed states in Syncer.
@@ -294,8 +328,6 @@ where
             bad_blocks: self.bad_blocks.clone(),
             verifier: PhantomData::<V>::default(),
         }
-        .spawn(channel)
-        .await
     }
 
     /// informs the syncer about a new potential tipset
@@ -311,6 +343,9 @@ where
         for block in ts.blocks() {
             if let Some(bad) = self.bad_blocks.peek(block.cid()).await {
                 warn!("Bad block detected, cid: {:?}", bad);
+                self.network
+                    .report_peer(peer, Misbehaviour::InvalidBlock)
+                    .await;
                 return Err(Error::Other("Block marked as bad".to_string()));
             }
         }
@@ -328,7 +363,12 @@ where
         if candidate_ts {
             // Check message meta after all other checks (expensive)
             for block in ts.blocks() {
-                self.validate_msg_meta(block)?;
+                if let Err(e) = self.validate_msg_meta(block) {
+                    self.network
+                        .report_peer(peer, Misbehaviour::InvalidBlock)
+                        .await;
+                    return Err(e);
+                }
             }
             self.set_peer_head(peer, Arc::new(ts.to_tipset())).await;
         }
@@ -339,20 +379,70 @@ where
     async fn set_peer_head(&mut self, peer: PeerId, ts: Arc<Tipset>) {
         self.network
             .peer_manager()
-            .update_peer_head(peer, Some(Arc::clone(&ts)))
+            .update_peer_head(peer.clone(), Some(Arc::clone(&ts)))
             .await;
 
-        // Only update target on initial sync
-        if self.state == ChainSyncState::Bootstrap {
-            if let Some(best_target) = self.select_sync_target().await {
-                self.schedule_tipset(best_target).await;
+        match self.state {
+            // Only update target on initial sync
+            ChainSyncState::Bootstrap => {
+                if let Some(best_target) = self.select_sync_target().await {
+                    self.schedule_tipset(best_target).await;
+                    self.state = ChainSyncState::Initial;
+                    return;
+                }
+            }
+            ChainSyncState::Initial => (),
+            ChainSyncState::Follow => {
+                if self.within_follow_distance(&ts).await {
+                    self.follow_head(peer, ts);
+                    return;
+                }
+                info!(
+                    "Fell behind by more than {} epochs, catching up with chain",
+                    FOLLOW_MAX_EPOCH_GAP
+                );
                 self.state = ChainSyncState::Initial;
-                return;
             }
         }
         self.schedule_tipset(ts).await;
     }
 
+    /// Passes a head received from the peer to the follower without waiting on it. If the
+    /// follower is behind, the oldest queued head is dropped so that it moves on to the newest
+    /// heads.
+    fn follow_head(&self, peer: PeerId, ts: Arc<Tipset>) {
+        if let Err(TrySendError::Full(head)) = self.follow_tx.try_send((peer, ts)) {
+            let _ = self.follow_rx.try_recv();
+            let _ = self.follow_tx.try_send(head);
+        }
+    }
+
+    /// Switches from catching up with the heads of peers to following the chain once caught up.
+    async fn update_state(&mut self) {
+        if self.state == ChainSyncState::Initial && self.is_caught_up().await {
+            info!("Caught up with the heads of peers, following chain");
+            self.state = ChainSyncState::Follow;
+        }
+    }
+
+    /// Returns true if the heaviest tipset is close enough to the highest head of peers to
+    /// follow the chain.
+    async fn is_caught_up(&self) -> bool {
+        match self.select_sync_target().await {
+            Some(target) => self.within_follow_distance(&target).await,
+            None => false,
+        }
+    }
+
+    /// Returns true if the tipset is at most `FOLLOW_MAX_EPOCH_GAP` epochs ahead of the
+    /// heaviest tipset.
+    async fn within_follow_distance(&self, ts: &Tipset) -> bool {
+        match self.state_manager.chain_store().heaviest_tipset().await {
+            Some(heaviest) => ts.epoch() - heaviest.epoch() <= FOLLOW_MAX_EPOCH_GAP,
+            None => false,
+        }
+    }
+
     /// Selects max sync target from current peer set
     async fn select_sync_target(&self) -> Option<Arc<Tipset>> {
         // Retrieve all peer heads from peer manager
@@ -364,45 +454,63 @@ where
     async fn schedule_tipset(&mut self, tipset: Arc<Tipset>) {
         debug!("Scheduling incoming tipset to sync: {:?}", tipset.cids());
 
-        let mut related_to_active = false;
-        for act_state in self.worker_state.read().await.iter() {
-            if let Some(target) = act_state.read().await.target() {
-                if target == &tipset {
-                    return;
-                }
-
-                if tipset.parents() == target.key() {
-                    related_to_active = true;
-                }
-            }
-        }
-
-        // Check if related to active tipset buckets.
-        if !related_to_active && self.active_sync_tipsets.related_to_any(tipset.as_ref()) {
-            related_to_active = true;
+        if self.active_syncs.contains_key(tipset.key()) {
+            return;
         }
 
+        // Tipsets on the chain of an active target are synced once that target is done.
+        let related_to_active = self
+            .active_syncs
+            .values()
+            .any(|target| tipset.parents() == target.key())
+            || self.active_sync_tipsets.related_to_any(tipset.as_ref());
         if related_to_active {
             self.active_sync_tipsets.insert(tipset);
             return;
         }
 
-        // if next_sync_target is from same chain as incoming tipset add it to be synced next
-        if let Some(tar) = &mut self.next_sync_target {
-            if tar.is_same_chain_as(&tipset) {
-                tar.add(tipset);
+        match &mut self.next_sync_target {
+            // if next_sync_target is from same chain as incoming tipset add it to be synced next
+            Some(tar) if tar.is_same_chain_as(&tipset) => tar.add(tipset),
+            _ => {
+                // add incoming tipset to queue to by synced later
+                self.sync_queue.insert(tipset);
+                // update next sync target if none
+                if self.next_sync_target.is_none() {
+                    self.next_sync_target = self.sync_queue.pop();
+                }
             }
-        } else {
-            // add incoming tipset to queue to by synced later
-            self.sync_queue.insert(tipset);
-            // update next sync target if none
-            if self.next_sync_target.is_none() {
-                if let Some(target_bucket) = self.sync_queue.pop() {
-                    self.next_sync_target = Some(target_bucket);
+        }
+    }
+
+    /// Assigns the next sync targets to idle workers, until all `num_workers` workers are busy.
+    async fn dispatch_sync_targets(&mut self, worker_tx: &Sender<Arc<Tipset>>, num_workers: usize) {
+        while self.active_syncs.len() < num_workers {
+            let target = match self.next_sync_target.take() {
+                Some(target) => target,
+                None => break,
+            };
+            self.next_sync_target = self.sync_queue.pop();
+            if let Some(ts) = target.heaviest_tipset() {
+                self.active_syncs.insert(ts.key().clone(), ts.clone());
+                worker_tx.send(ts).await;
+            }
+        }
+    }
+
+    /// Releases a target synced by a worker, scheduling the heaviest tipset received on its
+    /// chain while it was being synced.
+    async fn complete_sync(&mut self, target: &Tipset) {
+        self.active_syncs.remove(target.key());
+        if let Some(bucket) = self.active_sync_tipsets.pop_related(target) {
+            if let Some(ts) = bucket.heaviest_tipset() {
+                if ts.weight() > target.weight() {
+                    self.schedule_tipset(ts).await;
                 }
             }
         }
     }
+
     /// Validates message root from header matches message root generated from the
     /// bls and secp messages contained in the passed in block and stores them in a key-value store
     fn validate_msg_meta(&self, block: &Block) -> Result<(), Error> {
@@ -495,18 +603,23 @@ fn cids_from_messages<T: Cbor>(messages: &[T]) -> Result<Vec<Cid>, EncodingError
 #[cfg(test)]
 mod tests {
     use super::*;
+    use actor::{make_map, power, POWER_ACTOR_CODE_ID, STORAGE_POWER_ACTOR_ADDR};
+    use async_std::future::timeout;
     use async_std::sync::channel;
     use async_std::sync::Sender;
     use async_std::task;
     use beacon::MockBeacon;
+    use blocks::{BlockHeader, ElectionProof};
     use db::MemoryDB;
     use fil_types::verifier::MockVerifier;
     use forest_libp2p::NetworkEvent;
     use message_pool::{test_provider::TestApi, MessagePool};
     use state_manager::StateManager;
+    use state_tree::StateTree;
     use std::sync::Arc;
     use std::time::Duration;
     use test_utils::{construct_dummy_header, construct_messages};
+    use vm::ActorState;
 
     fn chain_syncer_setup(
         db: Arc<MemoryDB>,
@@ -517,14 +630,15 @@ mod tests {
     ) {
         let chain_store = Arc::new(ChainStore::new(db.clone()));
         let test_provider = TestApi::default();
+        let (local_sender, test_receiver) = channel(20);
         let mpool = task::block_on(MessagePool::new(
             test_provider,
             "test".to_string(),
+            local_sender.clone(),
             Default::default(),
         ))
         .unwrap();
         let mpool = Arc::new(mpool);
-        let (local_sender, test_receiver) = channel(20);
         let (event_sender, event_receiver) = channel(20);
 
         let gen = construct_dummy_header();
@@ -557,6 +671,187 @@ mod tests {
         let _chain_syncer = chain_syncer_setup(db);
     }
 
+    fn dummy_tipset(parents: TipsetKeys, weight: u64, miner: u64) -> Arc<Tipset> {
+        let header = blocks::BlockHeader::builder()
+            .parents(parents)
+            .weight(weight.into())
+            .miner_address(address::Address::new_id(miner))
+            .build_and_validate()
+            .unwrap();
+        Arc::new(Tipset::new(vec![header]).unwrap())
+    }
+
+    fn dummy_head(epoch: ChainEpoch) -> Arc<Tipset> {
+        let header = BlockHeader::builder()
+            .epoch(epoch)
+            .miner_address(address::Address::new_id(1))
+            .build_and_validate()
+            .unwrap();
+        Arc::new(Tipset::new(vec![header]).unwrap())
+    }
+
+    #[test]
+    fn follow_state_transitions() {
+        let db = Arc::new(MemoryDB::default());
+        let (mut cs, _, _) = chain_syncer_setup(db);
+        let peer = PeerId::random();
+
+        task::block_on(async {
+            cs.state_manager
+                .chain_store()
+                .set_heaviest_tipset(cs.genesis.clone())
+                .await
+                .unwrap();
+
+            // The head of the first peer is synced to catch up with the chain.
+            let target = dummy_head(5);
+            cs.set_peer_head(peer.clone(), target.clone()).await;
+            assert_eq!(cs.state, ChainSyncState::Initial);
+            let next = cs.next_sync_target.as_ref().unwrap();
+            assert_eq!(next.heaviest_tipset(), Some(target));
+
+            // Heads close to the heaviest tipset are passed to the follower.
+            cs.update_state().await;
+            assert_eq!(cs.state, ChainSyncState::Follow);
+            let near = dummy_head(FOLLOW_MAX_EPOCH_GAP);
+            cs.set_peer_head(peer.clone(), near.clone()).await;
+            assert_eq!(cs.follow_rx.try_recv().unwrap(), (peer.clone(), near));
+
+            // Heads too far ahead are synced by the workers again.
+            let far = dummy_head(FOLLOW_MAX_EPOCH_GAP + 1);
+            cs.set_peer_head(peer, far.clone()).await;
+            assert_eq!(cs.state, ChainSyncState::Initial);
+            assert!(cs.follow_rx.is_empty());
+            assert!(cs.sync_queue.related_to_any(&far));
+
+            cs.update_state().await;
+            assert_eq!(cs.state, ChainSyncState::Initial);
+        });
+    }
+
+    #[test]
+    fn follow_keeps_newest_heads() {
+        let db = Arc::new(MemoryDB::default());
+        let (mut cs, _, _) = chain_syncer_setup(db);
+        let peer = PeerId::random();
+
+        task::block_on(async {
+            cs.state_manager
+                .chain_store()
+                .set_heaviest_tipset(cs.genesis.clone())
+                .await
+                .unwrap();
+            cs.state = ChainSyncState::Follow;
+
+            // One more head than the follower channel holds, without the follower running.
+            let heads: Vec<_> = (0..=FOLLOW_MAX_EPOCH_GAP).map(dummy_head).collect();
+            for head in heads.iter() {
+                cs.set_peer_head(peer.clone(), head.clone()).await;
+            }
+
+            let followed: Vec<_> = std::iter::from_fn(|| cs.follow_rx.try_recv().ok())
+                .map(|(_, head)| head)
+                .collect();
+            assert_eq!(followed, heads[1..].to_vec());
+        });
+    }
+
+    #[test]
+    fn followed_head_becomes_heaviest() {
+        let db = Arc::new(MemoryDB::default());
+        let (mut cs, _, _) = chain_syncer_setup(db.clone());
+        let peer = PeerId::random();
+
+        // Tipset weights are only defined with power in the parent state.
+        let claims = make_map::<_, power::Claim>(db.as_ref()).flush().unwrap();
+        let mut power_state = power::State::new(claims, claims);
+        power_state.total_quality_adj_power = 1.into();
+        let power_head = db.put(&power_state, Blake2b256).unwrap();
+        let mut tree = StateTree::new(db.as_ref());
+        tree.set_actor(
+            &*STORAGE_POWER_ACTOR_ADDR,
+            ActorState::new(*POWER_ACTOR_CODE_ID, power_head, 0.into(), 0),
+        )
+        .unwrap();
+        let state_root = tree.flush().unwrap();
+        let msg_root = compute_msg_meta(db.as_ref(), &[], &[]).unwrap();
+
+        let header = |parents: TipsetKeys, epoch: ChainEpoch| {
+            BlockHeader::builder()
+                .parents(parents)
+                .epoch(epoch)
+                .weight((epoch as u64).into())
+                .miner_address(address::Address::new_id(1))
+                .state_root(state_root)
+                .messages(msg_root)
+                .election_proof(Some(ElectionProof::default()))
+                .build_and_validate()
+                .unwrap()
+        };
+        let base = Arc::new(Tipset::new(vec![header(cs.genesis.key().clone(), 1)]).unwrap());
+        let head = Arc::new(Tipset::new(vec![header(base.key().clone(), 2)]).unwrap());
+
+        // Both blocks passed validation already, which is covered by the block validation tests.
+        let chain_store = cs.state_manager.chain_store().clone();
+        chain::persist_objects(db.as_ref(), base.blocks()).unwrap();
+        chain_store
+            .mark_block_as_validated(&base.cids()[0])
+            .unwrap();
+        chain_store
+            .mark_block_as_validated(&head.cids()[0])
+            .unwrap();
+
+        task::block_on(async {
+            chain_store.set_heaviest_tipset(base).await.unwrap();
+            cs.state = ChainSyncState::Follow;
+            cs.new_worker()
+                .await
+                .spawn_follower(cs.follow_rx.clone(), FOLLOW_MAX_EPOCH_GAP)
+                .await;
+
+            cs.set_peer_head(peer, head.clone()).await;
+            let followed = timeout(Duration::from_secs(5), async {
+                while chain_store.heaviest_tipset().await.as_ref() != Some(&head) {
+                    task::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await;
+            assert!(followed.is_ok());
+        });
+    }
+
+    #[test]
+    fn sync_targets_assigned_exclusively() {
+        let db = Arc::new(MemoryDB::default());
+        let (mut cs, _, _) = chain_syncer_setup(db);
+
+        let a = dummy_tipset(TipsetKeys::default(), 1, 1);
+        let b = dummy_tipset(TipsetKeys::default(), 1, 2);
+        let a_child = dummy_tipset(a.key().clone(), 2, 1);
+
+        task::block_on(async {
+            let (worker_tx, mut worker_rx) = channel(3);
+
+            // Competing forks are synced in parallel.
+            cs.schedule_tipset(a.clone()).await;
+            cs.schedule_tipset(b.clone()).await;
+            cs.dispatch_sync_targets(&worker_tx, 3).await;
+            assert_eq!(worker_rx.next().await.unwrap(), a);
+            assert_eq!(worker_rx.next().await.unwrap(), b);
+
+            // Tipsets on the chain of an active target wait for it to be done.
+            cs.schedule_tipset(a.clone()).await;
+            cs.schedule_tipset(a_child.clone()).await;
+            cs.dispatch_sync_targets(&worker_tx, 3).await;
+            assert!(worker_rx.is_empty());
+
+            cs.complete_sync(&a).await;
+            cs.dispatch_sync_targets(&worker_tx, 3).await;
+            assert_eq!(worker_rx.next().await.unwrap(), a_child);
+            assert!(worker_rx.is_empty());
+        });
+    }
+
     #[test]
     fn compute_msg_meta_given_msgs_test() {
         let db = Arc::new(MemoryDB::default());
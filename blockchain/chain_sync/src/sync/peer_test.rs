@@ -21,15 +21,16 @@ fn peer_manager_update() {
 
     let chain_store = Arc::new(ChainStore::new(db.clone()));
 
+    let (local_sender, _test_receiver) = channel(20);
+
     let mpool = task::block_on(MessagePool::new(
         TestApi::default(),
         "test".to_string(),
+        local_sender.clone(),
         Default::default(),
     ))
     .unwrap();
     let mpool = Arc::new(mpool);
-
-    let (local_sender, _test_receiver) = channel(20);
     let (event_sender, event_receiver) = channel(20);
 
     let msg_root = compute_msg_meta(chain_store.blockstore(), &[], &[]).unwrap();
@@ -86,6 +86,15 @@ impl SyncState {
         &self.target
     }
 
+    /// Returns true if a sync to the target is in progress.
+    pub fn is_syncing(&self) -> bool {
+        self.target.is_some()
+            && matches!(
+                self.stage,
+                SyncStage::Headers | SyncStage::PersistHeaders | SyncStage::Messages
+            )
+    }
+
     /// Sets the sync stage for the syncing state. If setting to complete, sets end timer to now.
     pub fn set_stage(&mut self, stage: SyncStage) {
         if let SyncStage::Complete = stage {
@@ -7,27 +7,29 @@ mod full_sync_test;
 mod validate_block_test;
 
 use super::bad_block_cache::BadBlockCache;
+use super::peer_manager::MAX_REQUEST_WINDOW;
 use super::sync_state::{SyncStage, SyncState};
 use super::{Error, SyncNetworkContext};
 use actor::{is_account_actor, make_map_with_root, power, STORAGE_POWER_ACTOR_ADDR};
 use address::Address;
 use amt::Amt;
-use async_std::sync::{Receiver, RwLock};
+use async_std::sync::{Receiver, RwLock, Sender};
 use async_std::task::{self, JoinHandle};
 use beacon::{Beacon, BeaconEntry, IGNORE_DRAND_VAR};
 use blocks::{Block, BlockHeader, FullTipset, Tipset, TipsetKeys, TxMeta};
 use chain::{persist_objects, ChainStore};
 use cid::{Cid, Code::Blake2b256};
+use clock::ChainEpoch;
 use crypto::{verify_bls_aggregate, DomainSeparationTag};
 use encoding::{Cbor, Error as EncodingError};
 use fil_types::{
-    verifier::ProofVerifier, Randomness, ALLOWABLE_CLOCK_DRIFT, BLOCK_DELAY_SECS, BLOCK_GAS_LIMIT,
+    verifier::ProofVerifier, Randomness, ALLOWABLE_CLOCK_DRIFT, BLOCK_GAS_LIMIT,
     TICKET_RANDOMNESS_LOOKBACK, UPGRADE_SMOKE_HEIGHT,
 };
-use forest_libp2p::blocksync::TipsetBundle;
+use forest_libp2p::{blocksync::TipsetBundle, Misbehaviour};
 use futures::stream::{FuturesUnordered, StreamExt};
-use interpreter::price_list_by_epoch;
 use ipld_blockstore::BlockStore;
+use libp2p::core::PeerId;
 use log::{debug, info, warn};
 use message::{Message, SignedMessage, UnsignedMessage};
 use state_manager::StateManager;
@@ -40,6 +42,9 @@ use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Number of tipsets requested at once when fetching missing parents of a followed tipset.
+const FOLLOW_REQUEST_WINDOW: u64 = 5;
+
 /// Worker to handle syncing chain with the blocksync protocol.
 pub(crate) struct SyncWorker<DB, TBeacon, V> {
     /// State of the sync worker.
@@ -75,18 +80,177 @@ where
         self.state_manager.chain_store()
     }
 
-    pub async fn spawn(self, mut inbound_channel: Receiver<Arc<Tipset>>) -> JoinHandle<()> {
+    /// Spawns a worker which syncs the targets received, sending each target back through
+    /// `done_channel` once its sync finished or failed.
+    pub async fn spawn(
+        self,
+        mut inbound_channel: Receiver<Arc<Tipset>>,
+        done_channel: Sender<Arc<Tipset>>,
+    ) -> JoinHandle<()> {
         task::spawn(async move {
             while let Some(ts) = inbound_channel.next().await {
-                if let Err(e) = self.sync(ts).await {
+                if let Err(e) = self.sync(ts.clone()).await {
                     let err = e.to_string();
                     warn!("failed to sync tipset: {}", &err);
                     self.state.write().await.error(err);
                 }
+                done_channel.send(ts).await;
+            }
+        })
+    }
+
+    /// Spawns a worker which syncs tipsets received while following the chain, along with the
+    /// peers they were received from. Missing parents are fetched down to at most `max_depth`
+    /// epochs below the heaviest tipset.
+    pub async fn spawn_follower(
+        self,
+        mut inbound_channel: Receiver<(PeerId, Arc<Tipset>)>,
+        max_depth: ChainEpoch,
+    ) -> JoinHandle<()> {
+        task::spawn(async move {
+            while let Some((source, ts)) = inbound_channel.next().await {
+                if let Err(e) = self.sync_follow(&source, ts, max_depth).await {
+                    warn!("failed to follow tipset: {}", e);
+                }
             }
         })
     }
 
+    /// Syncs a tipset close to the heaviest tipset. Unlike `sync`, this doesn't wait for peers
+    /// and only walks back until a validated tipset, so that blocks at the head of the chain
+    /// are validated as soon as they are received. The `source` peer is reported if the tipset
+    /// or its parents are invalid.
+    pub async fn sync_follow(
+        &self,
+        source: &PeerId,
+        head: Arc<Tipset>,
+        max_depth: ChainEpoch,
+    ) -> Result<(), Error> {
+        let heaviest = self
+            .chain_store()
+            .heaviest_tipset()
+            .await
+            .ok_or_else(|| Error::Other("No heaviest tipset to follow from".to_owned()))?;
+
+        self.state
+            .write()
+            .await
+            .init(heaviest.clone(), head.clone());
+        if let Err(e) = self
+            .follow_tipset(source, &head, &heaviest, max_depth)
+            .await
+        {
+            self.state.write().await.error(e.to_string());
+            return Err(e);
+        }
+        self.set_stage(SyncStage::Complete).await;
+
+        self.chain_store().put_tipset(head.as_ref()).await?;
+        Ok(())
+    }
+
+    async fn follow_tipset(
+        &self,
+        source: &PeerId,
+        head: &Tipset,
+        heaviest: &Tipset,
+        max_depth: ChainEpoch,
+    ) -> Result<(), Error> {
+        let tipsets = self
+            .fetch_follow_headers(head, heaviest.epoch() - max_depth)
+            .await?;
+
+        self.set_stage(SyncStage::PersistHeaders).await;
+        let headers: Vec<&BlockHeader> = tipsets.iter().map(|t| t.blocks()).flatten().collect();
+        persist_objects(self.chain_store().blockstore(), &headers)?;
+
+        self.set_stage(SyncStage::Messages).await;
+        self.sync_messages_check_state(tipsets, Some(source)).await
+    }
+
+    /// Returns the tipset with its parents which are not validated yet, from the newest. Parents
+    /// are loaded from the store, or fetched in small windows with blocksync. Fails if no
+    /// validated parent is found above `min_epoch`.
+    async fn fetch_follow_headers(
+        &self,
+        head: &Tipset,
+        min_epoch: ChainEpoch,
+    ) -> Result<Vec<Tipset>, Error> {
+        self.set_stage(SyncStage::Headers).await;
+
+        let mut accepted_blocks: Vec<Cid> = head.cids().to_vec();
+        let mut tipsets = vec![head.clone()];
+        loop {
+            let cur_ts = tipsets.last().expect("follow tipsets cannot be empty");
+            self.validate_tipset_against_cache(cur_ts.parents(), &accepted_blocks)
+                .await?;
+
+            let stored = self.chain_store().tipset_from_keys(cur_ts.parents()).ok();
+            if let Some(ts) = &stored {
+                if self.is_validated(ts)? {
+                    break;
+                }
+            }
+            if cur_ts.epoch() <= min_epoch {
+                return Err(Error::Other(format!(
+                    "No validated parent of tipset at epoch {} found above epoch {}",
+                    head.epoch(),
+                    min_epoch
+                )));
+            }
+
+            let parents = match stored {
+                Some(ts) => vec![ts],
+                None => {
+                    let window = min(cur_ts.epoch() - min_epoch, FOLLOW_REQUEST_WINDOW as i64);
+                    debug!(
+                        "BlockSync parents of followed tipset from: {}, window: {}",
+                        cur_ts.epoch(),
+                        window
+                    );
+                    let tipsets = self
+                        .network
+                        .shared_blocksync_headers(cur_ts.parents(), window as u64)
+                        .await?;
+                    self.persist_headers(&tipsets)?;
+                    tipsets
+                }
+            };
+            if parents.is_empty() {
+                return Err(Error::Other(
+                    "BlockSync returned no parents of followed tipset".to_owned(),
+                ));
+            }
+            for ts in parents {
+                self.validate_tipset_against_cache(ts.key(), &accepted_blocks)
+                    .await?;
+                accepted_blocks.extend_from_slice(ts.cids());
+                self.state.write().await.set_epoch(ts.epoch());
+                tipsets.push(ts);
+            }
+        }
+        Ok(tipsets)
+    }
+
+    /// Persists the headers of the tipsets to the blockstore.
+    fn persist_headers(&self, tipsets: &[Tipset]) -> Result<(), Error> {
+        let headers: Vec<&BlockHeader> = tipsets.iter().map(|t| t.blocks()).flatten().collect();
+        Ok(persist_objects(self.chain_store().blockstore(), &headers)?)
+    }
+
+    /// Returns true if all blocks of the tipset were validated, or if the tipset is genesis.
+    fn is_validated(&self, ts: &Tipset) -> Result<bool, Error> {
+        if ts == self.genesis.as_ref() {
+            return Ok(true);
+        }
+        for cid in ts.cids() {
+            if !self.chain_store().is_block_validated(cid)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     /// Performs syncing process
     pub async fn sync(&self, head: Arc<Tipset>) -> Result<(), Error> {
         // Bootstrap peers before syncing
@@ -132,7 +296,8 @@ where
         }
         // Sync and validate messages from fetched tipsets
         self.set_stage(SyncStage::Messages).await;
-        if let Err(e) = self.sync_messages_check_state(tipsets).await {
+        // Headers are fetched from several peers, so no single peer is reported for bad blocks.
+        if let Err(e) = self.sync_messages_check_state(tipsets, None).await {
             self.state.write().await.error(e.to_string());
             return Err(e);
         }
@@ -187,18 +352,22 @@ where
                 continue;
             }
 
-            // TODO tweak request window when socket frame is tested
-            const REQUEST_WINDOW: i64 = 10;
             let epoch_diff = cur_ts.epoch() - to_epoch;
             debug!("BlockSync from: {} to {}", cur_ts.epoch(), to_epoch);
-            let window = min(epoch_diff, REQUEST_WINDOW);
+            // The window is further limited to the request window of the peer requested from.
+            let window = min(epoch_diff, MAX_REQUEST_WINDOW as i64);
 
             // Load blocks from network using blocksync
-            // TODO consider altering window size before returning error for failed sync.
             let tipsets = self
                 .network
-                .blocksync_headers(None, cur_ts.parents(), window as u64)
+                .shared_blocksync_headers(cur_ts.parents(), window as u64)
                 .await?;
+            if tipsets.is_empty() {
+                return Err(Error::Other("BlockSync returned no headers".to_owned()));
+            }
+            // Headers are persisted right away so that workers syncing targets on the same
+            // chain load them from the store instead of downloading them again.
+            self.persist_headers(&tipsets)?;
 
             info!(
                 "Got tipsets: Height: {}, Len: {}",
@@ -270,14 +439,13 @@ where
 
     /// fork detected, collect tipsets to be included in return_set sync_headers_reverse
     async fn sync_fork(&self, head: &Tipset, to: &Tipset) -> Result<Vec<Tipset>, Error> {
-        // TODO move to shared parameter (from actors crate most likely)
-        const FORK_LENGTH_THRESHOLD: u64 = 500;
+        let fork_length_threshold = self.chain_store().fork_length_threshold();
 
         // TODO make this request more flexible with the window size, shouldn't require a node
         // to have to request all fork length headers at once.
         let tips = self
             .network
-            .blocksync_headers(None, head.parents(), FORK_LENGTH_THRESHOLD)
+            .blocksync_headers(None, head.parents(), fork_length_threshold)
             .await?;
 
         let mut ts = self.chain_store().tipset_from_keys(to.parents())?;
@@ -296,13 +464,18 @@ where
             }
         }
 
-        Err(Error::Other(
-            "Fork longer than threshold finality of 500".to_string(),
-        ))
+        Err(Error::Other(format!(
+            "Fork longer than threshold finality of {}",
+            fork_length_threshold
+        )))
     }
 
     /// Syncs messages by first checking state for message existence otherwise fetches messages from blocksync
-    async fn sync_messages_check_state(&self, tipsets: Vec<Tipset>) -> Result<(), Error> {
+    async fn sync_messages_check_state(
+        &self,
+        tipsets: Vec<Tipset>,
+        source: Option<&PeerId>,
+    ) -> Result<(), Error> {
         let mut ts_iter = tipsets.into_iter().rev();
         // Currently syncing 1 height at a time, no reason for us to sync more
         const REQUEST_WINDOW: usize = 1;
@@ -345,7 +518,7 @@ where
 
                         // validate tipset and messages
                         let curr_epoch = fts.epoch();
-                        self.validate_tipset(fts).await?;
+                        self.validate_tipset(fts, source).await?;
                         self.state.write().await.set_epoch(curr_epoch);
 
                         // store messages
@@ -362,7 +535,7 @@ where
             };
             // full tipset found in storage; validate and continue
             let curr_epoch = fts.epoch();
-            self.validate_tipset(fts).await?;
+            self.validate_tipset(fts, source).await?;
             self.state.write().await.set_epoch(curr_epoch);
             continue;
         }
@@ -370,8 +543,9 @@ where
         Ok(())
     }
 
-    /// validates tipsets and adds header data to tipset tracker
-    async fn validate_tipset(&self, fts: FullTipset) -> Result<(), Error> {
+    /// validates tipsets and adds header data to tipset tracker. The `source` peer, if any, is
+    /// reported for blocks marked as bad.
+    async fn validate_tipset(&self, fts: FullTipset, source: Option<&PeerId>) -> Result<(), Error> {
         if &fts.to_tipset() == self.genesis.as_ref() {
             debug!("Skipping tipset validation for genesis");
             return Ok(());
@@ -397,6 +571,11 @@ where
                     // If the error is temporally invalidated, don't add to bad blocks cache.
                     if !matches!(e, Error::Temporal(_, _)) {
                         self.bad_blocks.put(cid, e.to_string()).await;
+                        if let Some(peer) = source {
+                            self.network
+                                .report_peer(peer.clone(), Misbehaviour::InvalidBlock)
+                                .await;
+                        }
                     }
                     return Err(Error::Other(format!("Invalid block detected: {}", e)));
                 }
@@ -465,7 +644,8 @@ where
 
         // Timestamp checks
         let nulls = (header.epoch() - (base_ts.epoch() + 1)) as u64;
-        let target_timestamp = base_ts.min_timestamp() + BLOCK_DELAY_SECS * (nulls + 1);
+        let block_delay = sm.network_chain().block_delay_secs();
+        let target_timestamp = base_ts.min_timestamp() + block_delay * (nulls + 1);
         if target_timestamp != header.timestamp() {
             return Err((
                 *block_cid,
@@ -793,7 +973,7 @@ where
             return Err("No bls signature included in the block header".into());
         }
 
-        let pl = price_list_by_epoch(base_ts.epoch());
+        let pl = state_manager.price_list(base_ts.epoch());
         let mut sum_gas_limit = 0;
 
         // check msgs for validity
@@ -1059,6 +1239,62 @@ mod tests {
         });
     }
 
+    #[test]
+    fn follow_headers_within_depth() {
+        let db = Arc::new(MemoryDB::default());
+        let (sw, network_receiver) = sync_worker_setup(db);
+
+        let source = PeerId::random();
+        let head = construct_tipset(4, 10);
+
+        task::block_on(async move {
+            sw.network
+                .peer_manager()
+                .update_peer_head(source, None)
+                .await;
+            // Parents fetched from the network don't link to a validated tipset above epoch 2.
+            let fetched = task::spawn(async move { sw.fetch_follow_headers(&head, 2).await });
+            send_blocksync_response(network_receiver);
+            assert!(fetched.await.is_err());
+        });
+    }
+
+    #[test]
+    fn invalid_block_source_reported() {
+        let db = Arc::new(MemoryDB::default());
+        let (sw, network_receiver) = sync_worker_setup(db);
+
+        let source = PeerId::random();
+        // Block without an election proof, ticket or signatures.
+        let header = BlockHeader::builder()
+            .epoch(1)
+            .miner_address(Address::new_id(1))
+            .build_and_validate()
+            .unwrap();
+        let cid = *header.cid();
+        let fts = FullTipset::new(vec![Block {
+            header,
+            bls_messages: Vec::new(),
+            secp_messages: Vec::new(),
+        }])
+        .unwrap();
+
+        task::block_on(async {
+            assert!(sw.validate_tipset(fts, Some(&source)).await.is_err());
+            assert!(sw.bad_blocks.peek(&cid).await.is_some());
+            match network_receiver.recv().await.unwrap() {
+                NetworkMessage::ReportPeer {
+                    peer_id,
+                    misbehaviour,
+                } => {
+                    assert_eq!(peer_id, source);
+                    assert_eq!(misbehaviour, Misbehaviour::InvalidBlock);
+                }
+                _ => unreachable!(),
+            }
+        });
+    }
+
     #[test]
     fn sync_headers_reverse_given_tipsets_test() {
         let db = Arc::new(MemoryDB::default());
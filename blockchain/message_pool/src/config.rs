@@ -12,7 +12,7 @@ const MPOOL_CONFIG_KEY: &[u8] = b"/mpool/config";
 const SIZE_LIMIT_LOW: i64 = 20000;
 const SIZE_LIMIT_HIGH: i64 = 30000;
 const PRUNE_COOLDOWN: Duration = Duration::from_secs(60); // 1 minute
-const REPLACE_BY_FEE_RATIO: f64 = 1.25;
+pub(crate) const REPLACE_BY_FEE_RATIO: f64 = 1.25;
 const GAS_LIMIT_OVERESTIMATION: f64 = 1.25;
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -69,6 +69,31 @@ impl MpoolConfig {
             gas_limit_overestimation,
         })
     }
+    /// Addresses whose messages are selected first when creating a block.
+    pub fn priority_addrs(&self) -> &[Address] {
+        &self.priority_addrs
+    }
+    /// Ratio by which the fees of a message have to be bumped to replace a pending message.
+    pub fn replace_by_fee_ratio(&self) -> f64 {
+        self.replace_by_fee_ratio
+    }
+    /// Number of pending messages above which the pool is pruned.
+    pub fn size_limit_high(&self) -> i64 {
+        self.size_limit_high
+    }
+    /// Number of pending messages kept when the pool is pruned.
+    pub fn size_limit_low(&self) -> i64 {
+        self.size_limit_low
+    }
+    /// Minimum time between two prunes of the pool.
+    pub fn prune_cooldown(&self) -> Duration {
+        self.prune_cooldown
+    }
+    /// Factor by which estimated gas limits are multiplied, to allow for state changes between
+    /// estimation and inclusion.
+    pub fn gas_limit_overestimation(&self) -> f64 {
+        self.gas_limit_overestimation
+    }
     pub fn save_config<DB: Store>(&self, store: &DB) -> Result<(), Box<dyn StdError>> {
         Ok(store.write(MPOOL_CONFIG_KEY, to_vec(&self)?)?)
     }
@@ -27,6 +27,14 @@ pub enum Error {
     InvalidFromAddr,
     #[error("Message with sequence already in mempool")]
     DuplicateSequence,
+    #[error("Replace by fee gas premium is too low")]
+    RbfPremiumTooLow,
+    #[error("Replace by fee gas fee cap is too low")]
+    RbfFeeCapTooLow,
+    #[error("Message sequence has too big a gap from the expected sequence")]
+    SequenceGap,
+    #[error("Too many pending messages for actor")]
+    TooManyPendingMessages,
     #[error("State inconsistency with message. Try again")]
     TryAgain,
     #[error("Validation Error: {0}")]
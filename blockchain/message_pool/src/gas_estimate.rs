@@ -0,0 +1,225 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::errors::Error;
+use super::msgpool::{MessagePool, Provider};
+use address::Address;
+use blocks::{Tipset, TipsetKeys};
+use chain::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET};
+use message::Message;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Zero};
+use rand_distr::{Distribution, Normal};
+use vm::TokenAmount;
+
+/// Lowest premium estimated, messages paying less are unlikely to be included in time.
+pub const MIN_GAS_PREMIUM: u64 = 100_000;
+
+/// Maximum total fee of a message, in attoFIL, when no maximum is specified (0.07 FIL).
+pub const DEFAULT_MAX_FEE: u64 = 70_000_000_000_000_000;
+
+/// Bits of precision used when scaling amounts by a floating point factor.
+const SCALE_PRECISION: u32 = 32;
+
+/// Premium paid by a message included in a recent tipset, with the gas it used.
+pub(crate) struct GasMeta {
+    pub price: BigInt,
+    pub used: i64,
+}
+
+impl<T> MessagePool<T>
+where
+    T: Provider + std::marker::Send + std::marker::Sync + 'static,
+{
+    /// Estimates the premium a message has to pay to be included within `nblocksincl` epochs,
+    /// from the premiums paid by the messages of the `2 * nblocksincl` tipsets before the given
+    /// one, or before the current tipset if no tipset is given.
+    pub async fn estimate_gas_premium(
+        &self,
+        nblocksincl: u64,
+        _sender: Address,
+        _gas_limit: i64,
+        tsk: TipsetKeys,
+    ) -> Result<BigInt, Error> {
+        let nblocksincl = nblocksincl.max(1);
+
+        let mut prices = Vec::new();
+        let mut blocks = 0;
+
+        let mut ts = self.tipset_or_current(&tsk).await?;
+        let api = self.api.read().await;
+        for _ in 0..(nblocksincl * 2) {
+            if ts.epoch() == 0 {
+                break;
+            }
+            let pts = api.load_tipset(ts.parents())?;
+            blocks += pts.blocks().len();
+
+            // Receipts of the parent's messages are stored in the child, if they can't be
+            // matched up with the messages, the gas limit is used as an upper bound instead.
+            let msgs = api.messages_for_tipset(&pts)?;
+            let receipts = api.parent_receipts(&ts.blocks()[0])?;
+            let receipts = if receipts.len() == msgs.len() {
+                Some(receipts)
+            } else {
+                None
+            };
+            prices.extend(msgs.iter().enumerate().map(|(i, msg)| {
+                GasMeta {
+                    price: msg.gas_premium().clone(),
+                    used: receipts
+                        .as_ref()
+                        .map_or_else(|| msg.gas_limit(), |r| r[i].gas_used),
+                }
+            }));
+            ts = pts;
+        }
+        drop(api);
+
+        let mut premium = median_gas_premium(prices, blocks);
+        if premium < BigInt::from(MIN_GAS_PREMIUM) {
+            premium = BigInt::from(match nblocksincl {
+                1 => MIN_GAS_PREMIUM * 2,
+                2 => MIN_GAS_PREMIUM * 3 / 2,
+                _ => MIN_GAS_PREMIUM,
+            });
+        }
+
+        // mean 1, stddev 0.005 => 95% within +-1%
+        let noise: f64 = Normal::new(1.0, 0.005)
+            .unwrap()
+            .sample(&mut rand::thread_rng());
+        scale(&premium, noise)
+    }
+
+    /// Estimates the fee cap of a message paying the given premium, allowing the base fee to
+    /// increase at the maximum rate for `max_queue_blks` epochs before the message is included.
+    /// The base fee is the one of the given tipset, or of the current tipset if none is given.
+    pub async fn estimate_fee_cap(
+        &self,
+        gas_premium: &BigInt,
+        max_queue_blks: i64,
+        tsk: TipsetKeys,
+    ) -> Result<BigInt, Error> {
+        let ts = self.tipset_or_current(&tsk).await?;
+        let parent_base_fee = ts.blocks()[0].parent_base_fee();
+        let increase_factor =
+            (1.0 + (BASE_FEE_MAX_CHANGE_DENOM as f64).recip()).powf(max_queue_blks as f64);
+
+        Ok(scale(parent_base_fee, increase_factor)? + gas_premium)
+    }
+
+    /// Loads the tipset of the given keys, or returns the current tipset if the keys are empty.
+    async fn tipset_or_current(&self, tsk: &TipsetKeys) -> Result<Tipset, Error> {
+        if tsk.cids().is_empty() {
+            Ok(self.cur_tipset.read().await.as_ref().clone())
+        } else {
+            self.api.read().await.load_tipset(tsk)
+        }
+    }
+}
+
+/// Returns the premium paid at the point where messages, sorted by descending premium, have used
+/// half the gas target of the given number of blocks, plus 5%. Lower premiums only get included
+/// once blocks fill up past that point. If recent blocks didn't use that much gas, the premium
+/// where the messages ran out is returned, and zero if there were no messages.
+pub(crate) fn median_gas_premium(mut prices: Vec<GasMeta>, blocks: usize) -> BigInt {
+    prices.sort_by(|a, b| b.price.cmp(&a.price));
+
+    let mut at = BLOCK_GAS_TARGET * blocks as i64 / 2;
+    at += BLOCK_GAS_TARGET * blocks as i64 / (2 * 20);
+
+    let mut prev1 = BigInt::zero();
+    let mut prev2 = BigInt::zero();
+    for meta in prices {
+        prev2 = std::mem::replace(&mut prev1, meta.price);
+        at -= meta.used;
+        if at < 0 {
+            break;
+        }
+    }
+
+    if prev2.is_zero() {
+        prev1
+    } else {
+        (prev1 + prev2) / 2
+    }
+}
+
+/// Lowers the fee cap of a message so that it never pays more than `max_fee` in total, keeping the
+/// premium at most the fee cap.
+pub fn cap_gas_fee<M: Message>(msg: &mut M, max_fee: &TokenAmount) {
+    let gas_limit = BigInt::from(msg.gas_limit());
+    if gas_limit <= BigInt::zero() || msg.gas_fee_cap() * &gas_limit <= *max_fee {
+        return;
+    }
+    let fee_cap = max_fee / gas_limit;
+    if *msg.gas_premium() > fee_cap {
+        msg.set_gas_premium(fee_cap.clone());
+    }
+    msg.set_gas_fee_cap(fee_cap);
+}
+
+/// Multiplies an amount by a floating point factor, in fixed point.
+fn scale(amount: &BigInt, factor: f64) -> Result<BigInt, Error> {
+    let factor = BigInt::from_f64(factor * (1u64 << SCALE_PRECISION) as f64)
+        .ok_or_else(|| Error::Other(format!("invalid scaling factor {}", factor)))?;
+    Ok(amount * factor / (1u64 << SCALE_PRECISION))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use message::UnsignedMessage;
+
+    fn meta(price: u64, used: i64) -> GasMeta {
+        GasMeta {
+            price: price.into(),
+            used,
+        }
+    }
+
+    #[test]
+    fn median_premium_by_gas_used() {
+        let half = BLOCK_GAS_TARGET / 2;
+
+        // Highest premium uses up half the target, the median falls between it and the next.
+        let prices = vec![meta(100, half), meta(300, half), meta(200, half / 10)];
+        assert_eq!(median_gas_premium(prices, 1), BigInt::from(250));
+
+        // Single message over the whole target.
+        let prices = vec![meta(400, BLOCK_GAS_TARGET), meta(100, 1)];
+        assert_eq!(median_gas_premium(prices, 1), BigInt::from(400));
+
+        // Blocks are mostly empty, the lowest premiums paid are used.
+        let prices = vec![meta(500, half / 2), meta(400, half / 2)];
+        assert_eq!(median_gas_premium(prices, 2), BigInt::from(450));
+        let prices = vec![meta(500, half / 2)];
+        assert_eq!(median_gas_premium(prices, 2), BigInt::from(500));
+        assert_eq!(median_gas_premium(Vec::new(), 5), BigInt::zero());
+    }
+
+    #[test]
+    fn cap_fee_to_max() {
+        let mut msg = UnsignedMessage::builder()
+            .to(Address::new_id(1))
+            .from(Address::new_id(2))
+            .gas_limit(1000)
+            .gas_fee_cap(500.into())
+            .gas_premium(200.into())
+            .build()
+            .unwrap();
+
+        cap_gas_fee(&mut msg, &BigInt::from(1_000_000));
+        assert_eq!(msg.gas_fee_cap(), &BigInt::from(500));
+        assert_eq!(msg.gas_premium(), &BigInt::from(200));
+
+        cap_gas_fee(&mut msg, &BigInt::from(300_000));
+        assert_eq!(msg.gas_fee_cap(), &BigInt::from(300));
+        assert_eq!(msg.gas_premium(), &BigInt::from(200));
+
+        cap_gas_fee(&mut msg, &BigInt::from(100_000));
+        assert_eq!(msg.gas_fee_cap(), &BigInt::from(100));
+        assert_eq!(msg.gas_premium(), &BigInt::from(100));
+    }
+}
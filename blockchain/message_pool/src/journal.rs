@@ -0,0 +1,95 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::errors::Error;
+use cid::Cid;
+use clock::ChainEpoch;
+use db::{IterableStore, Store};
+use encoding::{from_slice, to_vec, tuple::*};
+use log::warn;
+use message::SignedMessage;
+
+/// Namespace of the local message journal in the store. Each message is stored under the
+/// namespace followed by its cid, so the journal is enumerated by key prefix.
+const LOCAL_MSGS_PREFIX: &str = "/mpool/local/";
+
+/// Locally submitted message, with the epoch of the head it was submitted at and the epoch it was
+/// included at once it lands on chain.
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct LocalMsgEntry {
+    pub message: SignedMessage,
+    pub added_epoch: ChainEpoch,
+    pub included_epoch: Option<ChainEpoch>,
+}
+
+impl LocalMsgEntry {
+    /// Creates the entry of a local message which has not been included yet.
+    pub fn new(message: SignedMessage, added_epoch: ChainEpoch) -> Self {
+        Self {
+            message,
+            added_epoch,
+            included_epoch: None,
+        }
+    }
+}
+
+fn entry_key(cid: &Cid) -> String {
+    format!("{}{}", LOCAL_MSGS_PREFIX, cid)
+}
+
+/// Writes the journal entries of local messages, keyed by the cid of the unsigned message, in a
+/// single batch.
+pub fn put_local_entries<DB: Store>(
+    db: &DB,
+    entries: &[(Cid, LocalMsgEntry)],
+) -> Result<(), Error> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let batch = entries
+        .iter()
+        .map(|(cid, entry)| Ok((entry_key(cid), to_vec(entry)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    db.bulk_write(&batch)
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Removes the journal entries of local messages.
+pub fn remove_local_entries<DB: Store>(db: &DB, cids: &[Cid]) -> Result<(), Error> {
+    if cids.is_empty() {
+        return Ok(());
+    }
+    let keys: Vec<String> = cids.iter().map(entry_key).collect();
+    db.bulk_delete(&keys)
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Loads all entries of the local message journal, ordered by sequence so that the messages of
+/// a sender can be added back in order.
+pub fn load_local_entries<DB: IterableStore>(db: &DB) -> Result<Vec<(Cid, LocalMsgEntry)>, Error> {
+    let mut keys = Vec::new();
+    db.for_each_key_with_prefix(LOCAL_MSGS_PREFIX.as_bytes(), |key| {
+        keys.push(key.to_vec());
+        Ok(())
+    })
+    .map_err(|e| Error::Other(e.to_string()))?;
+
+    let mut entries = Vec::with_capacity(keys.len());
+    for key in keys {
+        let cid = match std::str::from_utf8(&key[LOCAL_MSGS_PREFIX.len()..])
+            .ok()
+            .and_then(|c| c.parse::<Cid>().ok())
+        {
+            Some(cid) => cid,
+            None => {
+                warn!("ignoring malformed local message journal key {:?}", key);
+                continue;
+            }
+        };
+        if let Some(bz) = db.read(&key).map_err(|e| Error::Other(e.to_string()))? {
+            entries.push((cid, from_slice::<LocalMsgEntry>(&bz)?));
+        }
+    }
+    entries.sort_by_key(|(_, entry)| entry.message.sequence());
+    Ok(entries)
+}
@@ -1,11 +1,16 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-// TODO Remove public once Message Selection has been implemented
-pub mod block_prob;
+mod block_prob;
 mod config;
 mod errors;
+mod gas_estimate;
+mod journal;
+mod msg_chain;
 mod msgpool;
+mod selection;
 pub use self::config::*;
 pub use self::errors::*;
+pub use self::gas_estimate::*;
+pub use self::journal::*;
 pub use self::msgpool::*;
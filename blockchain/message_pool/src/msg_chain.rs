@@ -0,0 +1,285 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use message::{Message, SignedMessage};
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use std::cmp::Ordering;
+use types::BLOCK_GAS_LIMIT;
+
+/// A chain of consecutive messages from a single sender. Messages of a sender are split into
+/// multiple chains such that each chain has a higher gas performance than the following one.
+#[derive(Clone, Debug)]
+pub(crate) struct MsgChain {
+    pub msgs: Vec<SignedMessage>,
+    pub gas_reward: BigInt,
+    pub gas_limit: i64,
+    pub gas_perf: f64,
+    pub eff_perf: f64,
+    pub bp: f64,
+    pub valid: bool,
+    /// Whether the messages of the chain have been selected.
+    pub merged: bool,
+    /// Index of the chain containing the preceding messages of the same sender.
+    pub prev: Option<usize>,
+    /// Index of the chain containing the following messages of the same sender.
+    pub next: Option<usize>,
+}
+
+impl MsgChain {
+    fn new(msg: SignedMessage, gas_reward: BigInt) -> Self {
+        let gas_limit = msg.gas_limit();
+        Self {
+            gas_perf: get_gas_perf(&gas_reward, gas_limit),
+            msgs: vec![msg],
+            gas_reward,
+            gas_limit,
+            eff_perf: 0.0,
+            bp: 0.0,
+            valid: true,
+            merged: false,
+            prev: None,
+            next: None,
+        }
+    }
+
+    /// Orders chains by gas performance, higher performing chains first.
+    pub fn compare(&self, other: &Self) -> Ordering {
+        other
+            .gas_perf
+            .partial_cmp(&self.gas_perf)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.gas_reward.cmp(&self.gas_reward))
+    }
+
+    /// Orders chains by effective gas performance, higher performing chains first.
+    pub fn compare_effective(&self, other: &Self) -> Ordering {
+        if self.gas_perf >= 0.0 && other.gas_perf < 0.0 {
+            return Ordering::Less;
+        }
+        if self.gas_perf < 0.0 && other.gas_perf >= 0.0 {
+            return Ordering::Greater;
+        }
+        other
+            .eff_perf
+            .partial_cmp(&self.eff_perf)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.compare(other))
+    }
+}
+
+/// Set of message chains, which reference their dependent chains by index.
+#[derive(Default, Debug)]
+pub(crate) struct Chains {
+    chains: Vec<MsgChain>,
+}
+
+impl Chains {
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    pub fn get(&self, i: usize) -> &MsgChain {
+        &self.chains[i]
+    }
+
+    /// Adds the chains of a single sender, linking each chain to its neighbours.
+    pub fn extend(&mut self, chains: Vec<MsgChain>) {
+        let offset = self.chains.len();
+        let count = chains.len();
+        for (i, mut chain) in chains.into_iter().enumerate() {
+            chain.prev = if i > 0 { Some(offset + i - 1) } else { None };
+            chain.next = if i + 1 < count {
+                Some(offset + i + 1)
+            } else {
+                None
+            };
+            self.chains.push(chain);
+        }
+    }
+
+    /// Sets the block probability of the chain, and updates the effective performance.
+    pub fn set_effective_perf(&mut self, i: usize, bp: f64) {
+        self.chains[i].bp = bp;
+        self.set_eff_perf(i);
+    }
+
+    fn set_eff_perf(&mut self, i: usize) {
+        let chain = &self.chains[i];
+        let mut eff_perf = chain.gas_perf * chain.bp;
+        if eff_perf > 0.0 {
+            if let Some(prev) = chain.prev.map(|p| &self.chains[p]) {
+                // Include the performance of the chain this depends on
+                eff_perf = (eff_perf * chain.gas_limit as f64
+                    + prev.eff_perf * prev.gas_limit as f64)
+                    / (chain.gas_limit + prev.gas_limit) as f64;
+            }
+        }
+        self.chains[i].eff_perf = eff_perf;
+    }
+
+    /// Marks the messages of the chain as selected.
+    pub fn set_merged(&mut self, i: usize) {
+        self.chains[i].merged = true;
+    }
+
+    /// Sets the effective performance of a chain which does not fit in any of the blocks.
+    pub fn set_null_effective_perf(&mut self, i: usize) {
+        let chain = &mut self.chains[i];
+        chain.eff_perf = if chain.gas_perf < 0.0 {
+            chain.gas_perf
+        } else {
+            0.0
+        };
+    }
+
+    /// Removes messages from the end of the chain until it fits in the gas limit and has a
+    /// non negative gas performance. All chains depending on this one are invalidated.
+    pub fn trim(&mut self, i: usize, gas_limit: i64, base_fee: &BigInt) {
+        let chain = &mut self.chains[i];
+        while let Some(msg) = chain.msgs.last() {
+            if chain.gas_limit <= gas_limit && chain.gas_perf >= 0.0 {
+                break;
+            }
+            chain.gas_reward -= get_gas_reward(msg, base_fee);
+            chain.gas_limit -= msg.gas_limit();
+            if chain.gas_limit > 0 {
+                chain.gas_perf = get_gas_perf(&chain.gas_reward, chain.gas_limit);
+            } else {
+                chain.gas_perf = 0.0;
+                chain.eff_perf = 0.0;
+            }
+            chain.msgs.pop();
+        }
+        if chain.msgs.is_empty() {
+            chain.valid = false;
+        } else if chain.bp != 0.0 {
+            self.set_eff_perf(i);
+        }
+
+        if let Some(next) = self.chains[i].next.take() {
+            self.invalidate(next);
+        }
+    }
+
+    /// Invalidates the chain and all chains depending on it.
+    pub fn invalidate(&mut self, mut i: usize) {
+        loop {
+            let chain = &mut self.chains[i];
+            chain.valid = false;
+            chain.msgs.clear();
+            match chain.next.take() {
+                Some(next) => i = next,
+                None => return,
+            }
+        }
+    }
+}
+
+/// Creates the message chains for a sender from its pending messages. The messages must be
+/// sorted by sequence, and messages which can not be included are dropped: messages before the
+/// actor sequence, messages after a gap in sequences and messages which exceed the block gas
+/// limit or the actor balance.
+pub(crate) fn create_message_chains(
+    msgs: Vec<SignedMessage>,
+    actor_sequence: u64,
+    mut balance: BigInt,
+    base_fee: &BigInt,
+    min_gas: impl Fn(&SignedMessage) -> Option<i64>,
+) -> Vec<MsgChain> {
+    let mut cur_sequence = actor_sequence;
+    let mut gas_limit = 0;
+    let mut valid = Vec::with_capacity(msgs.len());
+    for m in msgs {
+        if m.sequence() < cur_sequence {
+            continue;
+        }
+        if m.sequence() != cur_sequence {
+            break;
+        }
+        cur_sequence += 1;
+
+        match min_gas(&m) {
+            Some(min) if m.gas_limit() >= min => (),
+            _ => break,
+        }
+        gas_limit += m.gas_limit();
+        if gas_limit > BLOCK_GAS_LIMIT {
+            break;
+        }
+
+        let required = m.required_funds();
+        if balance < required {
+            break;
+        }
+        balance -= required;
+        // The value is only accounted for if the balance covers it, otherwise the message fails
+        // and the gas reward is still collected.
+        if &balance >= m.value() {
+            balance -= m.value();
+        }
+
+        let reward = get_gas_reward(&m, base_fee);
+        valid.push((m, reward));
+    }
+
+    // Aggregate messages into chains with non decreasing gas performance.
+    let mut chains: Vec<MsgChain> = Vec::new();
+    for (m, reward) in valid {
+        if let Some(cur) = chains.last_mut() {
+            let gas_reward = &cur.gas_reward + &reward;
+            let gas_limit = cur.gas_limit + m.gas_limit();
+            let gas_perf = get_gas_perf(&gas_reward, gas_limit);
+            if gas_perf >= cur.gas_perf {
+                cur.msgs.push(m);
+                cur.gas_reward = gas_reward;
+                cur.gas_limit = gas_limit;
+                cur.gas_perf = gas_perf;
+                continue;
+            }
+        }
+        chains.push(MsgChain::new(m, reward));
+    }
+
+    // Merge chains so each chain has a higher performance than the next.
+    loop {
+        let mut merged = false;
+        let mut i = chains.len();
+        while i > 1 {
+            i -= 1;
+            if chains[i].gas_perf >= chains[i - 1].gas_perf {
+                let next = chains.remove(i);
+                let cur = &mut chains[i - 1];
+                cur.msgs.extend(next.msgs);
+                cur.gas_reward += next.gas_reward;
+                cur.gas_limit += next.gas_limit;
+                cur.gas_perf = get_gas_perf(&cur.gas_reward, cur.gas_limit);
+                merged = true;
+            }
+        }
+        if !merged {
+            break;
+        }
+    }
+
+    chains
+}
+
+/// Returns the reward for the miner including the message, which is the gas limit multiplied by
+/// the premium which can be paid given the base fee.
+pub(crate) fn get_gas_reward(msg: &SignedMessage, base_fee: &BigInt) -> BigInt {
+    let mut max_premium = msg.gas_fee_cap() - base_fee;
+    if &max_premium > msg.gas_premium() {
+        max_premium = msg.gas_premium().clone();
+    }
+    max_premium * msg.gas_limit()
+}
+
+/// Returns the gas reward normalized to the block gas limit.
+pub(crate) fn get_gas_perf(gas_reward: &BigInt, gas_limit: i64) -> f64 {
+    if gas_limit == 0 || gas_reward.is_zero() {
+        return 0.0;
+    }
+    let reward = (gas_reward * BLOCK_GAS_LIMIT).to_f64().unwrap_or(0.0);
+    reward / gas_limit as f64
+}
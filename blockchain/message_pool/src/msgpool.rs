@@ -3,8 +3,12 @@
 
 use super::config::MpoolConfig;
 use super::errors::Error;
+use super::journal::{self, LocalMsgEntry};
+use super::msg_chain::{Chains, MsgChain};
+use super::selection::{create_chains, included_sequences, sorted_chains, MIN_GAS};
+use actor::miner::CHAIN_FINALITY;
 use address::{Address, Protocol};
-use async_std::sync::{Arc, RwLock};
+use async_std::sync::{channel, Arc, RwLock, Sender};
 use async_std::task;
 use async_trait::async_trait;
 use blocks::{BlockHeader, Tipset, TipsetKeys};
@@ -13,31 +17,40 @@ use chain::{HeadChange, MINIMUM_BASE_FEE};
 use cid::Cid;
 use cid::Code::Blake2b256;
 use crypto::{Signature, SignatureType};
-use db::Store;
+use db::{IterableStore, Store};
 use encoding::Cbor;
 use flo_stream::Subscriber;
+use forest_libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
 use futures::StreamExt;
-use log::{error, warn};
+use interpreter::{PriceList, PriceSchedule};
+use log::{error, info, warn};
 use lru::LruCache;
-use message::{ChainMessage, Message, SignedMessage, UnsignedMessage};
+use message::{ChainMessage, Message, MessageReceipt, SignedMessage, UnsignedMessage};
 use num_bigint::{BigInt, Integer};
 use state_manager::StateManager;
 use state_tree::StateTree;
 use std::borrow::BorrowMut;
+use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use types::verifier::ProofVerifier;
+use types::{NetworkChain, BLOCK_GAS_LIMIT};
 use vm::ActorState;
 
-const REPLACE_BY_FEE_RATIO: f32 = 1.25;
-const RBF_NUM: u64 = ((REPLACE_BY_FEE_RATIO - 1f32) * 256f32) as u64;
 const RBF_DENOM: u64 = 256;
+const MAX_NONCE_GAP: u64 = 4;
+const MAX_ACTOR_PENDING_MESSAGES: usize = 1000;
 const BASE_FEE_LOWER_BOUND_FACTOR_CONSERVATIVE: i64 = 100;
+const BASE_FEE_LOWER_BOUND_FACTOR: i64 = 10;
+const PROPAGATION_DELAY_SECS: u64 = 6;
+const REPUB_MSG_LIMIT: usize = 30;
+const MAX_LOCAL_MSGS: usize = 10000;
 
 /// Simple struct that contains a hashmap of messages where k: a message from address, v: a message
 /// which corresponds to that address
 #[derive(Clone, Default, Debug)]
 pub struct MsgSet {
-    msgs: HashMap<u64, SignedMessage>,
+    pub(crate) msgs: HashMap<u64, SignedMessage>,
     next_sequence: u64,
     required_funds: BigInt,
 }
@@ -52,26 +65,47 @@ impl MsgSet {
         }
     }
 
-    /// Add a signed message to the MsgSet. Increase next_sequence if the message has a sequence greater
-    /// than any existing message sequence.
-    pub fn add(&mut self, m: SignedMessage) -> Result<(), Error> {
-        if self.msgs.is_empty() || m.sequence() >= self.next_sequence {
-            self.next_sequence = m.sequence() + 1;
+    /// Add a signed message to the MsgSet. Increase next_sequence if the message fills the next
+    /// sequence. A message with the sequence of an existing message replaces it only if both its
+    /// gas fee cap and gas premium are bumped by the replace by fee ratio.
+    ///
+    /// When `strict`, messages too far ahead of the next sequence, replacements while there is a
+    /// gap in sequences and messages past the pending limit of the actor are rejected.
+    pub fn add(&mut self, m: SignedMessage, rbf_ratio: f64, strict: bool) -> Result<(), Error> {
+        let mut next_sequence = self.next_sequence;
+        let mut sequence_gap = false;
+        if m.sequence() == next_sequence {
+            next_sequence += 1;
+            while self.msgs.contains_key(&next_sequence) {
+                next_sequence += 1;
+            }
+        } else if strict && m.sequence() > next_sequence + MAX_NONCE_GAP {
+            return Err(Error::SequenceGap);
+        } else if m.sequence() > next_sequence {
+            sequence_gap = true;
         }
+
         if let Some(exms) = self.msgs.get(&m.sequence()) {
-            if m.cid()? != exms.cid()? {
-                let premium = exms.message().gas_premium();
-                let rbf_denom = BigInt::from(RBF_DENOM);
-                let min_price = premium + ((premium * RBF_NUM).div_floor(&rbf_denom)) + 1u8;
-                if m.message().gas_premium() <= &min_price {
-                    warn!("message gas price is below min gas price");
-                    return Err(Error::GasPriceTooLow);
-                }
-            } else {
+            if strict && sequence_gap {
+                return Err(Error::SequenceGap);
+            }
+            if m.cid()? == exms.cid()? {
                 warn!("try to add message with duplicate sequence increase gas premium");
                 return Err(Error::DuplicateSequence);
             }
+            if m.gas_premium() < &compute_min_rbf(exms.gas_premium(), rbf_ratio) {
+                return Err(Error::RbfPremiumTooLow);
+            }
+            if m.gas_fee_cap() < &compute_min_rbf(exms.gas_fee_cap(), rbf_ratio) {
+                return Err(Error::RbfFeeCapTooLow);
+            }
+            self.required_funds -= exms.required_funds();
+        } else if strict && self.msgs.len() >= MAX_ACTOR_PENDING_MESSAGES {
+            return Err(Error::TooManyPendingMessages);
         }
+
+        self.next_sequence = next_sequence;
+        self.required_funds += m.required_funds();
         self.msgs.insert(m.sequence(), m);
         Ok(())
     }
@@ -140,8 +174,18 @@ pub trait Provider {
     fn messages_for_tipset(&self, h: &Tipset) -> Result<Vec<ChainMessage>, Error>;
     /// Return a tipset given the tipset keys from the ChainStore
     fn load_tipset(&self, tsk: &TipsetKeys) -> Result<Tipset, Error>;
+    /// Return the receipts of the messages of the parent tipset of the given block header
+    fn parent_receipts(&self, h: &BlockHeader) -> Result<Vec<MessageReceipt>, Error>;
     /// Computes the base fee
     fn chain_compute_base_fee(&self, ts: &Tipset) -> Result<BigInt, Error>;
+    /// Persists entries of the local message journal
+    fn put_local_entries(&mut self, entries: &[(Cid, LocalMsgEntry)]) -> Result<(), Error>;
+    /// Removes entries from the local message journal
+    fn remove_local_entries(&mut self, cids: &[Cid]) -> Result<(), Error>;
+    /// Loads all entries of the local message journal
+    fn load_local_entries(&self) -> Result<Vec<(Cid, LocalMsgEntry)>, Error>;
+    /// Returns the network the chain belongs to
+    fn network_chain(&self) -> NetworkChain;
 }
 
 /// This is the Provider implementation that will be used for the mpool RPC
@@ -162,10 +206,18 @@ where
     }
 }
 
+/// Returns the Cid a signed message is stored under by `MpoolRpcProvider::put_message`, along
+/// with the Cid the message is referenced by, which differ for BLS messages.
+fn stored_message_cids(msg: &SignedMessage) -> Result<[Cid; 2], Error> {
+    let bytes = encoding::to_vec(msg).map_err(|e| Error::Other(e.to_string()))?;
+    let stored = Cid::new_from_cbor(&bytes, Blake2b256);
+    Ok([stored, msg.cid()?])
+}
+
 #[async_trait]
 impl<DB> Provider for MpoolRpcProvider<DB>
 where
-    DB: BlockStore + Sync + Send + 'static,
+    DB: BlockStore + IterableStore + Sync + Send + 'static,
 {
     async fn subscribe_head_changes(&mut self) -> Subscriber<HeadChange> {
         self.subscriber.clone()
@@ -208,9 +260,24 @@ where
         let ts = self.sm.chain_store().tipset_from_keys(tsk)?;
         Ok(ts)
     }
+    fn parent_receipts(&self, h: &BlockHeader) -> Result<Vec<MessageReceipt>, Error> {
+        chain::get_parent_receipts(self.sm.blockstore(), h).map_err(|err| err.into())
+    }
     fn chain_compute_base_fee(&self, ts: &Tipset) -> Result<BigInt, Error> {
         chain::compute_base_fee(self.sm.blockstore(), ts).map_err(|err| err.into())
     }
+    fn put_local_entries(&mut self, entries: &[(Cid, LocalMsgEntry)]) -> Result<(), Error> {
+        journal::put_local_entries(self.sm.blockstore(), entries)
+    }
+    fn remove_local_entries(&mut self, cids: &[Cid]) -> Result<(), Error> {
+        journal::remove_local_entries(self.sm.blockstore(), cids)
+    }
+    fn load_local_entries(&self) -> Result<Vec<(Cid, LocalMsgEntry)>, Error> {
+        journal::load_local_entries(self.sm.blockstore())
+    }
+    fn network_chain(&self) -> NetworkChain {
+        self.sm.network_chain()
+    }
     async fn state_account_key<V>(&self, addr: &Address, ts: &Tipset) -> Result<Address, Error>
     where
         V: ProofVerifier,
@@ -222,20 +289,39 @@ where
     }
 }
 
+/// Statistics on the pruning of the message pool.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PruneMetrics {
+    /// Number of times the pool has been pruned.
+    pub prunes: u64,
+    /// Number of prunes skipped because the pool was pruned within the cooldown.
+    pub skipped_prunes: u64,
+    /// Total number of messages evicted from the pool.
+    pub pruned_msgs: u64,
+    /// Duration of the last prune.
+    pub last_prune_duration: Duration,
+}
+
 /// This is the main MessagePool struct
 pub struct MessagePool<T> {
     local_addrs: Arc<RwLock<Vec<Address>>>,
-    pending: Arc<RwLock<HashMap<Address, MsgSet>>>,
+    pub(crate) pending: Arc<RwLock<HashMap<Address, MsgSet>>>,
     pub cur_tipset: Arc<RwLock<Arc<Tipset>>>,
-    api: Arc<RwLock<T>>,
+    pub(crate) api: Arc<RwLock<T>>,
     pub min_gas_price: BigInt,
     pub max_tx_pool_size: i64,
     pub network_name: String,
+    network_sender: Sender<NetworkMessage>,
     bls_sig_cache: Arc<RwLock<LruCache<Cid, Signature>>>,
     sig_val_cache: Arc<RwLock<LruCache<Cid, ()>>>,
-    // TODO look into adding a cap to local_msgs
-    local_msgs: Arc<RwLock<HashSet<SignedMessage>>>,
-    config: MpoolConfig,
+    /// Journal entries of locally submitted messages, keyed by the cid of the unsigned message
+    local_msgs: Arc<RwLock<HashMap<Cid, LocalMsgEntry>>>,
+    pub(crate) config: Arc<RwLock<MpoolConfig>>,
+    /// Gas price lists of the network, used to price the on-chain size of messages
+    pub(crate) price_schedule: Arc<PriceSchedule>,
+    /// Signals the pruning task that messages were added to the pool.
+    prune_trigger: Sender<()>,
+    prune_metrics: Arc<RwLock<PruneMetrics>>,
 }
 
 impl<T> MessagePool<T>
@@ -246,6 +332,7 @@ where
     pub async fn new(
         mut api: T,
         network_name: String,
+        network_sender: Sender<NetworkMessage>,
         config: MpoolConfig,
     ) -> Result<MessagePool<T>, Error>
     where
@@ -259,8 +346,11 @@ where
         )?));
         let bls_sig_cache = Arc::new(RwLock::new(LruCache::new(40000)));
         let sig_val_cache = Arc::new(RwLock::new(LruCache::new(32000)));
+        let price_schedule = Arc::new(PriceSchedule::for_chain(api.network_chain()));
+        let republish_interval = republish_interval(api.network_chain());
         let api_mutex = Arc::new(RwLock::new(api));
-        let local_msgs = Arc::new(RwLock::new(HashSet::new()));
+        let local_msgs = Arc::new(RwLock::new(HashMap::new()));
+        let (prune_trigger, prune_rx) = channel(1);
 
         let mut mp = MessagePool {
             local_addrs,
@@ -270,10 +360,14 @@ where
             min_gas_price: Default::default(),
             max_tx_pool_size: 5000,
             network_name,
+            network_sender,
             bls_sig_cache,
             sig_val_cache,
             local_msgs,
-            config,
+            config: Arc::new(RwLock::new(config)),
+            price_schedule,
+            prune_trigger,
+            prune_metrics: Default::default(),
         };
 
         mp.load_local().await?;
@@ -283,6 +377,8 @@ where
         let api = mp.api.clone();
         let bls_sig_cache = mp.bls_sig_cache.clone();
         let pending = mp.pending.clone();
+        let local_msgs = mp.local_msgs.clone();
+        let config = mp.config.clone();
 
         // TODO: Check this
         let cur_tipset = mp.cur_tipset.clone();
@@ -303,37 +399,170 @@ where
                             vec![tipset.as_ref().clone()],
                         ),
                     };
+                    let replace_by_fee_ratio = config.read().await.replace_by_fee_ratio();
                     head_change(
                         api.as_ref(),
                         bls_sig_cache.as_ref(),
                         pending.as_ref(),
                         &cur.as_ref(),
-                        rev,
-                        app,
+                        replace_by_fee_ratio,
+                        rev.clone(),
+                        app.clone(),
                     )
                     .await
                     .unwrap_or_else(|err| warn!("Error changing head: {:?}", err));
+                    update_local_msgs(api.as_ref(), local_msgs.as_ref(), &rev, &app)
+                        .await
+                        .unwrap_or_else(|err| warn!("Error updating local messages: {:?}", err));
                 }
             }
         });
+
+        let api = mp.api.clone();
+        let pending = mp.pending.clone();
+        let local_addrs = mp.local_addrs.clone();
+        let cur_tipset = mp.cur_tipset.clone();
+        let network_sender = mp.network_sender.clone();
+        let network_name = mp.network_name.clone();
+        let price_schedule = mp.price_schedule.clone();
+
+        task::spawn(async move {
+            loop {
+                task::sleep(republish_interval).await;
+                republish_pending_messages(
+                    api.as_ref(),
+                    pending.as_ref(),
+                    local_addrs.as_ref(),
+                    cur_tipset.as_ref(),
+                    price_schedule.as_ref(),
+                    &network_sender,
+                    &network_name,
+                )
+                .await
+                .unwrap_or_else(|err| warn!("Error republishing messages: {:?}", err));
+            }
+        });
+
+        let api = mp.api.clone();
+        let pending = mp.pending.clone();
+        let local_addrs = mp.local_addrs.clone();
+        let cur_tipset = mp.cur_tipset.clone();
+        let config = mp.config.clone();
+        let price_schedule = mp.price_schedule.clone();
+        let prune_metrics = mp.prune_metrics.clone();
+
+        task::spawn(async move {
+            let mut last_prune = None;
+            while prune_rx.recv().await.is_ok() {
+                prune_excess_messages(
+                    api.as_ref(),
+                    pending.as_ref(),
+                    local_addrs.as_ref(),
+                    cur_tipset.as_ref(),
+                    config.as_ref(),
+                    price_schedule.as_ref(),
+                    prune_metrics.as_ref(),
+                    &mut last_prune,
+                )
+                .await
+                .unwrap_or_else(|err| warn!("Error pruning messages: {:?}", err));
+            }
+        });
         Ok(mp)
     }
 
-    /// Add a signed message to local_addrs and local_msgs
+    /// Add a signed message to local_addrs and local_msgs, and persist it to the journal
     async fn add_local(&self, m: SignedMessage) -> Result<(), Error> {
-        self.local_addrs.write().await.push(*m.from());
-        self.local_msgs.write().await.insert(m);
+        {
+            let mut local_addrs = self.local_addrs.write().await;
+            if !local_addrs.contains(m.from()) {
+                local_addrs.push(*m.from());
+            }
+        }
+
+        if self.local_msgs.read().await.len() >= MAX_LOCAL_MSGS {
+            // Forget the local messages which were replaced or dropped from the pool
+            let stale: Vec<Cid> = {
+                let pending = self.pending.read().await;
+                let local_msgs = self.local_msgs.read().await;
+                local_msgs
+                    .iter()
+                    .filter(|(_, entry)| {
+                        let lm = &entry.message;
+                        entry.included_epoch.is_none()
+                            && pending
+                                .get(lm.from())
+                                .and_then(|mset| mset.msgs.get(&lm.sequence()))
+                                .map_or(true, |pm| pm != lm)
+                    })
+                    .map(|(cid, _)| *cid)
+                    .collect()
+            };
+            let mut local_msgs = self.local_msgs.write().await;
+            for cid in stale.iter() {
+                local_msgs.remove(cid);
+            }
+            self.api.write().await.remove_local_entries(&stale)?;
+        }
+
+        let added_epoch = self.cur_tipset.read().await.epoch();
+        let entry = (m.message().cid()?, LocalMsgEntry::new(m, added_epoch));
+        let mut local_msgs = self.local_msgs.write().await;
+        let mut api = self.api.write().await;
+
+        // The message replaces any pending local message with the same sequence
+        let mut removed: Vec<Cid> = local_msgs
+            .iter()
+            .filter(|(cid, local)| {
+                let lm = &local.message;
+                **cid != entry.0
+                    && local.included_epoch.is_none()
+                    && lm.from() == entry.1.message.from()
+                    && lm.sequence() == entry.1.message.sequence()
+            })
+            .map(|(cid, _)| *cid)
+            .collect();
+        for cid in removed.iter() {
+            local_msgs.remove(cid);
+        }
+
+        if local_msgs.len() >= MAX_LOCAL_MSGS {
+            // Make room for the new message by forgetting the oldest one
+            let oldest = local_msgs
+                .iter()
+                .min_by_key(|(_, local)| (local.added_epoch, local.message.sequence()))
+                .map(|(cid, _)| *cid);
+            if let Some(oldest) = oldest {
+                warn!(
+                    "local message limit reached, no longer tracking local message {}",
+                    oldest
+                );
+                local_msgs.remove(&oldest);
+                removed.push(oldest);
+            }
+        }
+
+        api.remove_local_entries(&removed)?;
+        api.put_local_entries(std::slice::from_ref(&entry))?;
+        local_msgs.insert(entry.0, entry.1);
         Ok(())
     }
 
-    /// Push a signed message to the MessagePool
+    /// Push a signed message to the MessagePool. The message is only published if its fee cap
+    /// meets the base fee lower bound, otherwise it is kept locally until it is republished.
     pub async fn push(&self, msg: SignedMessage) -> Result<Cid, Error> {
         self.check_message(&msg).await?;
         let cid = msg.cid().map_err(|err| Error::Other(err.to_string()))?;
-        self.add_tipset(msg.clone(), &self.cur_tipset.read().await.clone())
-            .await?;
-        self.add_local(msg).await?;
-        // TODO: Publish over Gossip
+        let cur_ts = self.cur_tipset.read().await.clone();
+        let price_list = self.price_schedule.price_list(cur_ts.epoch());
+        let publish = verify_msg_before_add(&msg, &cur_ts, price_list, true)?;
+        self.add_tipset(msg.clone(), &cur_ts).await?;
+        self.add_local(msg.clone()).await?;
+
+        if publish {
+            publish_msg(&self.network_sender, &self.network_name, &msg).await?;
+        }
+
         Ok(cid)
     }
 
@@ -366,7 +595,7 @@ where
 
     /// Add a SignedMessage without doing any of the checks
     pub async fn add_skip_checks(&mut self, m: SignedMessage) -> Result<(), Error> {
-        self.add_helper(m).await
+        self.add_helper(m, false).await
     }
 
     /// Verify the message signature. first check if it has already been verified and put into
@@ -400,13 +629,13 @@ where
         if balance < msg_balance {
             return Err(Error::NotEnoughFunds);
         }
-        self.add_helper(msg).await
+        self.add_helper(msg, true).await
     }
 
     /// Finish verifying signed message before adding it to the pending mset hashmap. If an entry
     /// in the hashmap does not yet exist, create a new mset that will correspond to the from message
     /// and push it to the pending hashmap
-    async fn add_helper(&self, msg: SignedMessage) -> Result<(), Error> {
+    async fn add_helper(&self, msg: SignedMessage, strict: bool) -> Result<(), Error> {
         let from = *msg.from();
         add_helper(
             self.api.as_ref(),
@@ -415,8 +644,19 @@ where
             msg,
             self.get_state_sequence(&from, &self.cur_tipset.read().await.clone())
                 .await?,
+            self.config.read().await.replace_by_fee_ratio(),
+            strict,
         )
-        .await
+        .await?;
+        // The pruning task checks the size of the pool, so a trigger already queued covers this
+        // message as well.
+        let _ = self.prune_trigger.try_send(());
+        Ok(())
+    }
+
+    /// Returns the statistics on the pruning of the pool.
+    pub async fn prune_metrics(&self) -> PruneMetrics {
+        self.prune_metrics.read().await.clone()
     }
 
     /// Get the sequence for a given address, return Error if there is a failure to retrieve sequence
@@ -480,13 +720,14 @@ where
             return Err(Error::TryAgain);
         }
 
-        let publish = verify_msg_before_add(&msg, &cur_ts, true)?;
+        let price_list = self.price_schedule.price_list(cur_ts.epoch());
+        let publish = verify_msg_before_add(&msg, &cur_ts, price_list, true)?;
         self.check_balance(&msg, &cur_ts).await?;
-        self.add_helper(msg.clone()).await?;
+        self.add_helper(msg.clone(), true).await?;
         self.add_local(msg.clone()).await?;
 
         if publish {
-            // TODO: Implement this, Publish message through gossipsub
+            publish_msg(&self.network_sender, &self.network_name, &msg).await?;
         }
 
         Ok(msg)
@@ -557,6 +798,22 @@ where
         Some(msg_vec)
     }
 
+    /// Returns the Cids of the messages in the pool and in the local message journal. These are
+    /// stored in the blockstore without being reachable from the chain until they are included,
+    /// so they have to be retained by garbage collection.
+    pub async fn message_cids(&self) -> Result<Vec<Cid>, Error> {
+        let mut cids = Vec::new();
+        for mset in self.pending.read().await.values() {
+            for msg in mset.msgs.values() {
+                cids.extend_from_slice(&stored_message_cids(msg)?);
+            }
+        }
+        for entry in self.local_msgs.read().await.values() {
+            cids.extend_from_slice(&stored_message_cids(&entry.message)?);
+        }
+        Ok(cids)
+    }
+
     /// Return Vector of signed messages given a block header for self
     pub async fn messages_for_blocks(
         &self,
@@ -577,83 +834,89 @@ where
         Ok(msg_vec)
     }
 
-    /// Return gas price estimate this has been translated from lotus, a more smart implementation will
-    /// most likely need to be implemented
-    // TODO: UPDATE
-    pub fn estimate_gas_premium(
-        &self,
-        nblocksincl: u64,
-        _sender: Address,
-        _gas_limit: u64,
-        _tsk: TipsetKeys,
-    ) -> Result<BigInt, Error> {
-        // TODO possibly come up with a smarter way to estimate the gas price
-        // TODO a smarter way exists now
-        let min_gas_price = 0;
-        match nblocksincl {
-            0 => Ok(BigInt::from(min_gas_price + 2)),
-            1 => Ok(BigInt::from(min_gas_price + 1)),
-            _ => Ok(BigInt::from(min_gas_price)),
-        }
-    }
-
-    /// local_message field
+    /// Loads the local message journal. Messages which have not been included are added back to
+    /// the pool, unless the sequence of the sender has moved past them.
     pub async fn load_local(&mut self) -> Result<(), Error> {
-        let mut local_msgs = self.local_msgs.write().await;
-        let mut rm_vec = Vec::new();
-        let msg_vec: Vec<SignedMessage> = local_msgs.iter().cloned().collect();
-
-        for k in msg_vec.into_iter() {
-            // TODO no need to clone message, if error, message could be returned in error
-            self.add(k.clone()).await.unwrap_or_else(|err| {
-                if err == Error::SequenceTooLow {
-                    warn!("error adding message: {:?}", err);
-                    rm_vec.push(k);
+        let entries = self.api.read().await.load_local_entries()?;
+        let cur_ts = self.cur_tipset.read().await.clone();
+        let mut included = Vec::new();
+        for (cid, entry) in entries {
+            let from = *entry.message.from();
+            if entry.included_epoch.is_none() {
+                match self.get_state_sequence(&from, &cur_ts).await {
+                    Ok(sequence) if entry.message.sequence() < sequence => {
+                        // The message, or one replacing it, was included while offline
+                        included.push(cid);
+                        continue;
+                    }
+                    Ok(_) => {
+                        if let Err(err) = self.add(entry.message.clone()).await {
+                            warn!("error adding local message {}: {:?}", cid, err);
+                        }
+                    }
+                    Err(err) => warn!("error loading sequence of {}: {:?}", from, err),
                 }
-            })
-        }
-
-        for item in rm_vec {
-            local_msgs.remove(&item);
+            }
+            let mut local_addrs = self.local_addrs.write().await;
+            if !local_addrs.contains(&from) {
+                local_addrs.push(from);
+            }
+            self.local_msgs.write().await.insert(cid, entry);
         }
+        self.api.write().await.remove_local_entries(&included)?;
 
         Ok(())
     }
     /// If `local = true`, the local messages will be removed as well as pending messages.
     /// If `local = false`, pending messages will be removed while retaining local messages.
-    pub async fn clear(&mut self, local: bool) {
+    pub async fn clear(&self, local: bool) -> Result<(), Error> {
         if local {
-            let local_addrs = self.local_addrs.read().await;
-            for a in local_addrs.iter() {
-                if let Some(mset) = self.pending.read().await.get(&a) {
-                    for m in mset.msgs.values() {
-                        if !self.local_msgs.write().await.remove(&m) {
-                            warn!("error deleting local message");
+            let local_cids = {
+                let local_addrs = self.local_addrs.read().await;
+                let pending = self.pending.read().await;
+                let mut cids = Vec::new();
+                for a in local_addrs.iter() {
+                    if let Some(mset) = pending.get(&a) {
+                        for m in mset.msgs.values() {
+                            cids.push(m.message().cid()?);
                         }
                     }
                 }
+                cids
+            };
+            let mut local_msgs = self.local_msgs.write().await;
+            for cid in local_cids.iter() {
+                if local_msgs.remove(cid).is_none() {
+                    warn!("error deleting local message");
+                }
             }
+            self.api.write().await.remove_local_entries(&local_cids)?;
             self.pending.write().await.clear();
         } else {
             let mut pending = self.pending.write().await;
             let local_addrs = self.local_addrs.read().await;
             pending.retain(|a, _| local_addrs.contains(&a));
         }
+        Ok(())
     }
-    pub fn get_config(&self) -> &MpoolConfig {
-        &self.config
+    pub async fn get_config(&self) -> MpoolConfig {
+        self.config.read().await.clone()
     }
-    pub fn set_config<DB: Store>(&mut self, db: &DB, cfg: MpoolConfig) -> Result<(), Error> {
+    pub async fn set_config<DB: Store>(&self, db: &DB, cfg: MpoolConfig) -> Result<(), Error> {
         cfg.save_config(db)
             .map_err(|e| Error::Other(e.to_string()))?;
-        self.config = cfg;
+        *self.config.write().await = cfg;
         Ok(())
     }
 }
 
-fn verify_msg_before_add(m: &SignedMessage, cur_ts: &Tipset, local: bool) -> Result<bool, Error> {
-    let epoch = cur_ts.epoch();
-    let min_gas = interpreter::price_list_by_epoch(epoch).on_chain_message(m.marshal_cbor()?.len());
+fn verify_msg_before_add(
+    m: &SignedMessage,
+    cur_ts: &Tipset,
+    price_list: &PriceList,
+    local: bool,
+) -> Result<bool, Error> {
+    let min_gas = price_list.on_chain_message(m.marshal_cbor()?.len());
     m.message()
         .valid_for_block_inclusion(min_gas.total())
         .map_err(Error::Other)?;
@@ -674,6 +937,13 @@ fn verify_msg_before_add(m: &SignedMessage, cur_ts: &Tipset, local: bool) -> Res
     Ok(local)
 }
 
+/// Returns the minimum value a replacing message has to bid, given the value of the replaced
+/// message.
+fn compute_min_rbf(cur: &BigInt, rbf_ratio: f64) -> BigInt {
+    let rbf_num = ((rbf_ratio - 1.0) * RBF_DENOM as f64) as u64;
+    cur + (cur * rbf_num).div_floor(&BigInt::from(RBF_DENOM)) + 1u8
+}
+
 fn get_base_fee_lower_bound(base_fee: &BigInt, factor: i64) -> BigInt {
     let base_fee_lower_bound = base_fee.div_floor(&BigInt::from(factor));
     if base_fee_lower_bound < *MINIMUM_BASE_FEE {
@@ -726,6 +996,8 @@ async fn add_helper<T>(
     pending: &RwLock<HashMap<Address, MsgSet>>,
     msg: SignedMessage,
     sequence: u64,
+    rbf_ratio: f64,
+    strict: bool,
 ) -> Result<(), Error>
 where
     T: Provider,
@@ -748,11 +1020,11 @@ where
     let mut pending = pending.write().await;
     let msett = pending.get_mut(msg.message().from());
     match msett {
-        Some(mset) => mset.add(msg)?,
+        Some(mset) => mset.add(msg, rbf_ratio, strict)?,
         None => {
             let mut mset = MsgSet::new(sequence);
             let from = *msg.message().from();
-            mset.add(msg)?;
+            mset.add(msg, rbf_ratio, strict)?;
             pending.insert(from, mset);
         }
     }
@@ -781,6 +1053,7 @@ pub async fn head_change<T>(
     bls_sig_cache: &RwLock<LruCache<Cid, Signature>>,
     pending: &RwLock<HashMap<Address, MsgSet>>,
     cur_tipset: &RwLock<Arc<Tipset>>,
+    replace_by_fee_ratio: f64,
     revert: Vec<Tipset>,
     apply: Vec<Tipset>,
 ) -> Result<(), Error>
@@ -826,7 +1099,18 @@ where
         for (_, msg) in hm {
             let sequence =
                 get_state_sequence(api, &msg.from(), &cur_tipset.read().await.clone()).await?;
-            if let Err(e) = add_helper(api, bls_sig_cache, pending, msg, sequence).await {
+            // Messages from reverted tipsets were valid on chain, so they skip the strict checks
+            if let Err(e) = add_helper(
+                api,
+                bls_sig_cache,
+                pending,
+                msg,
+                sequence,
+                replace_by_fee_ratio,
+                false,
+            )
+            .await
+            {
                 error!("Failed to readd message from reorg to mpool: {}", e);
             }
         }
@@ -834,6 +1118,290 @@ where
     Ok(())
 }
 
+/// Interval between republishing local messages, about ten epochs of the network.
+fn republish_interval(chain: NetworkChain) -> Duration {
+    Duration::from_secs(10 * chain.block_delay_secs() + PROPAGATION_DELAY_SECS)
+}
+
+/// Publishes the message over gossipsub on the messages topic of the network.
+async fn publish_msg(
+    network_sender: &Sender<NetworkMessage>,
+    network_name: &str,
+    msg: &SignedMessage,
+) -> Result<(), Error> {
+    network_sender
+        .send(NetworkMessage::PubsubMessage {
+            topic: Topic::new(format!("{}/{}", PUBSUB_MSG_STR, network_name)),
+            message: msg.marshal_cbor()?,
+        })
+        .await;
+    Ok(())
+}
+
+/// Republishes the pending messages of local addresses, so they are not lost when peers drop them.
+/// Only messages which fit in a block and have a fee cap which can cover the base fee within the
+/// next blocks are republished, up to a limit.
+pub async fn republish_pending_messages<T>(
+    api: &RwLock<T>,
+    pending: &RwLock<HashMap<Address, MsgSet>>,
+    local_addrs: &RwLock<Vec<Address>>,
+    cur_tipset: &RwLock<Arc<Tipset>>,
+    price_schedule: &PriceSchedule,
+    network_sender: &Sender<NetworkMessage>,
+    network_name: &str,
+) -> Result<(), Error>
+where
+    T: Provider + 'static,
+{
+    let ts = cur_tipset.read().await.clone();
+    let api = api.read().await;
+    let base_fee = api.chain_compute_base_fee(&ts)?;
+    let base_fee_lower_bound = get_base_fee_lower_bound(&base_fee, BASE_FEE_LOWER_BOUND_FACTOR);
+
+    let local: HashSet<Address> = local_addrs.read().await.iter().copied().collect();
+    let mut local_pending = HashMap::new();
+    for (addr, mset) in pending.read().await.iter() {
+        if local.contains(addr) {
+            let mut msgs: Vec<SignedMessage> = mset.msgs.values().cloned().collect();
+            msgs.sort_by_key(|m| m.sequence());
+            local_pending.insert(*addr, msgs);
+        }
+    }
+    if local_pending.is_empty() {
+        return Ok(());
+    }
+
+    let price_list = price_schedule.price_list(ts.epoch());
+    let included = included_sequences(&*api, &ts)?;
+    let mut chains = Chains::default();
+    for (addr, msgs) in local_pending {
+        chains.extend(create_chains(
+            &*api, &addr, msgs, &base_fee, &ts, &included, price_list,
+        )?);
+    }
+    let mut order = sorted_chains(&chains, MsgChain::compare);
+
+    let mut gas_limit = BLOCK_GAS_LIMIT;
+    let mut msgs = Vec::new();
+    let mut i = 0;
+    while i < order.len() {
+        if msgs.len() >= REPUB_MSG_LIMIT || gas_limit <= MIN_GAS {
+            break;
+        }
+        let chain = chains.get(order[i]);
+        if !chain.valid {
+            i += 1;
+            continue;
+        }
+        if chain.gas_limit <= gas_limit {
+            // Only republish messages which can be included within the next blocks
+            let eligible = chain
+                .msgs
+                .iter()
+                .take_while(|m| m.gas_fee_cap() >= &base_fee_lower_bound)
+                .count();
+            for m in &chain.msgs[..eligible] {
+                if msgs.len() >= REPUB_MSG_LIMIT {
+                    break;
+                }
+                gas_limit -= m.gas_limit();
+                msgs.push(m.clone());
+            }
+            if eligible < chain.msgs.len() {
+                chains.invalidate(order[i]);
+            }
+            i += 1;
+            continue;
+        }
+
+        // The chain does not fit, trim it and push it down
+        chains.trim(order[i], gas_limit, &base_fee);
+        for j in i..order.len() - 1 {
+            if chains.get(order[j]).compare(chains.get(order[j + 1])) == Ordering::Less {
+                break;
+            }
+            order.swap(j, j + 1);
+        }
+    }
+
+    info!("republishing {} messages", msgs.len());
+    for m in msgs.iter() {
+        publish_msg(network_sender, network_name, m).await?;
+    }
+    Ok(())
+}
+
+/// Prunes the pool once the number of pending messages reaches the high watermark, unless it
+/// has already been pruned within the cooldown. The messages of the lowest performing chains are
+/// evicted until the pool is down to the low watermark.
+async fn prune_excess_messages<T>(
+    api: &RwLock<T>,
+    pending: &RwLock<HashMap<Address, MsgSet>>,
+    local_addrs: &RwLock<Vec<Address>>,
+    cur_tipset: &RwLock<Arc<Tipset>>,
+    config: &RwLock<MpoolConfig>,
+    price_schedule: &PriceSchedule,
+    metrics: &RwLock<PruneMetrics>,
+    last_prune: &mut Option<Instant>,
+) -> Result<(), Error>
+where
+    T: Provider + 'static,
+{
+    let config = config.read().await.clone();
+    let size: usize = pending.read().await.values().map(|m| m.msgs.len()).sum();
+    if (size as i64) < config.size_limit_high() {
+        return Ok(());
+    }
+    if let Some(last) = last_prune {
+        if last.elapsed() < config.prune_cooldown() {
+            metrics.write().await.skipped_prunes += 1;
+            return Ok(());
+        }
+    }
+    *last_prune = Some(Instant::now());
+
+    let start = Instant::now();
+    let ts = cur_tipset.read().await.clone();
+
+    // Messages from local and priority addresses are never evicted.
+    let mut protected: HashSet<Address> = config.priority_addrs().iter().copied().collect();
+    protected.extend(local_addrs.read().await.iter());
+
+    let by_sender: Vec<(Address, Vec<SignedMessage>)> = pending
+        .read()
+        .await
+        .iter()
+        .map(|(addr, mset)| {
+            let mut msgs: Vec<SignedMessage> = mset.msgs.values().cloned().collect();
+            msgs.sort_by_key(|m| m.sequence());
+            (*addr, msgs)
+        })
+        .collect();
+
+    let mut keep_count = 0;
+    let mut prune_msgs = HashMap::new();
+    let mut chains = Chains::default();
+    {
+        let api = api.read().await;
+        let base_fee = api.chain_compute_base_fee(&ts)?;
+        let price_list = price_schedule.price_list(ts.epoch());
+        let included = included_sequences(&*api, &ts)?;
+        for (addr, msgs) in by_sender {
+            if protected.contains(&addr) {
+                keep_count += msgs.len() as i64;
+                continue;
+            }
+            for m in msgs.iter() {
+                prune_msgs.insert((addr, m.sequence()), m.cid()?);
+            }
+            chains.extend(create_chains(
+                &*api, &addr, msgs, &base_fee, &ts, &included, price_list,
+            )?);
+        }
+    }
+
+    // Keep the best performing chains while under the low watermark
+    let size_limit_low = config.size_limit_low();
+    'keep: for i in sorted_chains(&chains, MsgChain::compare) {
+        for m in chains.get(i).msgs.iter() {
+            if keep_count >= size_limit_low {
+                break 'keep;
+            }
+            prune_msgs.remove(&(*m.from(), m.sequence()));
+            keep_count += 1;
+        }
+    }
+
+    info!("pruning {} messages", prune_msgs.len());
+    let mut pruned_msgs = 0;
+    {
+        let mut pending = pending.write().await;
+        for ((from, sequence), cid) in prune_msgs {
+            let mset = match pending.get_mut(&from) {
+                Some(mset) => mset,
+                None => continue,
+            };
+            // Messages replaced since the snapshot was taken are kept
+            let unchanged = match mset.msgs.get(&sequence) {
+                Some(m) => m.cid()? == cid,
+                None => false,
+            };
+            if unchanged {
+                mset.rm(sequence, false);
+                pruned_msgs += 1;
+            }
+            if mset.msgs.is_empty() {
+                pending.remove(&from);
+            }
+        }
+    }
+
+    let mut metrics = metrics.write().await;
+    metrics.prunes += 1;
+    metrics.pruned_msgs += pruned_msgs;
+    metrics.last_prune_duration = start.elapsed();
+    Ok(())
+}
+
+/// Tracks the inclusion of local messages in the reverted and applied tipsets, and removes the
+/// journal entries of messages once their inclusion is final.
+pub async fn update_local_msgs<T>(
+    api: &RwLock<T>,
+    local_msgs: &RwLock<HashMap<Cid, LocalMsgEntry>>,
+    revert: &[Tipset],
+    apply: &[Tipset],
+) -> Result<(), Error>
+where
+    T: Provider + 'static,
+{
+    let mut local_msgs = local_msgs.write().await;
+    if local_msgs.is_empty() {
+        return Ok(());
+    }
+    let mut api = api.write().await;
+
+    // The journal is updated once for the whole head change
+    let mut updated = HashSet::new();
+    let changes = revert
+        .iter()
+        .map(|ts| (ts, None))
+        .chain(apply.iter().map(|ts| (ts, Some(ts.epoch()))));
+    for (ts, included_epoch) in changes {
+        for msg in api.messages_for_tipset(ts)? {
+            let cid = match msg {
+                ChainMessage::Signed(smsg) => smsg.message().cid()?,
+                ChainMessage::Unsigned(umsg) => umsg.cid()?,
+            };
+            if let Some(entry) = local_msgs.get_mut(&cid) {
+                entry.included_epoch = included_epoch;
+                updated.insert(cid);
+            }
+        }
+    }
+    let entries: Vec<(Cid, LocalMsgEntry)> = updated
+        .into_iter()
+        .map(|cid| (cid, local_msgs[&cid].clone()))
+        .collect();
+    api.put_local_entries(&entries)?;
+
+    if let Some(head) = apply.last() {
+        let finalized: Vec<Cid> = local_msgs
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .included_epoch
+                    .map_or(false, |epoch| epoch + CHAIN_FINALITY <= head.epoch())
+            })
+            .map(|(cid, _)| *cid)
+            .collect();
+        for cid in finalized.iter() {
+            local_msgs.remove(cid);
+        }
+        api.remove_local_entries(&finalized)?;
+    }
+    Ok(())
+}
+
 /// This is a helper method for head_change. This method will remove a sequence for a from address
 /// from the rmsgs hashmap. Also remove the from address and sequence from the messagepool.
 async fn rm(
@@ -871,6 +1439,7 @@ pub mod test_provider {
     use address::Address;
     use blocks::{BlockHeader, Tipset};
     use cid::Cid;
+    use db::MemoryDB;
     use flo_stream::{MessagePublisher, Publisher, Subscriber};
     use message::{SignedMessage, UnsignedMessage};
 
@@ -878,7 +1447,10 @@ pub mod test_provider {
     pub struct TestApi {
         bmsgs: HashMap<Cid, Vec<SignedMessage>>,
         state_sequence: HashMap<Address, u64>,
+        balances: HashMap<Address, BigInt>,
+        db: MemoryDB,
         tipsets: Vec<Tipset>,
+        receipts: HashMap<Cid, Vec<MessageReceipt>>,
         publisher: Publisher<HeadChange>,
     }
 
@@ -888,7 +1460,10 @@ pub mod test_provider {
             TestApi {
                 bmsgs: HashMap::new(),
                 state_sequence: HashMap::new(),
+                balances: HashMap::new(),
+                db: MemoryDB::default(),
                 tipsets: Vec::new(),
+                receipts: HashMap::new(),
                 publisher: Publisher::new(1),
             }
         }
@@ -900,12 +1475,22 @@ pub mod test_provider {
             self.state_sequence.insert(*addr, sequence);
         }
 
+        /// Set the balance of an Address for TestApi
+        pub fn set_state_balance(&mut self, addr: &Address, balance: BigInt) {
+            self.balances.insert(*addr, balance);
+        }
+
         /// Set the block messages for TestApi
         pub fn set_block_messages(&mut self, h: &BlockHeader, msgs: Vec<SignedMessage>) {
             self.bmsgs.insert(*h.cid(), msgs);
             self.tipsets.push(Tipset::new(vec![h.clone()]).unwrap())
         }
 
+        /// Set the receipts of the parent messages of a block header for TestApi
+        pub fn set_parent_receipts(&mut self, h: &BlockHeader, receipts: Vec<MessageReceipt>) {
+            self.receipts.insert(*h.cid(), receipts);
+        }
+
         /// Set the heaviest tipset for TestApi
         pub async fn set_heaviest_tipset(&mut self, ts: Arc<Tipset>) {
             self.publisher.publish(HeadChange::Apply(ts)).await
@@ -947,13 +1532,12 @@ pub mod test_provider {
                 }
                 sequence += 1;
             }
-            let actor = ActorState::new(
-                Cid::default(),
-                Cid::default(),
-                // TODO balance not handled in tests
-                BigInt::from(9_000_000 as u64),
-                sequence,
-            );
+            let balance = self
+                .balances
+                .get(addr)
+                .cloned()
+                .unwrap_or_else(|| BigInt::from(9_000_000 as u64));
+            let actor = ActorState::new(Cid::default(), Cid::default(), balance, sequence);
             Ok(actor)
         }
 
@@ -1006,9 +1590,29 @@ pub mod test_provider {
             Err(Errors::InvalidToAddr)
         }
 
+        fn parent_receipts(&self, h: &BlockHeader) -> Result<Vec<MessageReceipt>, Errors> {
+            Ok(self.receipts.get(h.cid()).cloned().unwrap_or_default())
+        }
+
         fn chain_compute_base_fee(&self, _ts: &Tipset) -> Result<BigInt, Error> {
             Ok(100.into())
         }
+
+        fn put_local_entries(&mut self, entries: &[(Cid, LocalMsgEntry)]) -> Result<(), Error> {
+            journal::put_local_entries(&self.db, entries)
+        }
+
+        fn remove_local_entries(&mut self, cids: &[Cid]) -> Result<(), Error> {
+            journal::remove_local_entries(&self.db, cids)
+        }
+
+        fn load_local_entries(&self) -> Result<Vec<(Cid, LocalMsgEntry)>, Error> {
+            journal::load_local_entries(&self.db)
+        }
+
+        fn network_chain(&self) -> NetworkChain {
+            NetworkChain::Mainnet
+        }
     }
 
     pub fn create_header(weight: u64, parent_bz: &[u8], cached_bytes: &[u8]) -> BlockHeader {
@@ -1026,19 +1630,25 @@ pub mod test_provider {
 pub mod tests {
     use super::test_provider::*;
     use super::*;
-    use crate::MessagePool;
+    use crate::{MessagePool, MIN_GAS_PREMIUM};
     use address::Address;
+    use async_std::sync::channel;
     use async_std::task;
     use blocks::{BlockHeader, ElectionProof, Ticket, Tipset};
+    use chain::ChainStore;
     use cid::Cid;
     use crypto::{SignatureType, VRFProof};
+    use db::MemoryDB;
     use key_management::{MemKeyStore, Wallet};
     use message::{SignedMessage, UnsignedMessage};
     use num_bigint::BigInt;
+    use state_manager::ChainGc;
     use std::borrow::BorrowMut;
     use std::convert::TryFrom;
     use std::thread::sleep;
     use std::time::Duration;
+    use types::verifier::MockVerifier;
+    use vm::{ExitCode, Serialized};
 
     fn create_smsg(
         to: &Address,
@@ -1060,6 +1670,26 @@ pub mod tests {
         smsg
     }
 
+    fn create_fee_smsg(
+        from: &Address,
+        wallet: &mut Wallet<MemKeyStore>,
+        sequence: u64,
+        gas_fee_cap: u64,
+        gas_premium: u64,
+    ) -> SignedMessage {
+        let umsg = UnsignedMessage::builder()
+            .to(Address::new_id(1001))
+            .from(*from)
+            .sequence(sequence)
+            .gas_limit(1_000_000)
+            .gas_fee_cap(gas_fee_cap.into())
+            .gas_premium(gas_premium.into())
+            .build()
+            .unwrap();
+        let sig = wallet.sign(from, &umsg.to_signing_bytes()).unwrap();
+        SignedMessage::new_from_parts(umsg, sig).unwrap()
+    }
+
     fn mock_block(weight: u64, ticket_sequence: u64) -> BlockHeader {
         let addr = Address::new_id(1234561);
         let c =
@@ -1123,8 +1753,10 @@ pub mod tests {
         let mut tma = TestApi::default();
         tma.set_state_sequence(&sender, 0);
 
+        let (tx, _rx) = channel(50);
+
         task::block_on(async move {
-            let mpool = MessagePool::new(tma, "mptest".to_string(), Default::default())
+            let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
                 .await
                 .unwrap();
             let mut smsg_vec = Vec::new();
@@ -1153,6 +1785,7 @@ pub mod tests {
                 bls_sig_cache.as_ref(),
                 pending.as_ref(),
                 cur_tipset.as_ref(),
+                mpool.get_config().await.replace_by_fee_ratio(),
                 Vec::new(),
                 vec![Tipset::new(vec![a]).unwrap()],
             )
@@ -1182,8 +1815,10 @@ pub mod tests {
             smsg_vec.push(msg);
         }
 
+        let (tx, _rx) = channel(50);
+
         task::block_on(async move {
-            let mpool = MessagePool::new(tma, "mptest".to_string(), Default::default())
+            let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
                 .await
                 .unwrap();
 
@@ -1210,6 +1845,7 @@ pub mod tests {
                 bls_sig_cache.as_ref(),
                 pending.as_ref(),
                 cur_tipset.as_ref(),
+                mpool.get_config().await.replace_by_fee_ratio(),
                 Vec::new(),
                 vec![Tipset::new(vec![a]).unwrap()],
             )
@@ -1230,6 +1866,7 @@ pub mod tests {
                 bls_sig_cache.as_ref(),
                 pending.as_ref(),
                 cur_tipset.as_ref(),
+                mpool.get_config().await.replace_by_fee_ratio(),
                 Vec::new(),
                 vec![Tipset::new(vec![b.clone()]).unwrap()],
             )
@@ -1245,6 +1882,7 @@ pub mod tests {
                 bls_sig_cache.as_ref(),
                 pending.as_ref(),
                 cur_tipset.as_ref(),
+                mpool.get_config().await.replace_by_fee_ratio(),
                 vec![Tipset::new(vec![b]).unwrap()],
                 Vec::new(),
             )
@@ -1263,19 +1901,21 @@ pub mod tests {
         let keystore = MemKeyStore::new();
         let mut wallet = Wallet::new(keystore);
         let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
-        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
 
         let mut tma = TestApi::default();
         tma.set_state_sequence(&sender, 0);
+        tma.set_state_balance(&sender, BigInt::from(10u64.pow(18)));
+
+        let (tx, rx) = channel(50);
 
         task::block_on(async move {
-            let mpool = MessagePool::new(tma, "mptest".to_string(), Default::default())
+            let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
                 .await
                 .unwrap();
 
             let mut smsg_vec = Vec::new();
             for i in 0..3 {
-                let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i);
+                let msg = create_fee_smsg(&sender, &mut wallet, i, 100, 0);
                 smsg_vec.push(msg);
             }
 
@@ -1287,6 +1927,17 @@ pub mod tests {
             mpool.push(smsg_vec[2].clone()).await.unwrap();
             assert_eq!(mpool.get_sequence(&sender).await.unwrap(), 3);
 
+            // Pushed messages are published over gossipsub
+            for smsg in smsg_vec.iter() {
+                match rx.recv().await.unwrap() {
+                    NetworkMessage::PubsubMessage { topic, message } => {
+                        assert_eq!(topic.to_string(), format!("{}/mptest", PUBSUB_MSG_STR));
+                        assert_eq!(message, smsg.marshal_cbor().unwrap());
+                    }
+                    _ => panic!("expected pubsub message"),
+                }
+            }
+
             let header = mock_block(1, 1);
             let tipset = Tipset::new(vec![header.clone()]).unwrap();
 
@@ -1305,4 +1956,506 @@ pub mod tests {
             assert_eq!(cur_ts.as_ref(), &tipset);
         })
     }
+
+    #[test]
+    fn test_republish_local_messages() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        tma.set_state_balance(&sender, BigInt::from(10u64.pow(18)));
+
+        let (tx, rx) = channel(50);
+        task::block_on(async move {
+            let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                .await
+                .unwrap();
+
+            // Only the messages before the first one below the base fee lower bound are published
+            for (i, fee_cap) in [200, 200, 50, 200].iter().enumerate() {
+                let smsg = create_fee_smsg(&sender, &mut wallet, i as u64, *fee_cap, 0);
+                mpool.add_helper(smsg.clone(), true).await.unwrap();
+                mpool.add_local(smsg).await.unwrap();
+            }
+
+            republish_pending_messages(
+                mpool.api.as_ref(),
+                mpool.pending.as_ref(),
+                mpool.local_addrs.as_ref(),
+                mpool.cur_tipset.as_ref(),
+                mpool.price_schedule.as_ref(),
+                &mpool.network_sender,
+                &mpool.network_name,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(rx.len(), 2);
+            for i in 0..2 {
+                match rx.recv().await.unwrap() {
+                    NetworkMessage::PubsubMessage { message, .. } => {
+                        let smsg = SignedMessage::unmarshal_cbor(&message).unwrap();
+                        assert_eq!(smsg.sequence(), i);
+                    }
+                    _ => panic!("expected pubsub message"),
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_republish_interval() {
+        assert_eq!(
+            republish_interval(NetworkChain::Mainnet),
+            Duration::from_secs(306)
+        );
+        assert_eq!(
+            republish_interval(NetworkChain::Devnet),
+            Duration::from_secs(46)
+        );
+    }
+
+    #[test]
+    fn test_republish_limit() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        tma.set_state_balance(&sender, BigInt::from(10u64.pow(18)));
+
+        let (tx, rx) = channel(50);
+        task::block_on(async move {
+            let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                .await
+                .unwrap();
+
+            for i in 0..(REPUB_MSG_LIMIT as u64 + 10) {
+                let smsg = create_fee_smsg(&sender, &mut wallet, i, 200, 0);
+                mpool.add_helper(smsg.clone(), true).await.unwrap();
+                mpool.add_local(smsg).await.unwrap();
+            }
+
+            republish_pending_messages(
+                mpool.api.as_ref(),
+                mpool.pending.as_ref(),
+                mpool.local_addrs.as_ref(),
+                mpool.cur_tipset.as_ref(),
+                mpool.price_schedule.as_ref(),
+                &mpool.network_sender,
+                &mpool.network_name,
+            )
+            .await
+            .unwrap();
+
+            assert_eq!(rx.len(), REPUB_MSG_LIMIT);
+        })
+    }
+
+    /// Waits until the pruning task has updated the prune metrics to satisfy the condition.
+    async fn wait_for_prune<T, F>(mpool: &MessagePool<T>, cond: F) -> PruneMetrics
+    where
+        T: Provider + Send + Sync + 'static,
+        F: Fn(&PruneMetrics) -> bool,
+    {
+        for _ in 0..100 {
+            let metrics = mpool.prune_metrics().await;
+            if cond(&metrics) {
+                return metrics;
+            }
+            task::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("pruning task did not update the metrics");
+    }
+
+    #[test]
+    fn test_prune_excess_messages() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let priority = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        for addr in [sender, priority].iter() {
+            tma.set_state_sequence(addr, 0);
+            tma.set_state_balance(addr, BigInt::from(10u64.pow(18)));
+        }
+        let config =
+            MpoolConfig::new(vec![priority], 10, 5, 1.25, Duration::from_secs(60), 1.25).unwrap();
+
+        let (tx, _rx) = channel(50);
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(tma, "mptest".to_string(), tx, config)
+                .await
+                .unwrap();
+
+            let mut msgs = Vec::new();
+            for i in 0..6 {
+                msgs.push((sender, i));
+            }
+            for i in 0..6 {
+                msgs.push((priority, i));
+            }
+            let mut msgs = msgs.into_iter();
+
+            // The pool is pruned once it reaches 10 messages, keeping the 4 messages of the
+            // priority address and the best performing message of the other sender.
+            for (from, sequence) in msgs.by_ref().take(10) {
+                let smsg = create_fee_smsg(&from, &mut wallet, sequence, 200, 10);
+                mpool.add_skip_checks(smsg).await.unwrap();
+            }
+            wait_for_prune(&mpool, |m| m.prunes == 1).await;
+
+            // The remaining priority messages grow the pool back to 7 messages, which is below
+            // the high watermark, so they are all kept.
+            for (from, sequence) in msgs {
+                let smsg = create_fee_smsg(&from, &mut wallet, sequence, 200, 10);
+                mpool.add_skip_checks(smsg).await.unwrap();
+            }
+            task::sleep(Duration::from_millis(100)).await;
+
+            let sequences = |msgs: Option<Vec<SignedMessage>>| {
+                msgs.unwrap_or_default()
+                    .iter()
+                    .map(|m| m.sequence())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(sequences(mpool.pending_for(&sender).await), vec![0]);
+            assert_eq!(
+                sequences(mpool.pending_for(&priority).await),
+                (0..6).collect::<Vec<_>>()
+            );
+            let metrics = mpool.prune_metrics().await;
+            assert_eq!(metrics.prunes, 1);
+            assert_eq!(metrics.pruned_msgs, 5);
+            assert_eq!(metrics.skipped_prunes, 0);
+        })
+    }
+
+    #[test]
+    fn test_prune_cooldown() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        tma.set_state_balance(&sender, BigInt::from(10u64.pow(18)));
+        let config =
+            MpoolConfig::new(Vec::new(), 10, 5, 1.25, Duration::from_secs(60), 1.25).unwrap();
+
+        let (tx, _rx) = channel(50);
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(tma, "mptest".to_string(), tx, config)
+                .await
+                .unwrap();
+
+            for i in 0..10 {
+                let smsg = create_fee_smsg(&sender, &mut wallet, i, 200, 10);
+                mpool.add_skip_checks(smsg).await.unwrap();
+            }
+            wait_for_prune(&mpool, |m| m.prunes == 1).await;
+
+            // Reaching the high watermark again within the cooldown does not prune the pool
+            for i in 5..15 {
+                let smsg = create_fee_smsg(&sender, &mut wallet, i, 200, 10);
+                mpool.add_skip_checks(smsg).await.unwrap();
+            }
+            let metrics = wait_for_prune(&mpool, |m| m.skipped_prunes > 0).await;
+            assert_eq!(metrics.prunes, 1);
+            assert_eq!(mpool.pending_for(&sender).await.unwrap().len(), 15);
+        })
+    }
+
+    #[test]
+    fn test_replace_by_fee() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let mut mset = MsgSet::new(0);
+
+        mset.add(
+            create_fee_smsg(&sender, &mut wallet, 0, 200, 100),
+            1.25,
+            true,
+        )
+        .unwrap();
+        let dup = mset.msgs[&0].clone();
+        assert_eq!(mset.add(dup, 1.25, true), Err(Error::DuplicateSequence));
+
+        // Both the premium and the fee cap have to be bumped by the ratio
+        let low_premium = create_fee_smsg(&sender, &mut wallet, 0, 300, 125);
+        assert_eq!(
+            mset.add(low_premium, 1.25, true),
+            Err(Error::RbfPremiumTooLow)
+        );
+        let low_fee_cap = create_fee_smsg(&sender, &mut wallet, 0, 250, 126);
+        assert_eq!(
+            mset.add(low_fee_cap, 1.25, true),
+            Err(Error::RbfFeeCapTooLow)
+        );
+        let replacement = create_fee_smsg(&sender, &mut wallet, 0, 251, 126);
+        mset.add(replacement.clone(), 1.25, true).unwrap();
+        assert_eq!(mset.msgs[&0], replacement);
+        assert_eq!(mset.required_funds, replacement.required_funds());
+
+        // A higher ratio requires a bigger bump
+        let replacement = create_fee_smsg(&sender, &mut wallet, 0, 400, 200);
+        assert_eq!(
+            mset.add(replacement, 2.0, true),
+            Err(Error::RbfPremiumTooLow)
+        );
+    }
+
+    #[test]
+    fn test_sequence_gap_and_pending_limit() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let mut mset = MsgSet::new(0);
+
+        let far = create_fee_smsg(&sender, &mut wallet, MAX_NONCE_GAP + 1, 200, 100);
+        assert_eq!(mset.add(far.clone(), 1.25, true), Err(Error::SequenceGap));
+        // Messages from the chain are added regardless of the gap
+        mset.add(far, 1.25, false).unwrap();
+        assert_eq!(mset.next_sequence, 0);
+
+        let gapped = create_fee_smsg(&sender, &mut wallet, 2, 200, 100);
+        mset.add(gapped, 1.25, true).unwrap();
+        let replacement = create_fee_smsg(&sender, &mut wallet, 2, 400, 200);
+        assert_eq!(mset.add(replacement, 1.25, true), Err(Error::SequenceGap));
+
+        // Filling the gap advances the next sequence past the pending messages
+        mset.add(
+            create_fee_smsg(&sender, &mut wallet, 0, 200, 100),
+            1.25,
+            true,
+        )
+        .unwrap();
+        mset.add(
+            create_fee_smsg(&sender, &mut wallet, 1, 200, 100),
+            1.25,
+            true,
+        )
+        .unwrap();
+        assert_eq!(mset.next_sequence, 3);
+
+        let mut mset = MsgSet::new(0);
+        for i in 0..MAX_ACTOR_PENDING_MESSAGES as u64 {
+            let smsg = create_fee_smsg(&sender, &mut wallet, i, 200, 100);
+            mset.add(smsg, 1.25, true).unwrap();
+        }
+        let over = create_fee_smsg(
+            &sender,
+            &mut wallet,
+            MAX_ACTOR_PENDING_MESSAGES as u64,
+            200,
+            100,
+        );
+        assert_eq!(
+            mset.add(over, 1.25, true),
+            Err(Error::TooManyPendingMessages)
+        );
+    }
+
+    #[test]
+    fn test_local_message_journal() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = Address::new_id(1001);
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 1);
+        tma.set_state_balance(&sender, BigInt::from(10u64.pow(18)));
+        let stale = create_smsg(&target, &sender, wallet.borrow_mut(), 0);
+        let stale_cid = stale.message().cid().unwrap();
+        let journaled = create_smsg(&target, &sender, wallet.borrow_mut(), 1);
+        let journaled_cid = journaled.message().cid().unwrap();
+        tma.put_local_entries(&[
+            (stale_cid, LocalMsgEntry::new(stale, 0)),
+            (journaled_cid, LocalMsgEntry::new(journaled.clone(), 0)),
+        ])
+        .unwrap();
+
+        let (tx, _rx) = channel(50);
+        task::block_on(async move {
+            // Journaled messages are added back on startup, unless their sequence is too low
+            let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                .await
+                .unwrap();
+            assert_eq!(mpool.pending_for(&sender).await, Some(vec![journaled]));
+            let cids = |entries: Vec<(Cid, LocalMsgEntry)>| {
+                entries.into_iter().map(|(cid, _)| cid).collect::<Vec<_>>()
+            };
+            let entries = mpool.api.read().await.load_local_entries().unwrap();
+            assert_eq!(cids(entries), vec![journaled_cid]);
+
+            let pushed = create_fee_smsg(&sender, &mut wallet, 2, 100, 0);
+            let pushed_cid = pushed.message().cid().unwrap();
+            mpool.push(pushed.clone()).await.unwrap();
+            let entries = mpool.api.read().await.load_local_entries().unwrap();
+            assert_eq!(cids(entries), vec![journaled_cid, pushed_cid]);
+
+            // A replacement by fee takes the place of the replaced message in the journal
+            let pushed = create_fee_smsg(&sender, &mut wallet, 2, 200, 100);
+            let pushed_cid = pushed.message().cid().unwrap();
+            mpool.push(pushed.clone()).await.unwrap();
+            let entries = mpool.api.read().await.load_local_entries().unwrap();
+            assert_eq!(cids(entries), vec![journaled_cid, pushed_cid]);
+
+            // Entries are removed once their inclusion is final
+            let a = mock_block(1, 1);
+            mpool.api.write().await.set_block_messages(&a, vec![pushed]);
+            let included = Tipset::new(vec![a]).unwrap();
+            let head = BlockHeader::builder()
+                .miner_address(Address::new_id(0))
+                .epoch(included.epoch() + CHAIN_FINALITY)
+                .build_and_validate()
+                .unwrap();
+            let head = Tipset::new(vec![head]).unwrap();
+
+            update_local_msgs(
+                mpool.api.as_ref(),
+                mpool.local_msgs.as_ref(),
+                &[],
+                &[included],
+            )
+            .await
+            .unwrap();
+            let entries = mpool.api.read().await.load_local_entries().unwrap();
+            assert_eq!(entries[1].1.included_epoch, Some(0));
+
+            update_local_msgs(mpool.api.as_ref(), mpool.local_msgs.as_ref(), &[], &[head])
+                .await
+                .unwrap();
+            let entries = mpool.api.read().await.load_local_entries().unwrap();
+            assert_eq!(cids(entries), vec![journaled_cid]);
+
+            mpool.clear(true).await.unwrap();
+            assert!(mpool
+                .api
+                .read()
+                .await
+                .load_local_entries()
+                .unwrap()
+                .is_empty());
+            assert_eq!(mpool.pending_for(&sender).await, None);
+        })
+    }
+
+    #[test]
+    fn test_estimate_gas_premium() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        // Two messages which use a whole block gas target each when their limit is counted
+        let msgs: Vec<SignedMessage> = [3_000_000u64, 1_000_000]
+            .iter()
+            .enumerate()
+            .map(|(i, premium)| {
+                let umsg = UnsignedMessage::builder()
+                    .to(Address::new_id(1001))
+                    .from(sender)
+                    .sequence(i as u64)
+                    .gas_limit(BLOCK_GAS_LIMIT / 2)
+                    .gas_fee_cap((premium * 2).into())
+                    .gas_premium((*premium).into())
+                    .build()
+                    .unwrap();
+                let sig = wallet.sign(&sender, &umsg.to_signing_bytes()).unwrap();
+                SignedMessage::new_from_parts(umsg, sig).unwrap()
+            })
+            .collect();
+        let receipt = |gas_used| MessageReceipt {
+            exit_code: ExitCode::Ok,
+            return_data: Serialized::default(),
+            gas_used,
+        };
+
+        let estimate = |receipts: Vec<MessageReceipt>| {
+            let a = mock_block(1, 1);
+            let b = mock_block_with_parents(Tipset::new(vec![a.clone()]).unwrap(), 1, 2);
+            let c = mock_block_with_parents(Tipset::new(vec![b.clone()]).unwrap(), 1, 3);
+            let mut tma = TestApi::default();
+            tma.set_block_messages(&a, Vec::new());
+            tma.set_block_messages(&b, msgs.clone());
+            tma.set_parent_receipts(&c, receipts);
+
+            let (tx, _rx) = channel(50);
+            task::block_on(async move {
+                let mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                    .await
+                    .unwrap();
+                *mpool.cur_tipset.write().await = Arc::new(Tipset::new(vec![c]).unwrap());
+                mpool
+                    .estimate_gas_premium(1, sender, 0, TipsetKeys::default())
+                    .await
+                    .unwrap()
+            })
+        };
+        // The estimate is scaled by a small random noise
+        let within = |premium: BigInt, expected: u64| {
+            assert!(premium > BigInt::from(expected * 95 / 100), "{}", premium);
+            assert!(premium < BigInt::from(expected * 105 / 100), "{}", premium);
+        };
+
+        // Receipts which match the messages show the blocks were mostly empty, the lowest
+        // premiums paid are used.
+        within(estimate(vec![receipt(1000), receipt(1000)]), 2_000_000);
+        // The highest premium message used up the whole block.
+        within(
+            estimate(vec![receipt(BLOCK_GAS_LIMIT), receipt(1000)]),
+            3_000_000,
+        );
+
+        // Without matching receipts the gas limits are used, the median falls between the two.
+        within(estimate(vec![receipt(1000)]), 2_000_000);
+        within(estimate(Vec::new()), 2_000_000);
+    }
+
+    #[test]
+    fn test_gc_retains_pending_messages() {
+        let db = Arc::new(MemoryDB::default());
+        let put = |obj: &str| db.put(&obj, Blake2b256).unwrap();
+        let genesis = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .state_root(put("state"))
+            .messages(put("messages"))
+            .message_receipts(put("receipts"))
+            .build_and_validate()
+            .unwrap();
+        db.put(&genesis, Blake2b256).unwrap();
+        let garbage = put("garbage");
+        let cs = Arc::new(ChainStore::new(db.clone()));
+        let sm = Arc::new(StateManager::new(cs.clone()));
+
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = channel(50);
+
+        task::block_on(async move {
+            cs.set_heaviest_tipset(Arc::new(Tipset::new(vec![genesis]).unwrap()))
+                .await
+                .unwrap();
+            let mpool = Arc::new(
+                MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                    .await
+                    .unwrap(),
+            );
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), 0);
+            mpool.add(msg.clone()).await.unwrap();
+            // Stored the way the node stores messages added to its pool.
+            let provider =
+                MpoolRpcProvider::new(cs.publisher().write().await.subscribe(), sm.clone());
+            let stored = provider.put_message(&msg).unwrap();
+
+            let gc = ChainGc::<_, MockVerifier>::new(sm, 1).with_retained_cids(move || {
+                let mpool = mpool.clone();
+                async move { mpool.message_cids().await.map_err(|e| e.to_string()) }
+            });
+            assert_eq!(gc.collect().await.unwrap(), Some(1));
+            assert!(db.exists(stored.to_bytes()).unwrap());
+            assert!(!db.exists(garbage.to_bytes()).unwrap());
+        });
+    }
 }
@@ -0,0 +1,482 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::block_prob::block_probabilities;
+use super::errors::Error;
+use super::msg_chain::{create_message_chains, Chains, MsgChain};
+use super::msgpool::{MessagePool, Provider};
+use address::Address;
+use blocks::Tipset;
+use encoding::Cbor;
+use interpreter::PriceList;
+use log::warn;
+use message::{Message, SignedMessage};
+use num_bigint::BigInt;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use types::BLOCK_GAS_LIMIT;
+
+/// Gas limit of the smallest possible message, no more messages can be packed into a block once
+/// the remaining gas is below this.
+pub(crate) const MIN_GAS: i64 = 1_298_450;
+
+/// Ticket quality above which the block is very likely to be the only one in the tipset, in which
+/// case messages are selected greedily.
+const GREEDY_TICKET_QUALITY: f64 = 0.84;
+
+impl<T> MessagePool<T>
+where
+    T: Provider + std::marker::Send + std::marker::Sync + 'static,
+{
+    /// Selects the messages to include in a block mined on top of the given tipset. The ticket
+    /// quality of the block determines the probability of other blocks in the same tipset
+    /// including the same messages, which is accounted for when selecting.
+    pub async fn select_messages(&self, ts: &Tipset, tq: f64) -> Result<Vec<SignedMessage>, Error> {
+        let api = self.api.read().await;
+        let base_fee = api.chain_compute_base_fee(ts)?;
+        let mut pending = self.pending_by_sender().await;
+
+        let price_list = self.price_schedule.price_list(ts.epoch());
+
+        let included = included_sequences(&*api, ts)?;
+        let priority_addrs = self.config.read().await.priority_addrs().to_vec();
+        let mut chains = Chains::default();
+        for addr in priority_addrs.iter() {
+            if let Some(msgs) = pending.remove(addr) {
+                chains.extend(create_chains(
+                    &*api, addr, msgs, &base_fee, ts, &included, price_list,
+                )?);
+            }
+        }
+        let mut result = Vec::new();
+        let gas_limit = select_from_chains(
+            chains,
+            BLOCK_GAS_LIMIT,
+            &base_fee,
+            &mut result,
+            MsgChain::compare,
+        );
+        if gas_limit < MIN_GAS {
+            return Ok(result);
+        }
+
+        let mut chains = Chains::default();
+        for (addr, msgs) in pending {
+            chains.extend(create_chains(
+                &*api, &addr, msgs, &base_fee, ts, &included, price_list,
+            )?);
+        }
+        if tq > GREEDY_TICKET_QUALITY {
+            select_from_chains(chains, gas_limit, &base_fee, &mut result, MsgChain::compare);
+            return Ok(result);
+        }
+
+        // Partition the chains into the blocks they would be included in, and weigh each chain's
+        // performance with the probability of that block being mined.
+        let mut order = sorted_chains(&chains, MsgChain::compare);
+        let mut next_chain = 0;
+        for bp in block_probabilities(tq) {
+            let mut partition_gas = BLOCK_GAS_LIMIT;
+            while next_chain < order.len() {
+                let c = order[next_chain];
+                next_chain += 1;
+                chains.set_effective_perf(c, bp);
+                partition_gas -= chains.get(c).gas_limit;
+                if partition_gas < MIN_GAS {
+                    break;
+                }
+            }
+        }
+        for &c in &order[next_chain..] {
+            chains.set_null_effective_perf(c);
+        }
+        order.sort_by(|a, b| chains.get(*a).compare_effective(chains.get(*b)));
+
+        merge_chains(
+            &mut chains,
+            order,
+            gas_limit,
+            &base_fee,
+            &mut result,
+            MsgChain::compare_effective,
+        );
+        Ok(result)
+    }
+
+    /// Returns a snapshot of the pending messages of each sender, sorted by sequence.
+    pub(crate) async fn pending_by_sender(&self) -> HashMap<Address, Vec<SignedMessage>> {
+        self.pending
+            .read()
+            .await
+            .iter()
+            .map(|(addr, mset)| {
+                let mut msgs: Vec<SignedMessage> = mset.msgs.values().cloned().collect();
+                msgs.sort_by_key(|m| m.sequence());
+                (*addr, msgs)
+            })
+            .collect()
+    }
+}
+
+/// Returns the sequence following the last message of each sender included in the tipset. These
+/// messages have been applied by the time a block on top of the tipset is executed.
+pub(crate) fn included_sequences<T: Provider>(
+    api: &T,
+    ts: &Tipset,
+) -> Result<HashMap<Address, u64>, Error> {
+    let mut sequences = HashMap::new();
+    for m in api.messages_for_tipset(ts)? {
+        let next = sequences.entry(*m.from()).or_insert(0);
+        *next = u64::max(*next, m.sequence() + 1);
+    }
+    Ok(sequences)
+}
+
+/// Creates the message chains of a sender based on its actor state after the tipset, and the
+/// sequences of the messages included in the tipset from `included_sequences`.
+pub(crate) fn create_chains<T: Provider>(
+    api: &T,
+    addr: &Address,
+    msgs: Vec<SignedMessage>,
+    base_fee: &BigInt,
+    ts: &Tipset,
+    included: &HashMap<Address, u64>,
+    price_list: &PriceList,
+) -> Result<Vec<MsgChain>, Error> {
+    let actor = match api.get_actor_after(addr, ts) {
+        Ok(actor) => actor,
+        Err(e) => {
+            warn!("failed to load actor state for {}: {}", addr, e);
+            return Ok(Vec::new());
+        }
+    };
+    let sequence = included
+        .get(addr)
+        .map_or(actor.sequence, |s| u64::max(*s, actor.sequence));
+
+    let min_gas = |m: &SignedMessage| {
+        m.marshal_cbor()
+            .ok()
+            .map(|bz| price_list.on_chain_message(bz.len()).total())
+    };
+    Ok(create_message_chains(
+        msgs,
+        sequence,
+        actor.balance,
+        base_fee,
+        min_gas,
+    ))
+}
+
+/// Returns the indices of the chains, ordered with the given comparison.
+pub(crate) fn sorted_chains(
+    chains: &Chains,
+    cmp: fn(&MsgChain, &MsgChain) -> Ordering,
+) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..chains.len()).collect();
+    order.sort_by(|a, b| cmp(chains.get(*a), chains.get(*b)));
+    order
+}
+
+/// Sorts the chains and merges them into the result. Returns the remaining gas limit.
+fn select_from_chains(
+    mut chains: Chains,
+    gas_limit: i64,
+    base_fee: &BigInt,
+    result: &mut Vec<SignedMessage>,
+    cmp: fn(&MsgChain, &MsgChain) -> Ordering,
+) -> i64 {
+    let order = sorted_chains(&chains, cmp);
+    merge_chains(&mut chains, order, gas_limit, base_fee, result, cmp)
+}
+
+/// Merges the ordered chains into the result while they fit in the gas limit. A chain is merged
+/// together with the chains of the same sender it depends on which have not been merged yet, so
+/// that there are no gaps in the sequences of a sender. When a chain does not fit, it is trimmed
+/// and pushed down in the order, and the remaining gas is filled with the following chains.
+/// Returns the remaining gas limit.
+fn merge_chains(
+    chains: &mut Chains,
+    mut order: Vec<usize>,
+    mut gas_limit: i64,
+    base_fee: &BigInt,
+    result: &mut Vec<SignedMessage>,
+    cmp: fn(&MsgChain, &MsgChain) -> Ordering,
+) -> i64 {
+    let mut last = order.len();
+    for (i, &c) in order.iter().enumerate() {
+        let chain = chains.get(c);
+        if chain.merged {
+            continue;
+        }
+        // Chains with negative performance would be a loss for the miner
+        if chain.gas_perf < 0.0 {
+            break;
+        }
+        let (deps, deps_gas_limit) = unmerged_dependencies(chains, c);
+        let chain_gas_limit = chain.gas_limit + deps_gas_limit;
+        if chain_gas_limit <= gas_limit {
+            gas_limit -= chain_gas_limit;
+            merge_chain(chains, &deps, c, result);
+            continue;
+        }
+        last = i;
+        break;
+    }
+
+    'tail: while gas_limit >= MIN_GAS && last < order.len() {
+        chains.trim(order[last], gas_limit, base_fee);
+
+        // Push the trimmed chain down to keep the order
+        if chains.get(order[last]).valid {
+            for i in last..order.len() - 1 {
+                if cmp(chains.get(order[i]), chains.get(order[i + 1])) == Ordering::Less {
+                    break;
+                }
+                order.swap(i, i + 1);
+            }
+        }
+
+        for i in last..order.len() {
+            let c = order[i];
+            let chain = chains.get(c);
+            if !chain.valid || chain.merged {
+                continue;
+            }
+            if chain.gas_perf < 0.0 {
+                break 'tail;
+            }
+            if chain.gas_limit <= gas_limit {
+                let (deps, deps_gas_limit) = unmerged_dependencies(chains, c);
+                let chain_gas_limit = chain.gas_limit + deps_gas_limit;
+                if chain_gas_limit > gas_limit {
+                    // The chain can not be included without the chains it depends on
+                    chains.invalidate(c);
+                    continue;
+                }
+                gas_limit -= chain_gas_limit;
+                merge_chain(chains, &deps, c, result);
+                continue;
+            }
+            last = i;
+            continue 'tail;
+        }
+        break;
+    }
+    gas_limit
+}
+
+/// Returns the chains of the same sender the chain depends on which have not been merged yet,
+/// earliest first, along with their total gas limit.
+fn unmerged_dependencies(chains: &Chains, c: usize) -> (Vec<usize>, i64) {
+    let mut deps = Vec::new();
+    let mut gas_limit = 0;
+    let mut prev = chains.get(c).prev;
+    while let Some(p) = prev {
+        let chain = chains.get(p);
+        if chain.merged {
+            break;
+        }
+        deps.push(p);
+        gas_limit += chain.gas_limit;
+        prev = chain.prev;
+    }
+    deps.reverse();
+    (deps, gas_limit)
+}
+
+/// Merges the chain into the result, after the chains it depends on.
+fn merge_chain(chains: &mut Chains, deps: &[usize], c: usize, result: &mut Vec<SignedMessage>) {
+    for &i in deps.iter().chain(std::iter::once(&c)) {
+        chains.set_merged(i);
+        result.extend(chains.get(i).msgs.iter().cloned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::msgpool::test_provider::TestApi;
+    use super::*;
+    use crate::MpoolConfig;
+    use async_std::sync::channel;
+    use async_std::task;
+    use crypto::SignatureType;
+    use key_management::{MemKeyStore, Wallet};
+    use message::UnsignedMessage;
+    use std::time::Duration;
+
+    const TEST_GAS_LIMIT: i64 = 100_000_000;
+
+    fn create_smsg(
+        to: &Address,
+        from: &Address,
+        wallet: &mut Wallet<MemKeyStore>,
+        sequence: u64,
+        gas_premium: i64,
+    ) -> SignedMessage {
+        let umsg = UnsignedMessage::builder()
+            .to(*to)
+            .from(*from)
+            .sequence(sequence)
+            .gas_limit(TEST_GAS_LIMIT)
+            .gas_fee_cap(200.into())
+            .gas_premium(gas_premium.into())
+            .build()
+            .unwrap();
+        let sig = wallet.sign(from, &umsg.to_signing_bytes()).unwrap();
+        SignedMessage::new_from_parts(umsg, sig).unwrap()
+    }
+
+    fn senders(wallet: &mut Wallet<MemKeyStore>, tma: &mut TestApi, n: usize) -> Vec<Address> {
+        (0..n)
+            .map(|_| {
+                let addr = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+                tma.set_state_sequence(&addr, 0);
+                tma.set_state_balance(&addr, BigInt::from(10u64.pow(18)));
+                addr
+            })
+            .collect()
+    }
+
+    async fn add_msgs(
+        mpool: &mut MessagePool<TestApi>,
+        wallet: &mut Wallet<MemKeyStore>,
+        from: &Address,
+        count: u64,
+        gas_premium: i64,
+    ) {
+        let to = Address::new_id(1001);
+        for i in 0..count {
+            let msg = create_smsg(&to, from, wallet, i, gas_premium);
+            mpool.add_skip_checks(msg).await.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_select_by_gas_performance() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let mut tma = TestApi::default();
+        let addrs = senders(&mut wallet, &mut tma, 2);
+
+        let (tx, _rx) = channel(50);
+
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                .await
+                .unwrap();
+            add_msgs(&mut mpool, &mut wallet, &addrs[0], 10, 10).await;
+            add_msgs(&mut mpool, &mut wallet, &addrs[1], 10, 20).await;
+
+            let ts = mpool.cur_tipset.read().await.clone();
+            let msgs = mpool.select_messages(&ts, 0.25).await.unwrap();
+            assert_eq!(msgs.len(), 20);
+            for (i, m) in msgs.iter().enumerate() {
+                let (from, sequence) = if i < 10 {
+                    (&addrs[1], i)
+                } else {
+                    (&addrs[0], i - 10)
+                };
+                assert_eq!(m.from(), from);
+                assert_eq!(m.sequence(), sequence as u64);
+            }
+        })
+    }
+
+    #[test]
+    fn test_select_priority_addrs() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let mut tma = TestApi::default();
+        let addrs = senders(&mut wallet, &mut tma, 2);
+        let config = MpoolConfig::new(
+            vec![addrs[0]],
+            30000,
+            20000,
+            1.25,
+            Duration::from_secs(60),
+            1.25,
+        )
+        .unwrap();
+
+        let (tx, _rx) = channel(50);
+
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(tma, "mptest".to_string(), tx, config)
+                .await
+                .unwrap();
+            add_msgs(&mut mpool, &mut wallet, &addrs[0], 5, 10).await;
+            add_msgs(&mut mpool, &mut wallet, &addrs[1], 5, 20).await;
+
+            let ts = mpool.cur_tipset.read().await.clone();
+            let msgs = mpool.select_messages(&ts, 0.9).await.unwrap();
+            assert_eq!(msgs.len(), 10);
+            assert!(msgs[..5].iter().all(|m| m.from() == &addrs[0]));
+            assert!(msgs[5..].iter().all(|m| m.from() == &addrs[1]));
+        })
+    }
+
+    #[test]
+    fn test_select_trims_to_block_gas_limit() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let mut tma = TestApi::default();
+        let addrs = senders(&mut wallet, &mut tma, 2);
+
+        let (tx, _rx) = channel(50);
+
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(tma, "mptest".to_string(), tx, Default::default())
+                .await
+                .unwrap();
+            add_msgs(&mut mpool, &mut wallet, &addrs[0], 60, 10).await;
+            add_msgs(&mut mpool, &mut wallet, &addrs[1], 60, 20).await;
+
+            let ts = mpool.cur_tipset.read().await.clone();
+            let msgs = mpool.select_messages(&ts, 0.25).await.unwrap();
+            let max_msgs = (BLOCK_GAS_LIMIT / TEST_GAS_LIMIT) as usize;
+            assert_eq!(msgs.len(), max_msgs);
+            assert_eq!(msgs.iter().filter(|m| m.from() == &addrs[1]).count(), 60);
+            let trimmed: Vec<u64> = msgs
+                .iter()
+                .filter(|m| m.from() == &addrs[0])
+                .map(|m| m.sequence())
+                .collect();
+            assert_eq!(trimmed, (0..(max_msgs as u64 - 60)).collect::<Vec<_>>());
+        })
+    }
+
+    #[test]
+    fn test_merge_includes_unmerged_dependencies() {
+        let mut wallet = Wallet::new(MemKeyStore::new());
+        let from = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let to = Address::new_id(1001);
+        let msgs = vec![
+            create_smsg(&to, &from, &mut wallet, 0, 20),
+            create_smsg(&to, &from, &mut wallet, 1, 10),
+        ];
+        let base_fee = BigInt::from(100);
+        let mut chains = Chains::default();
+        chains.extend(create_message_chains(
+            msgs,
+            0,
+            BigInt::from(10u64.pow(18)),
+            &base_fee,
+            |_| Some(0),
+        ));
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains.get(1).prev, Some(0));
+
+        // Both chains have the same effective performance, so the child chain can be ordered
+        // before its parent
+        let mut result = Vec::new();
+        let gas_limit = merge_chains(
+            &mut chains,
+            vec![1, 0],
+            BLOCK_GAS_LIMIT,
+            &base_fee,
+            &mut result,
+            MsgChain::compare_effective,
+        );
+        assert_eq!(gas_limit, BLOCK_GAS_LIMIT - 2 * TEST_GAS_LIMIT);
+        let sequences: Vec<u64> = result.iter().map(|m| m.sequence()).collect();
+        assert_eq!(sequences, vec![0, 1]);
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use address::Error as AddrErr;
+use blocks::Error as BlkErr;
+use chain::Error as StoreErr;
+use chain_sync::Error as SyncErr;
+use crypto::Error as CryptoErr;
+use encoding::Error as EncErr;
+use message_pool::Error as MpoolErr;
+use state_manager::Error as StErr;
+use thiserror::Error;
+use wallet::Error as WalletErr;
+
+/// Block production error
+#[derive(Debug, Error)]
+pub enum Error {
+    /// Error originating constructing blockchain structures
+    #[error(transparent)]
+    Blockchain(#[from] BlkErr),
+    /// Error indicating a chain store error
+    #[error(transparent)]
+    Store(#[from] StoreErr),
+    /// Error originating from state
+    #[error(transparent)]
+    State(#[from] StErr),
+    /// Error originating from the message pool
+    #[error(transparent)]
+    MessagePool(#[from] MpoolErr),
+    /// Error originating from signing with the worker key
+    #[error(transparent)]
+    Wallet(#[from] WalletErr),
+    /// Error originating from encoding arbitrary data
+    #[error("{0}")]
+    Encoding(String),
+    /// Error originating from generating the winning PoSt proof
+    #[error("Failed to compute winning PoSt: {0}")]
+    Prover(String),
+    /// Any other error that does not need to be specifically handled
+    #[error("{0}")]
+    Other(String),
+}
+
+impl From<EncErr> for Error {
+    fn from(e: EncErr) -> Error {
+        Error::Encoding(e.to_string())
+    }
+}
+
+impl From<AddrErr> for Error {
+    fn from(e: AddrErr) -> Error {
+        Error::Other(e.to_string())
+    }
+}
+
+impl From<CryptoErr> for Error {
+    fn from(e: CryptoErr) -> Error {
+        Error::Other(e.to_string())
+    }
+}
+
+impl From<SyncErr> for Error {
+    fn from(e: SyncErr) -> Error {
+        Error::Other(e.to_string())
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Error {
+        Error::Other(e)
+    }
+}
@@ -0,0 +1,10 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+mod errors;
+mod miner;
+mod prover;
+
+pub use self::errors::*;
+pub use self::miner::*;
+pub use self::prover::*;
@@ -0,0 +1,585 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{Error, WinningPoStProver};
+use address::Address;
+use async_std::sync::{RwLock, Sender};
+use async_std::task;
+use beacon::{beacon_entries_for_block, Beacon, BeaconEntry};
+use blocks::{BlockHeader, ElectionProof, GossipBlock, Ticket, Tipset};
+use chain::persist_objects;
+use chain_sync::compute_msg_meta;
+use cid::Cid;
+use clock::ChainEpoch;
+use crypto::{aggregate_bls_signatures, DomainSeparationTag, Signature, VRFProof};
+use encoding::Cbor;
+use fil_types::{
+    verifier::ProofVerifier, Randomness, TICKET_RANDOMNESS_LOOKBACK, UPGRADE_SMOKE_HEIGHT,
+};
+use forest_libp2p::{NetworkMessage, Topic, PUBSUB_BLOCK_STR};
+use ipld_blockstore::BlockStore;
+use log::{debug, info, warn};
+use message::SignedMessage;
+use message_pool::{MessagePool, Provider};
+use state_manager::StateManager;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wallet::KeyStore;
+
+/// Time waited after the start of a round for blocks to propagate before picking a new base.
+const PROPAGATION_DELAY_SECS: u64 = 6;
+
+/// Produces blocks for a storage miner actor.
+///
+/// Each round the miner checks whether it was elected on top of the heaviest tipset, and if so
+/// computes the winning PoSt with its prover, fills a block with messages from the message pool,
+/// signs it with the worker key and publishes it over gossipsub.
+pub struct Miner<DB, B, T, P, KS> {
+    /// Address of the miner actor blocks are produced for.
+    address: Address,
+    state_manager: Arc<StateManager<DB>>,
+    beacon: Arc<B>,
+    mpool: Arc<MessagePool<T>>,
+    prover: P,
+    /// Keystore containing the miner's worker key.
+    keystore: Arc<RwLock<KS>>,
+    network_send: Sender<NetworkMessage>,
+    network_name: String,
+}
+
+impl<DB, B, T, P, KS> Miner<DB, B, T, P, KS>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+    T: Provider + Send + Sync + 'static,
+    P: WinningPoStProver + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    /// Creates a miner for the given miner actor. The keystore must contain the key of the
+    /// actor's worker address, which is used to sign tickets and blocks.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: Address,
+        state_manager: Arc<StateManager<DB>>,
+        beacon: Arc<B>,
+        mpool: Arc<MessagePool<T>>,
+        prover: P,
+        keystore: Arc<RwLock<KS>>,
+        network_send: Sender<NetworkMessage>,
+        network_name: String,
+    ) -> Self {
+        Self {
+            address,
+            state_manager,
+            beacon,
+            mpool,
+            prover,
+            keystore,
+            network_send,
+            network_name,
+        }
+    }
+
+    /// Returns the address of the miner actor.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Runs the mining loop, attempting to produce a block every round. This never returns, so
+    /// it should be spawned as a separate task.
+    pub async fn run<V>(self)
+    where
+        V: ProofVerifier,
+    {
+        info!("Starting block production for miner {}", self.address);
+        let block_delay = self.state_manager.network_chain().block_delay_secs();
+        loop {
+            let base = match self.state_manager.chain_store().heaviest_tipset().await {
+                Some(ts) => ts,
+                None => {
+                    task::sleep(Duration::from_secs(block_delay)).await;
+                    continue;
+                }
+            };
+
+            // Rounds since the base without a block are null rounds.
+            let now = now_secs();
+            let null_rounds = if now > base.min_timestamp() {
+                (now - base.min_timestamp() - 1) / block_delay
+            } else {
+                0
+            };
+            let round_time = base.min_timestamp() + block_delay * (null_rounds + 1);
+
+            match self.mine_one::<V>(&base, null_rounds).await {
+                Ok(Some(block)) => {
+                    // Blocks with a timestamp in the future are rejected by other nodes.
+                    sleep_until(round_time).await;
+                    if let Err(e) = self.submit_block(block).await {
+                        warn!("Failed to submit mined block: {}", e);
+                    }
+                }
+                Ok(None) => debug!(
+                    "Miner {} not elected in round {}",
+                    self.address,
+                    base.epoch() + null_rounds as ChainEpoch + 1
+                ),
+                Err(e) => warn!("Failed to mine block: {}", e),
+            }
+
+            sleep_until(round_time + PROPAGATION_DELAY_SECS).await;
+        }
+    }
+
+    /// Attempts to produce a block on top of the base tipset after the given number of null
+    /// rounds. Returns `None` if the miner was not elected to produce a block for the round.
+    pub async fn mine_one<V>(
+        &self,
+        base: &Arc<Tipset>,
+        null_rounds: u64,
+    ) -> Result<Option<GossipBlock>, Error>
+    where
+        V: ProofVerifier,
+    {
+        let sm = &self.state_manager;
+        let cs = sm.chain_store();
+        let round = base.epoch() + null_rounds as ChainEpoch + 1;
+
+        let prev_beacon = cs.latest_beacon_entry(base)?;
+        let beacon_entries = beacon_entries_for_block(self.beacon.as_ref(), round, &prev_beacon)
+            .await
+            .map_err(|e| Error::Other(format!("Failed to get beacon entries: {}", e)))?;
+        let rbase = beacon_entries.last().unwrap_or(&prev_beacon);
+
+        let lbts = cs
+            .get_lookback_tipset_for_round(base, round)?
+            .map(Arc::new)
+            .unwrap_or_else(|| Arc::clone(base));
+        let (lbst, _) = sm
+            .tipset_state::<V>(&lbts)
+            .await
+            .map_err(|e| Error::Other(format!("Could not compute lookback state: {}", e)))?;
+
+        if !sm.miner_has_min_power(&self.address, &lbts)? {
+            debug!("Miner {} does not meet minimum power", self.address);
+            return Ok(None);
+        }
+        if sm.is_miner_slashed(&self.address, base.parent_state())? {
+            return Err(Error::Other(format!("Miner {} is slashed", self.address)));
+        }
+
+        let worker = sm.get_miner_work_addr(&lbst, &self.address)?;
+        let miner_bz = self.address.marshal_cbor()?;
+
+        let ticket = self
+            .compute_ticket(base, round, rbase, &worker, &miner_bz)
+            .await?;
+
+        // Check if the miner was elected
+        let vrf_base = chain::draw_randomness(
+            rbase.data(),
+            DomainSeparationTag::ElectionProofProduction,
+            round,
+            &miner_bz,
+        )
+        .map_err(|e| Error::Other(format!("Failed to draw randomness: {}", e)))?;
+        let vrfproof = VRFProof::new(self.sign(&worker, &vrf_base).await?.bytes().to_vec());
+        let (mpow, tpow) = sm.get_power(&lbst, &self.address)?;
+        let mut election_proof = ElectionProof {
+            win_count: 0,
+            vrfproof,
+        };
+        election_proof.win_count =
+            election_proof.compute_win_count(&mpow.quality_adj_power, &tpow.quality_adj_power);
+        if election_proof.win_count < 1 {
+            return Ok(None);
+        }
+
+        // Prove storage of the challenged sectors
+        let post_rand = chain::draw_randomness(
+            rbase.data(),
+            DomainSeparationTag::WinningPoStChallengeSeed,
+            round,
+            &miner_bz,
+        )
+        .map_err(|e| Error::Other(format!("Failed to draw randomness: {}", e)))?;
+        let sectors = sm
+            .get_sectors_for_winning_post::<V>(&lbst, &self.address, Randomness(post_rand))
+            .map_err(|e| Error::Other(format!("Failed to get sectors for PoSt: {}", e)))?;
+        if sectors.is_empty() {
+            debug!("Miner {} has no sectors to prove", self.address);
+            return Ok(None);
+        }
+        let winning_post_proof = self
+            .prover
+            .compute_proof(&sectors, Randomness(post_rand), self.address.id()?)
+            .map_err(|e| Error::Prover(e.to_string()))?;
+
+        let msgs = self.mpool.select_messages(base, ticket.quality()).await?;
+
+        info!(
+            "Miner {} elected in round {} with {} wins",
+            self.address, round, election_proof.win_count
+        );
+
+        let block = self
+            .create_block::<V>(
+                base,
+                round,
+                null_rounds,
+                &worker,
+                ticket,
+                election_proof,
+                beacon_entries,
+                winning_post_proof,
+                msgs,
+            )
+            .await?;
+        Ok(Some(block))
+    }
+
+    /// Computes the ticket for the round, which is the worker's VRF over the beacon entry and the
+    /// minimum ticket of the base.
+    async fn compute_ticket(
+        &self,
+        base: &Tipset,
+        round: ChainEpoch,
+        rbase: &BeaconEntry,
+        worker: &Address,
+        miner_bz: &[u8],
+    ) -> Result<Ticket, Error> {
+        let mut buf = miner_bz.to_vec();
+        if round > UPGRADE_SMOKE_HEIGHT {
+            let vrf_proof = base
+                .min_ticket()
+                .ok_or_else(|| Error::Other("Base tipset did not have a ticket".to_owned()))?
+                .vrfproof
+                .as_bytes();
+            buf.extend_from_slice(vrf_proof);
+        }
+
+        let vrf_base = chain::draw_randomness(
+            rbase.data(),
+            DomainSeparationTag::TicketProduction,
+            round - TICKET_RANDOMNESS_LOOKBACK,
+            &buf,
+        )
+        .map_err(|e| Error::Other(format!("Failed to draw randomness: {}", e)))?;
+
+        let sig = self.sign(worker, &vrf_base).await?;
+        Ok(Ticket::new(VRFProof::new(sig.bytes().to_vec())))
+    }
+
+    /// Assembles and signs a block on top of the base tipset.
+    #[allow(clippy::too_many_arguments)]
+    async fn create_block<V>(
+        &self,
+        base: &Arc<Tipset>,
+        round: ChainEpoch,
+        null_rounds: u64,
+        worker: &Address,
+        ticket: Ticket,
+        election_proof: ElectionProof,
+        beacon_entries: Vec<BeaconEntry>,
+        winning_post_proof: Vec<fil_types::PoStProof>,
+        msgs: Vec<SignedMessage>,
+    ) -> Result<GossipBlock, Error>
+    where
+        V: ProofVerifier,
+    {
+        let sm = &self.state_manager;
+        let db = sm.blockstore();
+        let block_delay = sm.network_chain().block_delay_secs();
+
+        let (state_root, receipts_root) = sm
+            .tipset_state::<V>(base)
+            .await
+            .map_err(|e| Error::Other(format!("Could not compute base state: {}", e)))?;
+        let weight = chain::weight(db, base)?;
+        let base_fee = chain::compute_base_fee(db, base)?;
+
+        let mut bls_msgs = Vec::new();
+        let mut bls_sigs = Vec::new();
+        let mut secp_msgs = Vec::new();
+        for msg in msgs {
+            if msg.is_bls() {
+                bls_sigs.push(msg.signature().clone());
+                bls_msgs.push(msg.into_message());
+            } else {
+                secp_msgs.push(msg);
+            }
+        }
+
+        // Messages are persisted so they can be served to peers syncing the block.
+        persist_objects(db, &bls_msgs)?;
+        persist_objects(db, &secp_msgs)?;
+        let msg_root = compute_msg_meta(db, &bls_msgs, &secp_msgs)?;
+        let bls_aggregate = aggregate_bls_signatures(&bls_sigs)?;
+
+        let mut builder = BlockHeader::builder();
+        builder
+            .parents(base.key().clone())
+            .weight(weight)
+            .epoch(round)
+            .beacon_entries(beacon_entries)
+            .winning_post_proof(winning_post_proof)
+            .miner_address(self.address)
+            .messages(msg_root)
+            .message_receipts(receipts_root)
+            .state_root(state_root)
+            .election_proof(Some(election_proof))
+            .timestamp(base.min_timestamp() + block_delay * (null_rounds + 1))
+            .ticket(Some(ticket))
+            .bls_aggregate(Some(bls_aggregate))
+            .parent_base_fee(base_fee);
+
+        let unsigned = builder.build_and_validate()?;
+        let signature = self.sign(worker, &unsigned.to_signing_bytes()?).await?;
+        let header = builder.signature(Some(signature)).build_and_validate()?;
+
+        Ok(GossipBlock {
+            header,
+            bls_messages: cids(&bls_msgs)?,
+            secpk_messages: cids(&secp_msgs)?,
+        })
+    }
+
+    /// Stores the block locally, publishes it to the network and sets it as the head if heavier.
+    async fn submit_block(&self, block: GossipBlock) -> Result<(), Error> {
+        let cs = self.state_manager.chain_store();
+        persist_objects(cs.blockstore(), std::slice::from_ref(&block.header))?;
+
+        self.network_send
+            .send(NetworkMessage::PubsubMessage {
+                topic: Topic::new(format!("{}/{}", PUBSUB_BLOCK_STR, self.network_name)),
+                message: block.marshal_cbor()?,
+            })
+            .await;
+        info!(
+            "Published block {} at epoch {}",
+            block.header.cid(),
+            block.header.epoch()
+        );
+
+        // Gossipsub does not deliver the block back to this node.
+        cs.put_tipset(&Tipset::new(vec![block.header])?).await?;
+        Ok(())
+    }
+
+    /// Signs data with the key of the given address from the keystore.
+    async fn sign(&self, addr: &Address, data: &[u8]) -> Result<Signature, Error> {
+        let keystore = self.keystore.read().await;
+        let key = wallet::find_key(addr, &*keystore)?;
+        Ok(wallet::sign(
+            *key.key_info.key_type(),
+            key.key_info.private_key(),
+            data,
+        )?)
+    }
+}
+
+fn cids<C: Cbor>(objects: &[C]) -> Result<Vec<Cid>, Error> {
+    Ok(objects.iter().map(Cbor::cid).collect::<Result<_, _>>()?)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Retrieved system time before UNIX epoch")
+        .as_secs()
+}
+
+async fn sleep_until(timestamp: u64) {
+    let now = now_secs();
+    if timestamp > now {
+        task::sleep(Duration::from_secs(timestamp - now)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockProver;
+    use actor::{
+        make_map, miner, power, MINER_ACTOR_CODE_ID, POWER_ACTOR_CODE_ID, STORAGE_POWER_ACTOR_ADDR,
+    };
+    use async_std::sync::channel;
+    use beacon::MockBeacon;
+    use chain::ChainStore;
+    use cid::Code::Blake2b256;
+    use crypto::{verify_bls_aggregate, SignatureType};
+    use db::MemoryDB;
+    use fil_types::{verifier::MockVerifier, RegisteredSealProof};
+    use message::UnsignedMessage;
+    use message_pool::test_provider::TestApi;
+    use num_bigint::BigInt;
+    use state_tree::StateTree;
+    use vm::ActorState;
+    use wallet::{generate_key, Key, MemKeyStore};
+
+    type TestMiner = Miner<MemoryDB, MockBeacon, TestApi, MockProver, MemKeyStore>;
+
+    /// Creates a miner with a single unit of power out of the given network power, on top of a
+    /// genesis tipset containing its miner and power actor states.
+    fn setup(network_power: BigInt) -> (TestMiner, Arc<Tipset>, Key) {
+        let db = Arc::new(MemoryDB::default());
+        let miner_addr = Address::new_id(1000);
+        let worker = generate_key(SignatureType::BLS).unwrap();
+        let mut keystore = MemKeyStore::new();
+        wallet::import(worker.key_info.clone(), &mut keystore).unwrap();
+
+        let mut claims = make_map(db.as_ref());
+        let claim = power::Claim {
+            raw_byte_power: 1.into(),
+            quality_adj_power: 1.into(),
+        };
+        power::set_claim(&mut claims, &miner_addr, claim).unwrap();
+        let claims_root = claims.flush().unwrap();
+        let mut power_state = power::State::new(claims_root, claims_root);
+        power_state.total_raw_byte_power = network_power.clone();
+        power_state.total_quality_adj_power = network_power;
+
+        let info = miner::MinerInfo::new(
+            worker.address,
+            worker.address,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RegisteredSealProof::StackedDRG2KiBV1,
+        )
+        .unwrap();
+        let info_cid = db.put(&info, Blake2b256).unwrap();
+        let miner_state = miner::State::new(
+            info_cid,
+            0,
+            claims_root,
+            claims_root,
+            claims_root,
+            claims_root,
+            claims_root,
+        );
+
+        let mut tree = StateTree::new(db.as_ref());
+        let power_head = db.put(&power_state, Blake2b256).unwrap();
+        tree.set_actor(
+            &*STORAGE_POWER_ACTOR_ADDR,
+            ActorState::new(*POWER_ACTOR_CODE_ID, power_head, 0.into(), 0),
+        )
+        .unwrap();
+        let miner_head = db.put(&miner_state, Blake2b256).unwrap();
+        tree.set_actor(
+            &miner_addr,
+            ActorState::new(*MINER_ACTOR_CODE_ID, miner_head, 0.into(), 0),
+        )
+        .unwrap();
+        let state_root = tree.flush().unwrap();
+
+        let msg_root = compute_msg_meta(db.as_ref(), &[], &[]).unwrap();
+        let genesis = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .state_root(state_root)
+            .messages(msg_root)
+            .message_receipts(msg_root)
+            .beacon_entries(vec![BeaconEntry::new(0, vec![0; 32])])
+            .ticket(Some(Ticket::default()))
+            .timestamp(1_000_000)
+            .build_and_validate()
+            .unwrap();
+
+        let cs = Arc::new(ChainStore::new(db));
+        cs.set_genesis(&genesis).unwrap();
+        let base = Arc::new(Tipset::new(vec![genesis]).unwrap());
+        task::block_on(cs.set_heaviest_tipset(base.clone())).unwrap();
+
+        let (network_send, _) = channel(1);
+        let mpool = task::block_on(MessagePool::new(
+            TestApi::default(),
+            "test".to_owned(),
+            network_send.clone(),
+            Default::default(),
+        ))
+        .unwrap();
+        let miner = Miner::new(
+            miner_addr,
+            Arc::new(StateManager::new(cs)),
+            Arc::new(MockBeacon::new(Duration::from_secs(1))),
+            Arc::new(mpool),
+            MockProver,
+            Arc::new(RwLock::new(keystore)),
+            network_send,
+            "test".to_owned(),
+        );
+        (miner, base, worker)
+    }
+
+    fn signed_message(typ: SignatureType) -> (SignedMessage, Vec<u8>) {
+        let key = generate_key(typ).unwrap();
+        let msg = UnsignedMessage::builder()
+            .from(key.address)
+            .to(Address::new_id(1001))
+            .build()
+            .unwrap();
+        let sig = wallet::sign(typ, key.key_info.private_key(), &msg.to_signing_bytes()).unwrap();
+        (
+            SignedMessage::new_from_parts(msg, sig).unwrap(),
+            key.public_key,
+        )
+    }
+
+    #[test]
+    fn mine_one_not_elected() {
+        // A single unit of power out of the network's will practically never win.
+        let (miner, base, _) = setup(BigInt::from(1) << 200);
+        let block = task::block_on(miner.mine_one::<MockVerifier>(&base, 0)).unwrap();
+        assert!(block.is_none());
+    }
+
+    #[test]
+    fn create_block_valid_header() {
+        let (miner, base, worker) = setup(BigInt::from(1));
+        let (bls_msg, bls_pub_key) = signed_message(SignatureType::BLS);
+        let (secp_msg, _) = signed_message(SignatureType::Secp256k1);
+
+        let null_rounds = 2;
+        let block = task::block_on(miner.create_block::<MockVerifier>(
+            &base,
+            base.epoch() + null_rounds as ChainEpoch + 1,
+            null_rounds,
+            &worker.address,
+            Ticket::default(),
+            ElectionProof::default(),
+            Vec::new(),
+            Vec::new(),
+            vec![bls_msg.clone(), secp_msg.clone()],
+        ))
+        .unwrap();
+        let header = &block.header;
+
+        let block_delay = miner.state_manager.network_chain().block_delay_secs();
+        assert_eq!(
+            header.timestamp(),
+            base.min_timestamp() + block_delay * (null_rounds + 1)
+        );
+        assert_eq!(header.parents(), base.key());
+        assert_eq!(header.state_root(), base.parent_state());
+        header.check_block_signature(&worker.address).unwrap();
+
+        let bls_cid = bls_msg.message().cid().unwrap();
+        assert_eq!(block.bls_messages, vec![bls_cid]);
+        assert_eq!(block.secpk_messages, vec![secp_msg.cid().unwrap()]);
+        let msg_root = compute_msg_meta(
+            miner.state_manager.blockstore(),
+            &[bls_msg.message().clone()],
+            &[secp_msg],
+        )
+        .unwrap();
+        assert_eq!(header.messages(), &msg_root);
+
+        let aggregate = header.bls_aggregate().as_ref().unwrap();
+        assert!(verify_bls_aggregate(
+            &[bls_cid.to_bytes().as_slice()],
+            &[bls_pub_key.as_slice()],
+            aggregate
+        ));
+    }
+}
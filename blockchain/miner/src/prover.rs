@@ -0,0 +1,101 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use commcid::cid_to_replica_commitment_v1;
+use fil_types::verifier::prover_id_from_u64;
+use fil_types::{PoStProof, Randomness, SectorInfo};
+use filecoin_proofs_api::{post, PrivateReplicaInfo, SectorId};
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::error::Error as StdError;
+use std::path::PathBuf;
+
+/// Generates the winning proof of spacetime included in a block the miner was elected to produce.
+pub trait WinningPoStProver {
+    /// Computes the winning PoSt over the challenged sectors of the given miner actor id.
+    fn compute_proof(
+        &self,
+        sectors: &[SectorInfo],
+        randomness: Randomness,
+        prover: u64,
+    ) -> Result<Vec<PoStProof>, Box<dyn StdError>>;
+}
+
+/// Prover which generates proofs from sealed sectors on the local filesystem. Sectors are
+/// expected in the same layout as Lotus sector storage, sealed replicas under `sealed/` and
+/// their cache directories under `cache/`, both named `s-t0<miner id>-<sector number>`.
+pub struct LocalProver {
+    storage_path: PathBuf,
+}
+
+impl LocalProver {
+    /// Creates a prover for the sector storage at the given path.
+    pub fn new(storage_path: impl Into<PathBuf>) -> Self {
+        Self {
+            storage_path: storage_path.into(),
+        }
+    }
+}
+
+impl WinningPoStProver for LocalProver {
+    fn compute_proof(
+        &self,
+        sectors: &[SectorInfo],
+        Randomness(mut randomness): Randomness,
+        prover: u64,
+    ) -> Result<Vec<PoStProof>, Box<dyn StdError>> {
+        let post_proof = match sectors.first() {
+            Some(s) => s.proof.registered_winning_post_proof()?,
+            None => return Ok(Vec::new()),
+        };
+
+        // Necessary to be valid bls12 381 element.
+        randomness[31] &= 0x3f;
+
+        let replicas = sectors
+            .iter()
+            .map(|s| -> Result<_, Box<dyn StdError>> {
+                let commr = cid_to_replica_commitment_v1(&s.sealed_cid)?;
+                let name = format!("s-t0{}-{}", prover, s.sector_number);
+                let replica = PrivateReplicaInfo::new(
+                    s.proof.registered_winning_post_proof()?.try_into()?,
+                    commr,
+                    self.storage_path.join("cache").join(&name),
+                    self.storage_path.join("sealed").join(&name),
+                );
+                Ok((SectorId::from(s.sector_number), replica))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        let proofs =
+            post::generate_winning_post(&randomness, &replicas, prover_id_from_u64(prover))?;
+
+        Ok(proofs
+            .into_iter()
+            .map(|(_, proof_bytes)| PoStProof {
+                post_proof,
+                proof_bytes,
+            })
+            .collect())
+    }
+}
+
+/// Prover which generates placeholder proofs, to be used with a mock verifier for testing.
+pub struct MockProver;
+
+impl WinningPoStProver for MockProver {
+    fn compute_proof(
+        &self,
+        sectors: &[SectorInfo],
+        _: Randomness,
+        _: u64,
+    ) -> Result<Vec<PoStProof>, Box<dyn StdError>> {
+        match sectors.first() {
+            Some(s) => Ok(vec![PoStProof {
+                post_proof: s.proof.registered_winning_post_proof()?,
+                proof_bytes: b"valid proof".to_vec(),
+            }]),
+            None => Ok(Vec::new()),
+        }
+    }
+}
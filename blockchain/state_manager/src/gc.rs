@@ -0,0 +1,415 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{state_index, Error, StateManager};
+use actor::miner::CHAIN_FINALITY;
+use async_std::task;
+use blockstore::BlockStore;
+use cid::Cid;
+use clock::ChainEpoch;
+use db::{Error as DbError, IterableStore};
+use fil_types::verifier::ProofVerifier;
+use forest_blocks::Tipset;
+use futures::future::{BoxFuture, Future, FutureExt};
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Number of keys evicted from the store in a single batch.
+const SWEEP_BATCH_SIZE: usize = 1000;
+
+/// Delay before retrying a collection which was skipped because a sync was in progress.
+const SYNC_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+type SyncCheck = Box<dyn Fn() -> BoxFuture<'static, bool> + Send + Sync>;
+type RetainedCids = Box<dyn Fn() -> BoxFuture<'static, Result<Vec<Cid>, String>> + Send + Sync>;
+
+/// Mark-and-sweep garbage collector for the chain blockstore.
+///
+/// Everything reachable from the heaviest tipset is kept: all block headers back to genesis,
+/// plus the messages, receipts and state trees of the tipsets within the retained number of
+/// finality epochs, along with the retained Cids, such as the messages pending in the message
+/// pool. All other IPLD blocks are evicted from the store, which deletes them unless the store
+/// is tiered, in which case they are moved to the colder tier. Keys which are not Cids (such as
+/// the genesis and head metadata keys) are never removed, except for the persisted computed
+/// states of tipsets whose state tree is deleted.
+///
+/// The Cids of reachable blocks are marked in a single set, which the chain walk also uses to
+/// skip the blocks it has already visited, so memory use grows with the number of reachable
+/// blocks. The store is swept in a single pass over its keys, so memory use doesn't grow with
+/// the number of blocks evicted. Blocks written while collecting are recorded in the write log
+/// of the store and never evicted. Headers and messages persisted by a sync aren't reachable
+/// from the heaviest tipset until the sync completes, so collection is skipped while the sync
+/// check reports a sync in progress.
+pub struct ChainGc<DB, V> {
+    sm: Arc<StateManager<DB>>,
+    retain_epochs: ChainEpoch,
+    is_syncing: Option<SyncCheck>,
+    retained_cids: Option<RetainedCids>,
+    verifier: PhantomData<V>,
+}
+
+impl<DB, V> ChainGc<DB, V>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    V: ProofVerifier,
+{
+    /// Creates a new garbage collector which retains state for `finality_count` finality
+    /// periods behind the heaviest tipset.
+    pub fn new(sm: Arc<StateManager<DB>>, finality_count: u64) -> Self {
+        Self {
+            sm,
+            retain_epochs: CHAIN_FINALITY * finality_count as ChainEpoch,
+            is_syncing: None,
+            retained_cids: None,
+            verifier: Default::default(),
+        }
+    }
+
+    /// Sets the check of whether a sync is in progress, during which collection is skipped.
+    pub fn with_sync_check<F, Fut>(mut self, is_syncing: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        self.is_syncing = Some(Box::new(move || is_syncing().boxed()));
+        self
+    }
+
+    /// Sets the source of Cids to keep which aren't reachable from the chain, such as the
+    /// messages pending in the message pool.
+    pub fn with_retained_cids<F, Fut>(mut self, retained_cids: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Vec<Cid>, String>> + Send + 'static,
+    {
+        self.retained_cids = Some(Box::new(move || retained_cids().boxed()));
+        self
+    }
+
+    /// Runs garbage collection every `interval`. This never returns, so it should be spawned
+    /// as a separate task.
+    pub async fn run(self, interval: Duration) {
+        let mut wait = interval;
+        loop {
+            task::sleep(wait).await;
+            wait = match self.collect().await {
+                Ok(Some(_)) => interval,
+                Ok(None) => SYNC_RETRY_INTERVAL.min(interval),
+                Err(e) => {
+                    warn!("Blockstore garbage collection failed: {}", e);
+                    interval
+                }
+            };
+        }
+    }
+
+    /// Runs a single garbage collection pass, returning the number of evicted blocks, or `None`
+    /// if the pass was skipped because a sync is in progress.
+    pub async fn collect(&self) -> Result<Option<usize>, Error> {
+        if self.syncing().await {
+            debug!("Sync in progress, skipping blockstore garbage collection");
+            return Ok(None);
+        }
+
+        // Blocks written from here on are kept, even if they aren't marked.
+        let db = self.sm.blockstore_cloned();
+        db.write_log().start();
+        let evicted = self.mark_and_sweep().await;
+        db.write_log().stop();
+        evicted
+    }
+
+    async fn mark_and_sweep(&self) -> Result<Option<usize>, Error> {
+        let start = Instant::now();
+
+        let (mut marked, head) = self.mark_from_head(HashSet::new()).await?;
+
+        // The head could have changed while marking, so mark again from the new head to avoid
+        // deleting any old blocks which have become reachable from it.
+        if let Some(new_head) = self.sm.chain_store().heaviest_tipset().await {
+            if new_head.key() != head.key() {
+                marked = self.mark_from_head(marked).await?.0;
+            }
+        }
+
+        if let Some(retained_cids) = &self.retained_cids {
+            for cid in retained_cids().await.map_err(Error::Other)? {
+                marked.insert(cid);
+            }
+        }
+
+        // A sync which started while marking could have persisted headers and messages which
+        // aren't marked.
+        if self.syncing().await {
+            debug!("Sync started while marking, skipping blockstore garbage collection");
+            return Ok(None);
+        }
+
+        debug!("Marked {} reachable blocks", marked.len());
+
+        let db = self.sm.blockstore_cloned();
+        // Forget the computed states of tipsets whose state tree or receipts are deleted. Evicted
+        // blocks which are still readable from a colder tier keep their computed states.
+        let evict_deletes = db.evict_deletes();
+        let (evicted, marked) = task::spawn_blocking(move || -> Result<_, Error> {
+            if evict_deletes {
+                let pruned =
+                    state_index::prune_tipset_states(db.as_ref(), |root| !marked.contains(root))?;
+                debug!("Pruned {} persisted tipset states", pruned);
+            }
+
+            Ok((sweep(db.as_ref(), &marked)?, marked))
+        })
+        .await?;
+        if evict_deletes {
+            let evicted_states = self
+                .sm
+                .evict_cached_tipset_states(|root| !marked.contains(root))
+                .await;
+            debug!("Evicted {} cached tipset states", evicted_states);
+        }
+
+        info!(
+            "Blockstore garbage collection evicted {} blocks in {}s",
+            evicted,
+            start.elapsed().as_secs()
+        );
+        Ok(Some(evicted))
+    }
+
+    async fn syncing(&self) -> bool {
+        match &self.is_syncing {
+            Some(is_syncing) => is_syncing().await,
+            None => false,
+        }
+    }
+
+    /// Marks all Cids reachable from the heaviest tipset, returning the marked Cids with the
+    /// tipset marked from. Blocks already marked are not walked again.
+    async fn mark_from_head(
+        &self,
+        mut marked: HashSet<Cid>,
+    ) -> Result<(HashSet<Cid>, Arc<Tipset>), Error> {
+        let head = self
+            .sm
+            .chain_store()
+            .heaviest_tipset()
+            .await
+            .ok_or_else(|| Error::Other("No heaviest tipset to collect from".to_owned()))?;
+
+        // The computed state of the head is not referenced by any header yet.
+        let (state_root, receipts_root) = self
+            .sm
+            .tipset_state::<V>(&head)
+            .await
+            .map_err(|e| Error::Other(e.to_string()))?;
+
+        let cs = self.sm.chain_store().clone();
+        let retain_epochs = self.retain_epochs;
+        let mark_head = head.clone();
+        let marked = task::spawn_blocking(move || -> Result<HashSet<Cid>, chain::Error> {
+            // Walked Cids are added to the mark set, so there is nothing else to do with them.
+            cs.walk_snapshot_from(&mark_head, retain_epochs, true, &mut marked, |_| Ok(()))?;
+            cs.walk_links_from(&state_root, &mut marked, |_| Ok(()))?;
+            cs.walk_links_from(&receipts_root, &mut marked, |_| Ok(()))?;
+            Ok(marked)
+        })
+        .await
+        .map_err(|e| Error::Other(e.to_string()))?;
+
+        Ok((marked, head))
+    }
+}
+
+/// Returns the Cid of the block stored under the key, or `None` if the key is not a Cid.
+fn block_cid(key: &[u8]) -> Option<Cid> {
+    let cid = Cid::try_from(key.to_vec()).ok()?;
+    // Only keys which exactly match the Cid encoding are blocks
+    if cid.to_bytes() == key {
+        Some(cid)
+    } else {
+        None
+    }
+}
+
+/// Evicts the blocks which are not marked and weren't written since the write log of the store
+/// was started, returning the number of blocks evicted.
+fn sweep<DB>(db: &DB, marked: &HashSet<Cid>) -> Result<usize, Error>
+where
+    DB: IterableStore,
+{
+    let mut evicted = 0;
+    let mut dead = Vec::with_capacity(SWEEP_BATCH_SIZE);
+    let mut evict = |dead: &mut Vec<Vec<u8>>| -> Result<(), DbError> {
+        db.write_log()
+            .evict_unwritten(dead, |keys| db.evict(keys))?;
+        evicted += dead.len();
+        dead.clear();
+        Ok(())
+    };
+    db.for_each_key(|key| {
+        match block_cid(key) {
+            Some(cid) if !marked.contains(&cid) => dead.push(key.to_vec()),
+            _ => return Ok(()),
+        }
+        if dead.len() >= SWEEP_BATCH_SIZE {
+            evict(&mut dead)?;
+        }
+        Ok(())
+    })?;
+    evict(&mut dead)?;
+    Ok(evicted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use address::Address;
+    use chain::{ChainStore, HEAD_KEY};
+    use cid::Code::Blake2b256;
+    use db::{MemoryDB, Store};
+    use fil_types::verifier::MockVerifier;
+    use forest_blocks::{BlockHeader, TipsetKeys};
+
+    /// Persists a header along with its state, messages and receipts objects, returning the
+    /// header and the Cids persisted.
+    fn put_header(
+        db: &MemoryDB,
+        epoch: ChainEpoch,
+        parents: TipsetKeys,
+        name: &str,
+    ) -> (BlockHeader, Vec<Cid>) {
+        let put = |obj: &str| db.put(&format!("{} {}", name, obj), Blake2b256).unwrap();
+        let header = BlockHeader::builder()
+            .epoch(epoch)
+            .parents(parents)
+            .state_root(put("state"))
+            .messages(put("messages"))
+            .message_receipts(put("receipts"))
+            .miner_address(Address::new_id(0))
+            .build_and_validate()
+            .unwrap();
+        db.put(&header, Blake2b256).unwrap();
+        let cids = vec![
+            *header.cid(),
+            *header.state_root(),
+            *header.messages(),
+            *header.message_receipts(),
+        ];
+        (header, cids)
+    }
+
+    /// Sets up a chain of genesis and a head tipset with a persisted computed state, returning
+    /// the state manager, the head header and the Cids reachable from the head.
+    fn test_chain(db: Arc<MemoryDB>) -> (Arc<StateManager<MemoryDB>>, BlockHeader, Vec<Cid>) {
+        let (gen, mut reachable) = put_header(&db, 0, TipsetKeys::default(), "genesis");
+        let (head, head_cids) = put_header(&db, 1, TipsetKeys::new(vec![*gen.cid()]), "head");
+        reachable.extend(head_cids);
+        let head_ts = Arc::new(Tipset::new(vec![head.clone()]).unwrap());
+
+        let computed = (
+            db.put(&"head computed state", Blake2b256).unwrap(),
+            db.put(&"head computed receipts", Blake2b256).unwrap(),
+        );
+        state_index::put_tipset_state(db.as_ref(), head_ts.key(), &computed).unwrap();
+        reachable.extend(vec![computed.0, computed.1]);
+
+        let cs = Arc::new(ChainStore::new(db));
+        task::block_on(cs.set_heaviest_tipset(head_ts)).unwrap();
+        (Arc::new(StateManager::new(cs)), head, reachable)
+    }
+
+    #[test]
+    fn collect_evicts_unreachable_blocks() {
+        let db = Arc::new(MemoryDB::default());
+        let (sm, head, reachable) = test_chain(db.clone());
+        let (_, mut unreachable) = put_header(&db, 1, head.parents().clone(), "orphan");
+        unreachable.push(db.put(&"garbage", Blake2b256).unwrap());
+
+        let gc = ChainGc::<_, MockVerifier>::new(sm, 1);
+        assert_eq!(
+            task::block_on(gc.collect()).unwrap(),
+            Some(unreachable.len())
+        );
+
+        for cid in reachable {
+            assert!(db.exists(cid.to_bytes()).unwrap(), "{} was evicted", cid);
+        }
+        for cid in unreachable {
+            assert!(!db.exists(cid.to_bytes()).unwrap(), "{} was retained", cid);
+        }
+        assert!(db.exists(HEAD_KEY).unwrap());
+    }
+
+    #[test]
+    fn collect_prunes_tipset_states() {
+        let db = Arc::new(MemoryDB::default());
+        let (sm, head, _) = test_chain(db.clone());
+        let (orphan, _) = put_header(&db, 1, head.parents().clone(), "orphan");
+        let orphan_key = TipsetKeys::new(vec![*orphan.cid()]);
+        let orphan_state = (
+            db.put(&"orphan computed state", Blake2b256).unwrap(),
+            db.put(&"orphan computed receipts", Blake2b256).unwrap(),
+        );
+        state_index::put_tipset_state(db.as_ref(), &orphan_key, &orphan_state).unwrap();
+        let orphan_ts = Tipset::new(vec![orphan]).unwrap();
+        assert_eq!(
+            task::block_on(sm.tipset_state::<MockVerifier>(&orphan_ts)).unwrap(),
+            orphan_state
+        );
+
+        let gc = ChainGc::<_, MockVerifier>::new(sm.clone(), 1);
+        task::block_on(gc.collect()).unwrap();
+
+        // The state of the head is kept, the state of the orphan is forgotten even if its state
+        // tree is written again.
+        let head_key = TipsetKeys::new(vec![*head.cid()]);
+        assert!(state_index::load_tipset_state(db.as_ref(), &head_key)
+            .unwrap()
+            .is_some());
+        db.put(&"orphan computed state", Blake2b256).unwrap();
+        assert_eq!(
+            state_index::load_tipset_state(db.as_ref(), &orphan_key).unwrap(),
+            None
+        );
+        let cache = task::block_on(sm.cache.read());
+        assert!(cache.contains_key(&head_key));
+        assert!(!cache.contains_key(&orphan_key));
+    }
+
+    #[test]
+    fn collect_keeps_blocks_written_while_collecting() {
+        let db = Arc::new(MemoryDB::default());
+        let (sm, _, _) = test_chain(db.clone());
+        let written = MemoryDB::default()
+            .put(&"written while collecting", Blake2b256)
+            .unwrap();
+
+        let gc_db = db.clone();
+        let gc = ChainGc::<_, MockVerifier>::new(sm, 1).with_retained_cids(move || {
+            let db = gc_db.clone();
+            async move {
+                db.put(&"written while collecting", Blake2b256)
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>(Vec::new())
+            }
+        });
+        assert_eq!(task::block_on(gc.collect()).unwrap(), Some(0));
+        assert!(db.exists(written.to_bytes()).unwrap());
+    }
+
+    #[test]
+    fn collect_skipped_while_syncing() {
+        let db = Arc::new(MemoryDB::default());
+        let (sm, _, _) = test_chain(db.clone());
+        let garbage = db.put(&"garbage", Blake2b256).unwrap();
+
+        let gc = ChainGc::<_, MockVerifier>::new(sm, 1).with_sync_check(|| async { true });
+        assert_eq!(task::block_on(gc.collect()).unwrap(), None);
+        assert!(db.exists(garbage.to_bytes()).unwrap());
+    }
+}
@@ -6,10 +6,15 @@ extern crate lazy_static;
 
 mod chain_rand;
 mod errors;
+mod gc;
+mod state_index;
+mod upgrades;
 pub mod utils;
 mod vm_circ_supply;
 
 pub use self::errors::*;
+pub use self::gc::ChainGc;
+pub use self::upgrades::*;
 use actor::*;
 use address::{Address, BLSPublicKey, Payload, Protocol, BLS_PUB_LEN};
 use async_log::span;
@@ -22,12 +27,15 @@ use cid::Cid;
 use clock::ChainEpoch;
 use encoding::de::DeserializeOwned;
 use encoding::Cbor;
-use fil_types::{get_network_version_default, verifier::ProofVerifier};
+use fil_types::{verifier::ProofVerifier, NetworkChain, NetworkVersion};
 use flo_stream::Subscriber;
 use forest_blocks::{BlockHeader, Tipset, TipsetKeys};
 use futures::channel::oneshot;
 use futures::stream::{FuturesUnordered, StreamExt};
-use interpreter::{resolve_to_key_addr, ApplyRet, BlockMessages, Rand, VM};
+use interpreter::{
+    execution_trace, resolve_to_key_addr, ApplyRet, BlockMessages, CircSupplyCalc, ExecutionTrace,
+    PriceList, PriceSchedule, Rand, VM,
+};
 use ipld_amt::Amt;
 use lazycell::AtomicLazyCell;
 use log::{debug, info, trace, warn};
@@ -38,7 +46,7 @@ use serde::{Deserialize, Serialize};
 use state_tree::StateTree;
 use std::collections::HashMap;
 use std::error::Error as StdError;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use vm_circ_supply::GenesisInfoPair;
 
 /// Intermediary for retrieving state objects and updating actor states
@@ -53,11 +61,22 @@ pub struct InvocResult {
     #[serde(with = "message_receipt::json::opt")]
     pub msg_rct: Option<MessageReceipt>,
     pub error: Option<String>,
+    #[serde(with = "execution_trace::json::opt")]
+    pub execution_trace: Option<ExecutionTrace>,
 }
 
 // An alias Result that represents an InvocResult and an Error
 pub type StateCallResult = Result<InvocResult, Error>;
 
+/// Result of re-executing a tipset with additional messages
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ComputeStateOutput {
+    #[serde(with = "cid::json")]
+    pub root: Cid,
+    pub trace: Vec<InvocResult>,
+}
+
 #[derive(Default, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MarketBalance {
@@ -72,10 +91,14 @@ pub struct StateManager<DB> {
 
     /// This is a cache which indexes tipsets to their calculated state.
     /// The calculated state is wrapped in a mutex to avoid duplicate computation
-    /// of the state/receipt root.
+    /// of the state/receipt root. Calculated states are also persisted in the store, which is
+    /// checked before computing a state missing from the cache.
     cache: RwLock<HashMap<TipsetKeys, Arc<RwLock<Option<CidPair>>>>>,
     subscriber: Option<Subscriber<HeadChange>>,
     genesis_info: GenesisInfoPair,
+    chain: NetworkChain,
+    upgrade_schedule: UpgradeSchedule<DB>,
+    price_schedule: PriceSchedule,
 }
 
 impl<DB> StateManager<DB>
@@ -88,6 +111,9 @@ where
             cache: RwLock::new(HashMap::new()),
             subscriber: None,
             genesis_info: GenesisInfoPair::default(),
+            chain: NetworkChain::default(),
+            upgrade_schedule: UpgradeSchedule::default(),
+            price_schedule: PriceSchedule::mainnet(),
         }
     }
 
@@ -101,8 +127,41 @@ where
             cache: RwLock::new(HashMap::new()),
             subscriber: Some(chain_subs),
             genesis_info: GenesisInfoPair::default(),
+            chain: NetworkChain::default(),
+            upgrade_schedule: UpgradeSchedule::default(),
+            price_schedule: PriceSchedule::mainnet(),
         }
     }
+
+    /// Sets the network the chain belongs to, mainnet by default. This also replaces the
+    /// upgrade and gas price schedules with the schedules of the network.
+    pub fn with_network_chain(mut self, chain: NetworkChain) -> Self {
+        self.chain = chain;
+        self.upgrade_schedule = UpgradeSchedule::for_chain(chain);
+        self.price_schedule = PriceSchedule::for_chain(chain);
+        self
+    }
+
+    /// Sets the schedule of network upgrades of the network, mainnet's is used by default.
+    pub fn with_upgrade_schedule(mut self, upgrade_schedule: UpgradeSchedule<DB>) -> Self {
+        self.upgrade_schedule = upgrade_schedule;
+        self
+    }
+
+    /// Returns the network the chain belongs to.
+    pub fn network_chain(&self) -> NetworkChain {
+        self.chain
+    }
+
+    /// Returns the network version in effect at the given epoch.
+    pub fn network_version(&self, epoch: ChainEpoch) -> NetworkVersion {
+        self.upgrade_schedule.network_version(epoch)
+    }
+
+    /// Returns the gas price list in effect at the given epoch.
+    pub fn price_list(&self, epoch: ChainEpoch) -> &PriceList {
+        self.price_schedule.price_list(epoch)
+    }
     /// Loads actor state from IPLD Store
     pub fn load_actor_state<D>(&self, addr: &Address, state_cid: &Cid) -> Result<D, Error>
     where
@@ -198,11 +257,13 @@ where
     }
 
     /// Performs the state transition for the tipset and applies all unique messages in all blocks.
-    /// This function returns the state root and receipt root of the transition.
+    /// This function returns the state root and receipt root of the transition. The parent
+    /// tipset is given to the state migrations which read past chain state.
     #[allow(clippy::too_many_arguments)]
     pub fn apply_blocks<R, V, CB>(
         &self,
         parent_epoch: ChainEpoch,
+        parent: Option<&Tipset>,
         p_state: &Cid,
         messages: &[BlockMessages],
         epoch: ChainEpoch,
@@ -216,19 +277,60 @@ where
         CB: FnMut(&Cid, &ChainMessage, &ApplyRet) -> Result<(), String>,
     {
         let mut buf_store = BufferedBlockStore::new(self.blockstore());
-        // TODO change from statically using devnet params when needed
+        let network_version = |epoch| self.network_version(epoch);
+        // Only trace executions when the results are observed through the callback
+        let tracing = callback.is_some();
+        let mut callback = callback;
+
+        // Run cron for the null rounds since the parent, and the state migrations of any network
+        // upgrade crossed
+        let mut state_root = *p_state;
+        for i in parent_epoch..epoch {
+            if i > parent_epoch {
+                let mut vm = VM::<_, _, _, _, V>::new(
+                    &state_root,
+                    &buf_store,
+                    i,
+                    rand,
+                    base_fee.clone(),
+                    network_version,
+                    self.chain,
+                    &self.price_schedule,
+                    &self.genesis_info,
+                )?;
+                if tracing {
+                    vm.enable_tracing();
+                }
+                vm.run_cron(i, callback.as_mut())?;
+                state_root = vm.flush()?;
+            }
+            if let Some(migration) = self.upgrade_schedule.migration(i)? {
+                info!(
+                    "running state migration for network upgrade at height {}",
+                    i
+                );
+                buf_store.flush(&state_root)?;
+                state_root = migration(&self.cs, parent, &state_root, i)?;
+            }
+        }
+
         let mut vm = VM::<_, _, _, _, V>::new(
-            p_state,
+            &state_root,
             &buf_store,
             epoch,
             rand,
             base_fee,
-            get_network_version_default,
+            network_version,
+            self.chain,
+            &self.price_schedule,
             &self.genesis_info,
         )?;
+        if tracing {
+            vm.enable_tracing();
+        }
 
         // Apply tipset messages
-        let receipts = vm.apply_block_messages(messages, parent_epoch, epoch, callback)?;
+        let receipts = vm.apply_block_messages(messages, epoch, callback)?;
 
         // Construct receipt root from receipts
         let rect_root = Amt::new_from_slice(self.blockstore(), &receipts)?;
@@ -241,6 +343,27 @@ where
         Ok((state_root, rect_root))
     }
 
+    /// Removes the cached computed states of tipsets for which `remove` returns true given the
+    /// state root or the receipts root, returning the number of states removed. States still
+    /// being computed are kept.
+    pub(crate) async fn evict_cached_tipset_states<F>(&self, remove: F) -> usize
+    where
+        F: Fn(&Cid) -> bool,
+    {
+        let mut cache = self.cache.write().await;
+        let cached = cache.len();
+        cache.retain(|_, entry| match entry.try_read() {
+            Some(state) => match *state {
+                Some((state_root, receipts_root)) => {
+                    !remove(&state_root) && !remove(&receipts_root)
+                }
+                None => true,
+            },
+            None => true,
+        });
+        cached - cache.len()
+    }
+
     /// Returns the pair of (parent state root, message receipt root)
     pub async fn tipset_state<V>(
         self: &Arc<Self>,
@@ -277,6 +400,14 @@ where
                 return Ok(*entry);
             }
 
+            // State could have been computed before a restart, load it from the store.
+            if let Some(cid_pair) = state_index::load_tipset_state(self.blockstore(), tipset.key())?
+            {
+                trace!("loaded persisted state for tipset {:?}", tipset.cids());
+                *entry_lock = Some(cid_pair);
+                return Ok(cid_pair);
+            }
+
             // Entry does not have state computed yet, this task will fill entry if successful.
             debug!("calculating tipset state {:?}", tipset.cids());
 
@@ -297,7 +428,8 @@ where
                 self.compute_tipset_state::<V, _>(&tipset, no_func).await?
             };
 
-            // Fill entry with calculated cid pair
+            // Persist and fill entry with calculated cid pair
+            state_index::put_tipset_state(self.blockstore(), tipset.key(), &cid_pair)?;
             *entry_lock = Some(cid_pair);
             Ok(cid_pair)
         })
@@ -306,6 +438,7 @@ where
     fn call_raw<V>(
         &self,
         msg: &mut UnsignedMessage,
+        prior_messages: &[ChainMessage],
         bstate: &Cid,
         rand: &ChainRand<DB>,
         bheight: &ChainEpoch,
@@ -322,16 +455,23 @@ where
                 *bheight,
                 rand,
                 0.into(),
-                get_network_version_default,
+                |epoch| self.network_version(epoch),
+                self.chain,
+                &self.price_schedule,
                 &self.genesis_info,
             )?;
 
+            apply_prior_messages(&mut vm, prior_messages)?;
+            vm.enable_tracing();
+
             if msg.gas_limit() == 0 {
                 msg.set_gas_limit(10000000000)
             }
 
-            let actor = self
-                .get_actor(msg.from(), bstate)?
+            let actor = vm
+                .state()
+                .get_actor(msg.from())
+                .map_err(|e| Error::Other(format!("Could not get actor from state: {}", e)))?
                 .ok_or_else(|| Error::Other("Could not get actor".to_string()))?;
             msg.set_sequence(actor.sequence);
             let apply_ret = vm.apply_implicit_message(msg);
@@ -349,14 +489,18 @@ where
                 msg: msg.clone(),
                 msg_rct: Some(apply_ret.msg_receipt.clone()),
                 error: apply_ret.act_error.map(|e| e.to_string()),
+                execution_trace: apply_ret.execution_trace,
             })
         })
     }
 
-    /// runs the given message and returns its result without any persisted changes.
+    /// runs the given message and returns its result without any persisted changes. The message
+    /// is applied on the state computed for the tipset, at the following epoch, after the prior
+    /// messages such as pending messages of the sender.
     pub async fn call<V>(
-        &self,
+        self: &Arc<Self>,
         message: &mut UnsignedMessage,
+        prior_messages: &[ChainMessage],
         tipset: Option<Arc<Tipset>>,
     ) -> StateCallResult
     where
@@ -370,9 +514,12 @@ where
                 .await
                 .ok_or_else(|| Error::Other("No heaviest tipset".to_string()))?
         };
-        let state = ts.parent_state();
         let chain_rand = ChainRand::new(ts.key().to_owned(), self.cs.clone());
-        self.call_raw::<V>(message, state, &chain_rand, &ts.epoch())
+        let (st, _) = self
+            .tipset_state::<V>(&ts)
+            .await
+            .map_err(|e| Error::Other(format!("Could not load tipset state: {}", e)))?;
+        self.call_raw::<V>(message, prior_messages, &st, &chain_rand, &(ts.epoch() + 1))
     }
 
     pub async fn call_with_gas<V>(
@@ -404,13 +551,14 @@ where
             ts.epoch() + 1,
             &chain_rand,
             ts.blocks()[0].parent_base_fee().clone(),
-            get_network_version_default,
+            |epoch| self.network_version(epoch),
+            self.chain,
+            &self.price_schedule,
             &self.genesis_info,
         )?;
 
-        for msg in prior_messages {
-            vm.apply_message(&msg)?;
-        }
+        apply_prior_messages(&mut vm, prior_messages)?;
+        vm.enable_tracing();
         let from_actor = vm
             .state()
             .get_actor(message.from())
@@ -424,6 +572,7 @@ where
             msg: message.message().clone(),
             msg_rct: Some(ret.msg_receipt.clone()),
             error: ret.act_error.map(|e| e.to_string()),
+            execution_trace: ret.execution_trace,
         })
     }
 
@@ -471,6 +620,140 @@ where
         Ok((out_mes, out_ret))
     }
 
+    /// Re-executes the messages of the tipset, runs cron for the null rounds up to the given
+    /// height, then applies the extra messages on top of the resulting state. Returns the resulting state root along with the
+    /// execution results and traces of every message applied.
+    pub async fn compute_tipset_with_messages<V>(
+        self: &Arc<Self>,
+        height: ChainEpoch,
+        extra_msgs: Vec<ChainMessage>,
+        tsk: &TipsetKeys,
+    ) -> Result<ComputeStateOutput, Error>
+    where
+        V: ProofVerifier,
+    {
+        let ts = self
+            .cs
+            .tipset_from_keys(tsk)
+            .map_err(|e| Error::Other(e.to_string()))?;
+        if height < ts.epoch() {
+            return Err(Error::Other(format!(
+                "cannot compute state at height {} below tipset epoch {}",
+                height,
+                ts.epoch()
+            )));
+        }
+
+        let trace: Arc<Mutex<Vec<InvocResult>>> = Default::default();
+        let state_root = if ts.epoch() == 0 {
+            // Genesis messages are not executed, see tipset_state
+            *ts.parent_state()
+        } else {
+            let trace = trace.clone();
+            let callback = move |_: &Cid, msg: &ChainMessage, ret: &ApplyRet| {
+                trace.lock().map_err(|e| e.to_string())?.push(InvocResult {
+                    msg: msg.message().clone(),
+                    msg_rct: Some(ret.msg_receipt.clone()),
+                    error: ret.act_error.as_ref().map(|e| e.to_string()),
+                    execution_trace: ret.execution_trace.clone(),
+                });
+                Ok(())
+            };
+            self.compute_tipset_state::<V, _>(&ts, Some(callback))
+                .await?
+                .0
+        };
+        let mut trace = trace
+            .lock()
+            .map(|mut trace| std::mem::take(&mut *trace))
+            .map_err(|e| Error::Other(e.to_string()))?;
+
+        let sm = self.clone();
+        let chain_rand = ChainRand::new(ts.key().to_owned(), self.cs.clone());
+        let base_fee = ts.blocks()[0].parent_base_fee().clone();
+        let ts_epoch = ts.epoch();
+        task::spawn_blocking(move || -> Result<ComputeStateOutput, Error> {
+            // Run cron for the null rounds up to the requested height, and the state migrations
+            // of any network upgrade crossed, as when applying blocks
+            let buf_store = BufferedBlockStore::new(sm.blockstore());
+            let mut state_root = state_root;
+            for i in ts_epoch..height {
+                if i > ts_epoch {
+                    let mut vm = VM::<_, _, _, _, V>::new(
+                        &state_root,
+                        &buf_store,
+                        i,
+                        &chain_rand,
+                        base_fee.clone(),
+                        |epoch| sm.network_version(epoch),
+                        sm.chain,
+                        &sm.price_schedule,
+                        &sm.genesis_info,
+                    )?;
+                    vm.enable_tracing();
+                    let mut callback =
+                        |_: &Cid, msg: &ChainMessage, ret: &ApplyRet| -> Result<(), String> {
+                            trace.push(InvocResult {
+                                msg: msg.message().clone(),
+                                msg_rct: Some(ret.msg_receipt.clone()),
+                                error: ret.act_error.as_ref().map(|e| e.to_string()),
+                                execution_trace: ret.execution_trace.clone(),
+                            });
+                            Ok(())
+                        };
+                    vm.run_cron(i, Some(&mut callback))
+                        .map_err(|e| Error::Other(e.to_string()))?;
+                    state_root = vm.flush().map_err(|e| Error::Other(e.to_string()))?;
+                }
+                if let Some(migration) = sm.upgrade_schedule.migration(i)? {
+                    buf_store
+                        .flush(&state_root)
+                        .map_err(|e| Error::Other(e.to_string()))?;
+                    state_root = migration(&sm.cs, Some(&ts), &state_root, i)
+                        .map_err(|e| Error::Other(e.to_string()))?;
+                }
+            }
+
+            let mut vm = VM::<_, _, _, _, V>::new(
+                &state_root,
+                &buf_store,
+                height,
+                &chain_rand,
+                base_fee,
+                |epoch| sm.network_version(epoch),
+                sm.chain,
+                &sm.price_schedule,
+                &sm.genesis_info,
+            )?;
+            vm.enable_tracing();
+
+            for msg in extra_msgs {
+                let ret = vm.apply_message(&msg)?;
+                if let Some(err) = &ret.act_error {
+                    warn!(
+                        "message from {} with sequence {} failed: {}",
+                        msg.from(),
+                        msg.sequence(),
+                        err
+                    );
+                }
+                trace.push(InvocResult {
+                    msg: msg.message().clone(),
+                    msg_rct: Some(ret.msg_receipt),
+                    error: ret.act_error.map(|e| e.to_string()),
+                    execution_trace: ret.execution_trace,
+                });
+            }
+
+            let root = vm.flush().map_err(|e| Error::Other(e.to_string()))?;
+            buf_store
+                .flush(&root)
+                .map_err(|e| Error::Other(e.to_string()))?;
+            Ok(ComputeStateOutput { root, trace })
+        })
+        .await
+    }
+
     pub async fn compute_tipset_state<V, CB: 'static>(
         self: &Arc<Self>,
         tipset: &Tipset,
@@ -498,23 +781,16 @@ where
                 return Err(Error::Other(format!("duplicate miner in a tipset ({})", a)));
             }
 
-            let parent_epoch = if first_block.epoch() > 0 {
-                let parent_cid = first_block
-                    .parents()
-                    .cids()
-                    .get(0)
-                    .ok_or_else(|| Error::Other("block must have parents".to_string()))?;
-                let parent: BlockHeader = self
-                    .blockstore()
-                    .get(parent_cid)
-                    .map_err(|e| Error::Other(e.to_string()))?
-                    .ok_or_else(|| {
-                        format!("Could not find parent block with cid {}", parent_cid)
-                    })?;
-                parent.epoch()
+            let parent = if first_block.epoch() > 0 {
+                Some(
+                    self.cs
+                        .tipset_from_keys(first_block.parents())
+                        .map_err(|e| Error::Other(e.to_string()))?,
+                )
             } else {
-                Default::default()
+                None
             };
+            let parent_epoch = parent.as_ref().map(|p| p.epoch()).unwrap_or_default();
 
             let tipset_keys =
                 TipsetKeys::new(block_headers.iter().map(|s| s.cid()).cloned().collect());
@@ -532,6 +808,7 @@ where
             task::spawn_blocking(move || {
                 sm.apply_blocks::<_, V, _>(
                     parent_epoch,
+                    parent.as_ref(),
                     &sr,
                     &blocks,
                     epoch,
@@ -666,27 +943,19 @@ where
     where
         DB: BlockStore + Send + Sync + 'static,
     {
-        let mut subscribers = subscriber.clone().ok_or_else(|| {
+        subscriber.as_ref().ok_or_else(|| {
             Error::Other("State Manager not subscribed to tipset head changes".to_string())
         })?;
         let (sender, mut receiver) = oneshot::channel::<()>();
         let message = chain::get_chain_message(self.blockstore(), cid)
             .map_err(|err| Error::Other(format!("failed to load message {:}", err)))?;
 
-        let maybe_subscriber: Option<HeadChange> = subscribers.next().await;
-        let first_subscriber = maybe_subscriber.ok_or_else(|| {
-            Error::Other("SubHeadChanges first entry should have been one item".to_string())
-        })?;
-
-        let tipset = match first_subscriber {
-            HeadChange::Current(tipset) => tipset,
-            _ => {
-                return Err(Error::Other(format!(
-                    "expected current head on SHC stream (got {:?})",
-                    first_subscriber
-                )))
-            }
-        };
+        // Head changes only carry the tipsets applied and reverted, start from the current head.
+        let tipset = self
+            .cs
+            .heaviest_tipset()
+            .await
+            .ok_or_else(|| Error::Other("Could not get heaviest tipset".to_string()))?;
         let message_var = (message.from(), &message.sequence());
         let maybe_message_reciept = self.tipset_executed_message(&tipset, cid, message_var)?;
         if let Some(r) = maybe_message_reciept {
@@ -901,8 +1170,12 @@ where
         let ps: power::State = self
             .load_actor_state(&*STORAGE_POWER_ACTOR_ADDR, ts.parent_state())
             .map_err(|e| format!("loading power actor state: {}", e))?;
-        ps.miner_nominal_power_meets_consensus_minimum(self.blockstore(), addr)
-            .map_err(|e| e.to_string())
+        ps.miner_nominal_power_meets_consensus_minimum(
+            self.blockstore(),
+            addr,
+            &self.chain.consensus_miner_min_power(),
+        )
+        .map_err(|e| e.to_string())
     }
 
     pub async fn validate_chain<V: ProofVerifier>(
@@ -947,3 +1220,115 @@ where
         Ok(())
     }
 }
+
+/// Applies prior messages, such as the pending messages of a sender, in sequence order. Messages
+/// already included in the state are skipped, and no messages are applied past a sequence gap.
+fn apply_prior_messages<DB, R, N, C, V>(
+    vm: &mut VM<'_, '_, DB, R, N, C, V>,
+    prior_messages: &[ChainMessage],
+) -> Result<(), Error>
+where
+    DB: BlockStore,
+    R: Rand,
+    N: Fn(ChainEpoch) -> NetworkVersion,
+    C: CircSupplyCalc,
+    V: ProofVerifier,
+{
+    for prior in prior_messages {
+        let sequence = vm
+            .state()
+            .get_actor(prior.from())
+            .map_err(|e| Error::Other(format!("Could not get actor from state: {}", e)))?
+            .map(|actor| actor.sequence);
+        match sequence {
+            Some(sequence) if prior.sequence() < sequence => continue,
+            Some(sequence) if prior.sequence() == sequence => {
+                vm.apply_message(prior)?;
+            }
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::Code::Identity;
+    use db::MemoryDB;
+    use fil_types::verifier::MockVerifier;
+    use vm::{ActorState, ExitCode};
+
+    fn message(from: Address, sequence: u64) -> UnsignedMessage {
+        UnsignedMessage::builder()
+            .to(Address::new_id(101))
+            .from(from)
+            .sequence(sequence)
+            .gas_limit(1_000_000)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn call_applies_prior_messages() {
+        let db = Arc::new(MemoryDB::default());
+        let sender = Address::new_id(100);
+
+        let state_with_sequence = |sequence| {
+            let mut state = StateTree::new(db.as_ref());
+            let account_state = db
+                .put(&account::State { address: sender }, Identity)
+                .unwrap();
+            state
+                .set_actor(
+                    &sender,
+                    ActorState::new(
+                        ACCOUNT_ACTOR_CODE_ID.clone(),
+                        account_state,
+                        0.into(),
+                        sequence,
+                    ),
+                )
+                .unwrap();
+            state
+                .set_actor(
+                    &Address::new_id(101),
+                    ActorState::new(ACCOUNT_ACTOR_CODE_ID.clone(), account_state, 0.into(), 0),
+                )
+                .unwrap();
+            state.flush().unwrap()
+        };
+
+        // The first message of the sender is included in the head, so it is only in the state
+        // computed for the head and not in its parent state
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .epoch(5)
+            .state_root(state_with_sequence(0))
+            .build_and_validate()
+            .unwrap();
+        let head = Arc::new(Tipset::new(vec![header]).unwrap());
+        let receipts = Amt::<MessageReceipt, _>::new_from_slice(db.as_ref(), &[]).unwrap();
+        state_index::put_tipset_state(db.as_ref(), head.key(), &(state_with_sequence(1), receipts))
+            .unwrap();
+        let sm = Arc::new(StateManager::new(Arc::new(ChainStore::new(db))));
+
+        let call = |prior: &[u64]| {
+            let prior: Vec<ChainMessage> = prior
+                .iter()
+                .map(|&sequence| ChainMessage::Unsigned(message(sender, sequence)))
+                .collect();
+            let mut msg = message(sender, 0);
+            let res = task::block_on(sm.call::<MockVerifier>(&mut msg, &prior, Some(head.clone())))
+                .unwrap();
+            assert_eq!(res.msg_rct.unwrap().exit_code, ExitCode::Ok);
+            res.msg.sequence()
+        };
+
+        // Without prior messages the message is applied on the same state
+        assert_eq!(call(&[]), 1);
+        // The included message is skipped, and the ones after the gap are not applied
+        assert_eq!(call(&[0, 1, 2, 4, 5]), 3);
+        assert_eq!(call(&[2, 3]), 1);
+    }
+}
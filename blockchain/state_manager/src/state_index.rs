@@ -0,0 +1,102 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{CidPair, Error};
+use cid::Cid;
+use db::{IterableStore, Store};
+use encoding::{from_slice, to_vec};
+use forest_blocks::TipsetKeys;
+
+/// Prefix of the keys computed tipset states are persisted under, followed by the encoded keys
+/// of the tipset.
+const TIPSET_STATE_PREFIX: &[u8] = b"tipset_state/";
+
+fn tipset_state_key(tsk: &TipsetKeys) -> Result<Vec<u8>, Error> {
+    let mut key = TIPSET_STATE_PREFIX.to_vec();
+    key.extend(to_vec(tsk).map_err(|e| Error::Other(e.to_string()))?);
+    Ok(key)
+}
+
+/// Loads the persisted (state root, receipts root) pair computed for a tipset. States whose state
+/// tree or receipts are no longer in the store are ignored, so that they are computed again.
+pub(crate) fn load_tipset_state<DB>(db: &DB, tsk: &TipsetKeys) -> Result<Option<CidPair>, Error>
+where
+    DB: Store,
+{
+    let bz = match db.read(tipset_state_key(tsk)?)? {
+        Some(bz) => bz,
+        None => return Ok(None),
+    };
+    let (state_root, receipts_root): CidPair =
+        from_slice(&bz).map_err(|e| Error::Other(e.to_string()))?;
+    if !db.exists(state_root.to_bytes())? || !db.exists(receipts_root.to_bytes())? {
+        return Ok(None);
+    }
+    Ok(Some((state_root, receipts_root)))
+}
+
+/// Persists the (state root, receipts root) pair computed for a tipset.
+pub(crate) fn put_tipset_state<DB>(db: &DB, tsk: &TipsetKeys, state: &CidPair) -> Result<(), Error>
+where
+    DB: Store,
+{
+    let bz = to_vec(state).map_err(|e| Error::Other(e.to_string()))?;
+    Ok(db.write(tipset_state_key(tsk)?, bz)?)
+}
+
+/// Removes the persisted states of tipsets for which `remove` returns true given the state root
+/// or the receipts root, returning the number of states removed.
+pub(crate) fn prune_tipset_states<DB, F>(db: &DB, remove: F) -> Result<usize, Error>
+where
+    DB: IterableStore,
+    F: Fn(&Cid) -> bool,
+{
+    let mut keys = Vec::new();
+    db.for_each_key_with_prefix(TIPSET_STATE_PREFIX, |key| {
+        keys.push(key.to_vec());
+        Ok(())
+    })?;
+
+    let mut pruned = Vec::new();
+    for key in keys {
+        if let Some(bz) = db.read(&key)? {
+            let (state_root, receipts_root): CidPair =
+                from_slice(&bz).map_err(|e| Error::Other(e.to_string()))?;
+            if remove(&state_root) || remove(&receipts_root) {
+                pruned.push(key);
+            }
+        }
+    }
+    db.bulk_delete(&pruned)?;
+    Ok(pruned.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::Code::Identity;
+    use db::MemoryDB;
+
+    #[test]
+    fn persist_tipset_state() {
+        let db = MemoryDB::default();
+        let state_root = Cid::new_from_cbor(&[1], Identity);
+        let receipts_root = Cid::new_from_cbor(&[2], Identity);
+        let tsk = TipsetKeys::new(vec![Cid::new_from_cbor(&[3], Identity)]);
+
+        put_tipset_state(&db, &tsk, &(state_root, receipts_root)).unwrap();
+
+        // State tree and receipts aren't in the store yet.
+        assert_eq!(load_tipset_state(&db, &tsk).unwrap(), None);
+        db.write(state_root.to_bytes(), [0]).unwrap();
+        assert_eq!(load_tipset_state(&db, &tsk).unwrap(), None);
+        db.write(receipts_root.to_bytes(), [0]).unwrap();
+        assert_eq!(
+            load_tipset_state(&db, &tsk).unwrap(),
+            Some((state_root, receipts_root))
+        );
+
+        let other = TipsetKeys::new(vec![Cid::new_from_cbor(&[4], Identity)]);
+        assert_eq!(load_tipset_state(&db, &other).unwrap(), None);
+    }
+}
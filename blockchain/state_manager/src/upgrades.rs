@@ -0,0 +1,812 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use actor::{
+    init, miner, multisig, power, ACCOUNT_ACTOR_CODE_ID, BURNT_FUNDS_ACTOR_ADDR, INIT_ACTOR_ADDR,
+    MINER_ACTOR_CODE_ID, MULTISIG_ACTOR_CODE_ID, PAYCH_ACTOR_CODE_ID, RESERVE_ADDRESS,
+    STORAGE_POWER_ACTOR_ADDR,
+};
+use address::Address;
+use blockstore::BlockStore;
+use chain::ChainStore;
+use cid::{Cid, Code::Blake2b256};
+use clock::ChainEpoch;
+use fil_types::{
+    NetworkChain, NetworkVersion, FILECOIN_PRECISION, HAMT_BIT_WIDTH, TOTAL_FILECOIN,
+    UPGRADE_ACTORS_V2_HEIGHT, UPGRADE_BREEZE_HEIGHT, UPGRADE_CALIBNET_ACTORS_V2_HEIGHT,
+    UPGRADE_CALIBNET_BREEZE_HEIGHT, UPGRADE_CALIBNET_CALICO_HEIGHT,
+    UPGRADE_CALIBNET_IGNITION_HEIGHT, UPGRADE_CALIBNET_KUMQUAT_HEIGHT,
+    UPGRADE_CALIBNET_SMOKE_HEIGHT, UPGRADE_CALIBNET_TAPE_HEIGHT, UPGRADE_CALICO_HEIGHT,
+    UPGRADE_IGNITION_HEIGHT, UPGRADE_KUMQUAT_HEIGHT, UPGRADE_LIFTOFF_HEIGHT, UPGRADE_REFUEL_HEIGHT,
+    UPGRADE_SMOKE_HEIGHT, UPGRADE_TAPE_HEIGHT,
+};
+use forest_blocks::Tipset;
+use ipld_amt::Amt;
+use ipld_hamt::Hamt;
+use log::warn;
+use state_tree::StateTree;
+use std::error::Error as StdError;
+use vm::{ActorState, TokenAmount};
+
+/// Actor ids of the multisigs whose vesting is reset by the refuel upgrade: the SAFT, reserve
+/// and root verifier multisigs.
+const REFUEL_MULTISIG_IDS: [u64; 3] = [122, 90, 80];
+
+/// Epoch of the state whose actors get funds back in the breeze upgrade.
+const BREEZE_LOOKBACK_EPOCH: ChainEpoch = 32000;
+/// FIL shared by the miners in the breeze upgrade, in proportion to their committed storage.
+const BREEZE_FUNDS_FOR_MINERS: i64 = 1_000_000;
+/// FIL given in the breeze upgrade to each miner which had sectors at the lookback epoch.
+const BREEZE_BASE_MINER_BALANCE: i64 = 20;
+/// Actor id of the reimbursement service, topped up by the breeze upgrade.
+const BREEZE_REIMBURSEMENT_ID: u64 = 111;
+/// FIL balance the reimbursement service is topped up to by the breeze upgrade.
+const BREEZE_REIMBURSEMENT_BALANCE: i64 = 5_000_000;
+
+/// Migration of the state tree run at a network upgrade. It is given the chain store, the
+/// parent tipset of the tipset being computed if it is known, and the state root computed at
+/// the upgrade height. It returns the root of the migrated state tree.
+pub type StateMigration<DB> =
+    fn(&ChainStore<DB>, Option<&Tipset>, &Cid, ChainEpoch) -> Result<Cid, Box<dyn StdError>>;
+
+/// State migration of a network upgrade.
+pub enum Migration<DB> {
+    /// The upgrade does not change the state tree.
+    None,
+    /// The state tree is migrated with the given function.
+    Run(StateMigration<DB>),
+    /// The upgrade migrates the state tree, but the migration is not implemented. The state
+    /// cannot be computed across the upgrade.
+    Unsupported,
+}
+
+impl<DB> Clone for Migration<DB> {
+    fn clone(&self) -> Self {
+        match self {
+            Migration::None => Migration::None,
+            Migration::Run(migration) => Migration::Run(*migration),
+            Migration::Unsupported => Migration::Unsupported,
+        }
+    }
+}
+
+/// Network upgrade, which takes effect after the given height.
+pub struct Upgrade<DB> {
+    pub height: ChainEpoch,
+    pub network: NetworkVersion,
+    pub migration: Migration<DB>,
+}
+
+impl<DB> Clone for Upgrade<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            height: self.height,
+            network: self.network,
+            migration: self.migration.clone(),
+        }
+    }
+}
+
+/// Schedule of the network upgrades of a network, ordered by height.
+pub struct UpgradeSchedule<DB> {
+    upgrades: Vec<Upgrade<DB>>,
+}
+
+impl<DB> Clone for UpgradeSchedule<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            upgrades: self.upgrades.clone(),
+        }
+    }
+}
+
+impl<DB> Default for UpgradeSchedule<DB>
+where
+    DB: BlockStore,
+{
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+impl<DB> UpgradeSchedule<DB>
+where
+    DB: BlockStore,
+{
+    /// Creates a schedule from a list of upgrades. Upgrades must be in increasing order of height
+    /// and network version.
+    pub fn new(upgrades: Vec<Upgrade<DB>>) -> Result<Self, String> {
+        for pair in upgrades.windows(2) {
+            if pair[0].height >= pair[1].height {
+                return Err(format!(
+                    "upgrade at height {} is not after upgrade at height {}",
+                    pair[1].height, pair[0].height
+                ));
+            }
+            if pair[0].network > pair[1].network {
+                return Err(format!(
+                    "upgrade at height {} downgrades network version from {:?} to {:?}",
+                    pair[1].height, pair[0].network, pair[1].network
+                ));
+            }
+        }
+        Ok(Self { upgrades })
+    }
+
+    /// Upgrade schedule of mainnet. The ignition migration, which corrects the miner and power
+    /// actor states and splits and resets the genesis multisigs, is not implemented. The actors
+    /// v2 and calico migrations need the v2 actors, which are not implemented either. The state
+    /// cannot be computed across any of these upgrades.
+    pub fn mainnet() -> Self {
+        let upgrade = |height, network, migration| Upgrade {
+            height,
+            network,
+            migration,
+        };
+        Self {
+            upgrades: vec![
+                upgrade(
+                    UPGRADE_BREEZE_HEIGHT,
+                    NetworkVersion::V1,
+                    Migration::Run(migrate_breeze::<DB>),
+                ),
+                upgrade(UPGRADE_SMOKE_HEIGHT, NetworkVersion::V2, Migration::None),
+                upgrade(
+                    UPGRADE_IGNITION_HEIGHT,
+                    NetworkVersion::V3,
+                    Migration::Unsupported,
+                ),
+                upgrade(
+                    UPGRADE_REFUEL_HEIGHT,
+                    NetworkVersion::V3,
+                    Migration::Run(migrate_refuel::<DB>),
+                ),
+                upgrade(
+                    UPGRADE_ACTORS_V2_HEIGHT,
+                    NetworkVersion::V4,
+                    Migration::Unsupported,
+                ),
+                upgrade(UPGRADE_TAPE_HEIGHT, NetworkVersion::V5, Migration::None),
+                upgrade(
+                    UPGRADE_LIFTOFF_HEIGHT,
+                    NetworkVersion::V5,
+                    Migration::Run(migrate_liftoff::<DB>),
+                ),
+                upgrade(UPGRADE_KUMQUAT_HEIGHT, NetworkVersion::V6, Migration::None),
+                upgrade(
+                    UPGRADE_CALICO_HEIGHT,
+                    NetworkVersion::V7,
+                    Migration::Unsupported,
+                ),
+            ],
+        }
+    }
+
+    /// Upgrade schedule of the calibration network. The upgrades before actors v2 happened
+    /// before its genesis, and its network name was never changed by liftoff. The actors v2 and
+    /// calico migrations need the v2 actors, so the state cannot be computed across them.
+    pub fn calibnet() -> Self {
+        let upgrade = |height, network, migration| Upgrade {
+            height,
+            network,
+            migration,
+        };
+        Self {
+            upgrades: vec![
+                upgrade(
+                    UPGRADE_CALIBNET_BREEZE_HEIGHT,
+                    NetworkVersion::V1,
+                    Migration::None,
+                ),
+                upgrade(
+                    UPGRADE_CALIBNET_SMOKE_HEIGHT,
+                    NetworkVersion::V2,
+                    Migration::None,
+                ),
+                upgrade(
+                    UPGRADE_CALIBNET_IGNITION_HEIGHT,
+                    NetworkVersion::V3,
+                    Migration::None,
+                ),
+                upgrade(
+                    UPGRADE_CALIBNET_ACTORS_V2_HEIGHT,
+                    NetworkVersion::V4,
+                    Migration::Unsupported,
+                ),
+                upgrade(
+                    UPGRADE_CALIBNET_TAPE_HEIGHT,
+                    NetworkVersion::V5,
+                    Migration::None,
+                ),
+                upgrade(
+                    UPGRADE_CALIBNET_KUMQUAT_HEIGHT,
+                    NetworkVersion::V6,
+                    Migration::None,
+                ),
+                upgrade(
+                    UPGRADE_CALIBNET_CALICO_HEIGHT,
+                    NetworkVersion::V7,
+                    Migration::Unsupported,
+                ),
+            ],
+        }
+    }
+
+    /// Upgrade schedule of local devnets, which start at the latest network version.
+    pub fn devnet() -> Self {
+        Self {
+            upgrades: vec![Upgrade {
+                height: -1,
+                network: NetworkVersion::V7,
+                migration: Migration::None,
+            }],
+        }
+    }
+
+    /// Upgrade schedule of the given network.
+    pub fn for_chain(chain: NetworkChain) -> Self {
+        match chain {
+            NetworkChain::Mainnet => Self::mainnet(),
+            NetworkChain::Calibnet => Self::calibnet(),
+            NetworkChain::Devnet => Self::devnet(),
+        }
+    }
+
+    /// Returns the network version in effect at the given epoch.
+    pub fn network_version(&self, epoch: ChainEpoch) -> NetworkVersion {
+        self.upgrades
+            .iter()
+            .take_while(|upgrade| epoch > upgrade.height)
+            .last()
+            .map(|upgrade| upgrade.network)
+            .unwrap_or(NetworkVersion::V0)
+    }
+
+    /// Returns the state migration to run on the state computed at the given epoch, if any.
+    /// Errors if an upgrade at the epoch requires a migration which is not implemented.
+    pub fn migration(&self, epoch: ChainEpoch) -> Result<Option<StateMigration<DB>>, String> {
+        match self
+            .upgrades
+            .iter()
+            .find(|upgrade| upgrade.height == epoch)
+            .map(|upgrade| &upgrade.migration)
+        {
+            Some(Migration::Run(migration)) => Ok(Some(*migration)),
+            Some(Migration::Unsupported) => Err(format!(
+                "state migration of the network upgrade at height {} is not supported",
+                epoch
+            )),
+            Some(Migration::None) | None => Ok(None),
+        }
+    }
+}
+
+/// Breeze migration, which recovers the funds burnt through the testnet faucet. The balances of
+/// the accounts, multisigs and payment channels, except system ones, and the available balances
+/// of the miners are moved to the reserve. The miners which existed at the lookback epoch then
+/// get back a share of the miner funds in proportion to their committed storage, plus a base
+/// balance if they had sectors at the lookback. Finally the burnt funds are moved to the
+/// reserve, and the reimbursement service is topped up from it.
+fn migrate_breeze<DB>(
+    chain_store: &ChainStore<DB>,
+    parent: Option<&Tipset>,
+    state_root: &Cid,
+    _epoch: ChainEpoch,
+) -> Result<Cid, Box<dyn StdError>>
+where
+    DB: BlockStore,
+{
+    let store = chain_store.blockstore();
+    let parent = parent.ok_or("breeze migration requires the parent tipset")?;
+    let lookback = chain_store
+        .tipset_by_height(BREEZE_LOOKBACK_EPOCH, parent, false)?
+        .unwrap_or_else(|| parent.clone());
+    let lookback_tree = StateTree::new_from_root(store, lookback.parent_state())?;
+
+    // Transfers only change balances, so actors are iterated on the state before the migration
+    let actors: Hamt<_, ActorState> = Hamt::load_with_bit_width(state_root, store, HAMT_BIT_WIDTH)?;
+    let mut tree = StateTree::new_from_root(store, state_root)?;
+
+    // Take all excess funds away, into the reserve
+    let mut transfers = Vec::new();
+    actors.for_each(|key, actor| {
+        let addr = Address::from_bytes(&key.0)?;
+        if actor.code == *ACCOUNT_ACTOR_CODE_ID
+            || actor.code == *MULTISIG_ACTOR_CODE_ID
+            || actor.code == *PAYCH_ACTOR_CODE_ID
+        {
+            // Actors with an id below 1000 are system actors
+            if addr.id()? >= 1000 {
+                transfers.push((addr, actor.balance.clone()));
+            }
+        } else if actor.code == *MINER_ACTOR_CODE_ID {
+            let state: miner::State = store
+                .get(&actor.state)?
+                .ok_or_else(|| format!("miner {} state not found", addr))?;
+            let unlocked = &actor.balance - &state.locked_funds - &state.pre_commit_deposits;
+            if unlocked < TokenAmount::default() {
+                warn!(
+                    "miner {} balance {} does not cover its locked funds",
+                    addr, actor.balance
+                );
+                return Ok(());
+            }
+            let available = unlocked - &state.initial_pledge_requirement;
+            if available != TokenAmount::default() {
+                transfers.push((addr, available));
+            }
+        }
+        Ok(())
+    })?;
+    for (addr, amount) in transfers {
+        transfer(&mut tree, &addr, &RESERVE_ADDRESS, &amount)?;
+    }
+
+    let power_actor = tree
+        .get_actor(&STORAGE_POWER_ACTOR_ADDR)?
+        .ok_or("power actor not found in state")?;
+    let power_state: power::State = store
+        .get(&power_actor.state)?
+        .ok_or("power actor state not found")?;
+    let total_power = power_state.total_bytes_committed;
+
+    // Give funds back to the miners which existed at the lookback. The accounts, multisigs and
+    // payment channels get their lookback balance back capped at zero, so nothing.
+    let mut transfers = Vec::new();
+    actors.for_each(|key, actor| {
+        if actor.code != *MINER_ACTOR_CODE_ID {
+            return Ok(());
+        }
+        let addr = Address::from_bytes(&key.0)?;
+        let lookback_actor = match lookback_tree.get_actor(&addr)? {
+            Some(actor) => actor,
+            None => return Ok(()),
+        };
+
+        let state: miner::State = store
+            .get(&actor.state)?
+            .ok_or_else(|| format!("miner {} state not found", addr))?;
+        let info = state.get_info(store)?;
+        let sectors = Amt::<miner::SectorOnChainInfo, _>::load(&state.sectors, store)?;
+        let power = TokenAmount::from(sectors.count()) * (info.sector_size as u64);
+        if total_power == TokenAmount::default() {
+            return Err("no storage committed to share the miner funds by".into());
+        }
+        let funds = power * BREEZE_FUNDS_FOR_MINERS * FILECOIN_PRECISION / &total_power;
+        transfers.push((info.worker, funds));
+
+        let lookback_state: miner::State = store
+            .get(&lookback_actor.state)?
+            .ok_or_else(|| format!("miner {} lookback state not found", addr))?;
+        let lookback_sectors =
+            Amt::<miner::SectorOnChainInfo, _>::load(&lookback_state.sectors, store)?;
+        if lookback_sectors.count() > 0 {
+            transfers.push((
+                info.worker,
+                TokenAmount::from(BREEZE_BASE_MINER_BALANCE) * FILECOIN_PRECISION,
+            ));
+        }
+        Ok(())
+    })?;
+    for (addr, amount) in transfers {
+        transfer(&mut tree, &RESERVE_ADDRESS, &addr, &amount)?;
+    }
+
+    // Move the burnt funds back to the reserve, and top up the reimbursement service
+    let burnt_funds = tree
+        .get_actor(&BURNT_FUNDS_ACTOR_ADDR)?
+        .ok_or("burnt funds actor not found in state")?;
+    transfer(
+        &mut tree,
+        &BURNT_FUNDS_ACTOR_ADDR,
+        &RESERVE_ADDRESS,
+        &burnt_funds.balance,
+    )?;
+    let reimbursement_addr = Address::new_id(BREEZE_REIMBURSEMENT_ID);
+    let reimbursement = tree
+        .get_actor(&reimbursement_addr)?
+        .ok_or("reimbursement actor not found in state")?;
+    let top_up = TokenAmount::from(BREEZE_REIMBURSEMENT_BALANCE) * FILECOIN_PRECISION
+        - reimbursement.balance;
+    transfer(&mut tree, &RESERVE_ADDRESS, &reimbursement_addr, &top_up)?;
+
+    let root = tree.flush()?;
+
+    // Sanity check that the migration moved funds without creating or destroying any
+    let mut total = TokenAmount::default();
+    Hamt::<_, ActorState>::load_with_bit_width(&root, store, HAMT_BIT_WIDTH)?.for_each(
+        |_, actor| {
+            total += &actor.balance;
+            Ok(())
+        },
+    )?;
+    if total != *TOTAL_FILECOIN {
+        return Err(format!(
+            "balances after the breeze migration sum to {}, not the total supply",
+            total
+        )
+        .into());
+    }
+    Ok(root)
+}
+
+/// Moves funds between two actors of the state tree. Fails if the sender cannot cover them.
+fn transfer<DB>(
+    tree: &mut StateTree<DB>,
+    from: &Address,
+    to: &Address,
+    amount: &TokenAmount,
+) -> Result<(), Box<dyn StdError>>
+where
+    DB: BlockStore,
+{
+    tree.mutate_actor(from, |actor| {
+        actor.balance -= amount;
+        if actor.balance < TokenAmount::default() {
+            return Err(format!(
+                "transfer of {} exceeds the balance of {}",
+                amount, from
+            ));
+        }
+        Ok(())
+    })?;
+    tree.mutate_actor(to, |actor| {
+        actor.balance += amount;
+        Ok(())
+    })
+}
+
+/// Refuel migration, which removes the vesting schedules of the SAFT, reserve and root verifier
+/// multisigs.
+fn migrate_refuel<DB>(
+    chain_store: &ChainStore<DB>,
+    _parent: Option<&Tipset>,
+    state_root: &Cid,
+    _epoch: ChainEpoch,
+) -> Result<Cid, Box<dyn StdError>>
+where
+    DB: BlockStore,
+{
+    let store = chain_store.blockstore();
+    let mut tree = StateTree::new_from_root(store, state_root)?;
+    for id in REFUEL_MULTISIG_IDS.iter() {
+        let addr = Address::new_id(*id);
+        let mut actor = tree
+            .get_actor(&addr)?
+            .ok_or_else(|| format!("multisig {} not found in state", addr))?;
+        let mut msig_state: multisig::State = store
+            .get(&actor.state)?
+            .ok_or_else(|| format!("multisig {} state not found", addr))?;
+
+        msig_state.set_locked(0, 0, TokenAmount::default());
+        actor.state = store.put(&msig_state, Blake2b256)?;
+        tree.set_actor(&addr, actor)?;
+    }
+    tree.flush()
+}
+
+/// Liftoff migration, which renames the network to mainnet in the init actor state.
+fn migrate_liftoff<DB>(
+    chain_store: &ChainStore<DB>,
+    _parent: Option<&Tipset>,
+    state_root: &Cid,
+    _epoch: ChainEpoch,
+) -> Result<Cid, Box<dyn StdError>>
+where
+    DB: BlockStore,
+{
+    let store = chain_store.blockstore();
+    let mut tree = StateTree::new_from_root(store, state_root)?;
+    let mut init_actor = tree
+        .get_actor(&INIT_ACTOR_ADDR)?
+        .ok_or("init actor not found in state")?;
+    let mut init_state: init::State = store
+        .get(&init_actor.state)?
+        .ok_or("init actor state not found")?;
+
+    init_state.network_name = "mainnet".to_owned();
+    init_actor.state = store.put(&init_state, Blake2b256)?;
+    tree.set_actor(&INIT_ACTOR_ADDR, init_actor)?;
+    tree.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actor::POWER_ACTOR_CODE_ID;
+    use db::MemoryDB;
+    use fil_types::RegisteredSealProof;
+    use forest_blocks::BlockHeader;
+    use std::sync::Arc;
+
+    fn noop_migration(
+        _: &ChainStore<MemoryDB>,
+        _: Option<&Tipset>,
+        root: &Cid,
+        _: ChainEpoch,
+    ) -> Result<Cid, Box<dyn StdError>> {
+        Ok(*root)
+    }
+
+    fn fil(amount: i64) -> TokenAmount {
+        TokenAmount::from(amount) * FILECOIN_PRECISION
+    }
+
+    #[test]
+    fn schedule_lookup() {
+        let schedule = UpgradeSchedule::<MemoryDB>::new(vec![
+            Upgrade {
+                height: 10,
+                network: NetworkVersion::V1,
+                migration: Migration::None,
+            },
+            Upgrade {
+                height: 20,
+                network: NetworkVersion::V2,
+                migration: Migration::Run(noop_migration),
+            },
+            Upgrade {
+                height: 30,
+                network: NetworkVersion::V3,
+                migration: Migration::Unsupported,
+            },
+        ])
+        .unwrap();
+
+        assert_eq!(schedule.network_version(0), NetworkVersion::V0);
+        assert_eq!(schedule.network_version(10), NetworkVersion::V0);
+        assert_eq!(schedule.network_version(11), NetworkVersion::V1);
+        assert_eq!(schedule.network_version(21), NetworkVersion::V2);
+        assert!(schedule.migration(10).unwrap().is_none());
+        assert!(schedule.migration(20).unwrap().is_some());
+        assert!(schedule.migration(21).unwrap().is_none());
+        assert!(schedule.migration(30).is_err());
+    }
+
+    #[test]
+    fn schedule_must_be_ordered() {
+        let upgrade = |height, network| Upgrade::<MemoryDB> {
+            height,
+            network,
+            migration: Migration::None,
+        };
+        assert!(UpgradeSchedule::new(vec![
+            upgrade(20, NetworkVersion::V1),
+            upgrade(10, NetworkVersion::V2)
+        ])
+        .is_err());
+        assert!(UpgradeSchedule::new(vec![
+            upgrade(10, NetworkVersion::V2),
+            upgrade(20, NetworkVersion::V1)
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn network_schedules() {
+        let calibnet = UpgradeSchedule::<MemoryDB>::for_chain(NetworkChain::Calibnet);
+        assert_eq!(calibnet.network_version(0), NetworkVersion::V3);
+        assert_eq!(calibnet.network_version(31), NetworkVersion::V4);
+        assert_eq!(calibnet.network_version(61), NetworkVersion::V5);
+        assert_eq!(calibnet.network_version(91), NetworkVersion::V6);
+        assert_eq!(calibnet.network_version(101), NetworkVersion::V7);
+        assert!(calibnet
+            .migration(UPGRADE_LIFTOFF_HEIGHT)
+            .unwrap()
+            .is_none());
+        assert!(calibnet
+            .migration(UPGRADE_CALIBNET_ACTORS_V2_HEIGHT)
+            .is_err());
+        assert!(calibnet
+            .migration(UPGRADE_CALIBNET_KUMQUAT_HEIGHT)
+            .unwrap()
+            .is_none());
+        assert!(calibnet.migration(UPGRADE_CALIBNET_CALICO_HEIGHT).is_err());
+
+        let devnet = UpgradeSchedule::<MemoryDB>::for_chain(NetworkChain::Devnet);
+        assert_eq!(devnet.network_version(0), NetworkVersion::V7);
+    }
+
+    #[test]
+    fn mainnet_migrations() {
+        let mainnet = UpgradeSchedule::<MemoryDB>::mainnet();
+        assert!(mainnet.migration(UPGRADE_BREEZE_HEIGHT).unwrap().is_some());
+        assert!(mainnet.migration(UPGRADE_SMOKE_HEIGHT).unwrap().is_none());
+        assert!(mainnet.migration(UPGRADE_IGNITION_HEIGHT).is_err());
+        assert!(mainnet.migration(UPGRADE_REFUEL_HEIGHT).unwrap().is_some());
+        assert!(mainnet.migration(UPGRADE_ACTORS_V2_HEIGHT).is_err());
+        assert!(mainnet.migration(UPGRADE_LIFTOFF_HEIGHT).unwrap().is_some());
+        assert!(mainnet.migration(UPGRADE_KUMQUAT_HEIGHT).unwrap().is_none());
+        assert!(mainnet.migration(UPGRADE_CALICO_HEIGHT).is_err());
+    }
+
+    #[test]
+    fn breeze_recovers_burnt_funds() {
+        let chain_store = ChainStore::new(Arc::new(MemoryDB::default()));
+        let store = chain_store.blockstore();
+        let mut tree = StateTree::new(store);
+
+        let account = Address::new_id(1000);
+        let miner = Address::new_id(1001);
+        let worker = Address::new_id(1002);
+        let reimbursement = Address::new_id(BREEZE_REIMBURSEMENT_ID);
+        let set_actor = |tree: &mut StateTree<_>, addr, code, state, balance| {
+            let actor = ActorState::new(code, state, balance, 0);
+            tree.set_actor(&addr, actor).unwrap();
+        };
+        set_actor(
+            &mut tree,
+            account,
+            *ACCOUNT_ACTOR_CODE_ID,
+            Cid::default(),
+            fil(100),
+        );
+        set_actor(
+            &mut tree,
+            worker,
+            *ACCOUNT_ACTOR_CODE_ID,
+            Cid::default(),
+            fil(0),
+        );
+        set_actor(
+            &mut tree,
+            reimbursement,
+            *ACCOUNT_ACTOR_CODE_ID,
+            Cid::default(),
+            fil(1_000_000),
+        );
+        set_actor(
+            &mut tree,
+            *BURNT_FUNDS_ACTOR_ADDR,
+            *ACCOUNT_ACTOR_CODE_ID,
+            Cid::default(),
+            fil(300),
+        );
+
+        // A miner with a single sector, which is all of the committed storage
+        let info = miner::MinerInfo::new(
+            worker,
+            worker,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            RegisteredSealProof::StackedDRG2KiBV1,
+        )
+        .unwrap();
+        let sector = miner::SectorOnChainInfo {
+            sector_number: 0,
+            seal_proof: RegisteredSealProof::StackedDRG2KiBV1,
+            sealed_cid: Cid::default(),
+            deal_ids: Vec::new(),
+            activation: 0,
+            expiration: 100_000,
+            deal_weight: Default::default(),
+            verified_deal_weight: Default::default(),
+            initial_pledge: Default::default(),
+            expected_day_reward: Default::default(),
+            expected_storage_pledge: Default::default(),
+        };
+        let sectors = Amt::new_from_slice(store, &[sector]).unwrap();
+        let mut miner_state = miner::State::new(
+            store.put(&info, Blake2b256).unwrap(),
+            0,
+            Cid::default(),
+            sectors,
+            Cid::default(),
+            Cid::default(),
+            Cid::default(),
+        );
+        miner_state.locked_funds = fil(10);
+        miner_state.initial_pledge_requirement = fil(5);
+        set_actor(
+            &mut tree,
+            miner,
+            *MINER_ACTOR_CODE_ID,
+            store.put(&miner_state, Blake2b256).unwrap(),
+            fil(50),
+        );
+        let mut power_state = power::State::new(Cid::default(), Cid::default());
+        power_state.total_bytes_committed = (info.sector_size as u64).into();
+        set_actor(
+            &mut tree,
+            *STORAGE_POWER_ACTOR_ADDR,
+            *POWER_ACTOR_CODE_ID,
+            store.put(&power_state, Blake2b256).unwrap(),
+            fil(0),
+        );
+
+        // The reserve holds the rest of the supply
+        let reserve = &*TOTAL_FILECOIN - fil(100) - fil(1_000_000) - fil(300) - fil(50);
+        set_actor(
+            &mut tree,
+            *RESERVE_ADDRESS,
+            *MULTISIG_ACTOR_CODE_ID,
+            Cid::default(),
+            reserve.clone(),
+        );
+        let root = tree.flush().unwrap();
+
+        // The parent tipset is at the lookback epoch, with the same state
+        let header = BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .epoch(BREEZE_LOOKBACK_EPOCH)
+            .state_root(root)
+            .build_and_validate()
+            .unwrap();
+        let parent = Tipset::new(vec![header]).unwrap();
+
+        let root =
+            migrate_breeze(&chain_store, Some(&parent), &root, UPGRADE_BREEZE_HEIGHT).unwrap();
+        let tree = StateTree::new_from_root(store, &root).unwrap();
+        let balance = |addr: &Address| tree.get_actor(addr).unwrap().unwrap().balance;
+        assert_eq!(balance(&account), fil(0));
+        // The miner keeps its locked funds and initial pledge
+        assert_eq!(balance(&miner), fil(15));
+        assert_eq!(
+            balance(&worker),
+            fil(BREEZE_FUNDS_FOR_MINERS) + fil(BREEZE_BASE_MINER_BALANCE)
+        );
+        assert_eq!(balance(&BURNT_FUNDS_ACTOR_ADDR), fil(0));
+        assert_eq!(balance(&reimbursement), fil(BREEZE_REIMBURSEMENT_BALANCE));
+        assert_eq!(
+            balance(&RESERVE_ADDRESS),
+            reserve + fil(100) + fil(35) + fil(300)
+                - fil(BREEZE_FUNDS_FOR_MINERS)
+                - fil(BREEZE_BASE_MINER_BALANCE)
+                - fil(4_000_000)
+        );
+
+        assert!(migrate_breeze(&chain_store, None, &root, UPGRADE_BREEZE_HEIGHT).is_err());
+    }
+
+    #[test]
+    fn refuel_resets_vesting() {
+        let chain_store = ChainStore::new(Arc::new(MemoryDB::default()));
+        let store = chain_store.blockstore();
+        let mut tree = StateTree::new(store);
+        let msig_state = multisig::State {
+            signers: Vec::new(),
+            num_approvals_threshold: 1,
+            next_tx_id: multisig::TxnID(0),
+            initial_balance: 1000.into(),
+            start_epoch: 10,
+            unlock_duration: 100,
+            pending_txs: Cid::default(),
+        };
+        let head = store.put(&msig_state, Blake2b256).unwrap();
+        for id in REFUEL_MULTISIG_IDS.iter() {
+            let actor = ActorState::new(*MULTISIG_ACTOR_CODE_ID, head, 1000.into(), 0);
+            tree.set_actor(&Address::new_id(*id), actor).unwrap();
+        }
+        let root = tree.flush().unwrap();
+
+        let root = migrate_refuel(&chain_store, None, &root, UPGRADE_REFUEL_HEIGHT).unwrap();
+        let tree = StateTree::new_from_root(store, &root).unwrap();
+        for id in REFUEL_MULTISIG_IDS.iter() {
+            let actor = tree.get_actor(&Address::new_id(*id)).unwrap().unwrap();
+            let state: multisig::State = store.get(&actor.state).unwrap().unwrap();
+            assert_eq!(state.initial_balance, TokenAmount::default());
+            assert_eq!(state.start_epoch, 0);
+            assert_eq!(state.unlock_duration, 0);
+        }
+    }
+
+    #[test]
+    fn mainnet_matches_default_versions() {
+        let schedule = UpgradeSchedule::<MemoryDB>::mainnet();
+        for epoch in &[
+            0,
+            UPGRADE_BREEZE_HEIGHT,
+            UPGRADE_BREEZE_HEIGHT + 1,
+            UPGRADE_ACTORS_V2_HEIGHT + 1,
+            UPGRADE_LIFTOFF_HEIGHT + 1,
+            UPGRADE_KUMQUAT_HEIGHT + 1,
+            UPGRADE_CALICO_HEIGHT + 1,
+        ] {
+            assert_eq!(
+                schedule.network_version(*epoch),
+                fil_types::get_network_version_default(*epoch)
+            );
+        }
+    }
+}
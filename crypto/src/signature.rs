@@ -195,6 +195,31 @@ pub fn verify_bls_aggregate(data: &[&[u8]], pub_keys: &[&[u8]], aggregate_sig: &
     verify(&sig, &hashed_data[..], &pks[..])
 }
 
+/// Aggregates a set of bls signatures into a single signature. An empty set of signatures
+/// aggregates to the point at infinity.
+pub fn aggregate_bls_signatures(sigs: &[Signature]) -> Result<Signature, Error> {
+    if sigs.is_empty() {
+        // Compressed encoding of the G2 identity element
+        let mut bytes = vec![0u8; BLS_SIG_LEN];
+        bytes[0] = 0xc0;
+        return Ok(Signature::new_bls(bytes));
+    }
+    let bls_sigs = sigs
+        .iter()
+        .map(|s| {
+            if s.signature_type() != SignatureType::BLS {
+                return Err(Error::SigningError(
+                    "cannot aggregate non bls signature".to_owned(),
+                ));
+            }
+            BlsSignature::from_bytes(s.bytes()).map_err(|e| Error::SigningError(e.to_string()))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let agg =
+        bls_signatures::aggregate(&bls_sigs).map_err(|e| Error::SigningError(e.to_string()))?;
+    Ok(Signature::new_bls(agg.as_bytes()))
+}
+
 /// Return Address for a message given it's signing bytes hash and signature.
 pub fn ecrecover(hash: &[u8; 32], signature: &[u8; SECP_SIG_LEN]) -> Result<Address, Error> {
     // generate types to recover key from
@@ -254,6 +279,12 @@ mod tests {
             verify_bls_aggregate(&data, &public_keys_slice, &calculated_bls_agg),
             true
         );
+
+        let sigs: Vec<Signature> = signatures
+            .iter()
+            .map(|s| Signature::new_bls(s.as_bytes()))
+            .collect();
+        assert_eq!(aggregate_bls_signatures(&sigs).unwrap(), calculated_bls_agg);
     }
 
     #[test]
@@ -2,8 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::stringify_rpc_err;
+use blocks::TipsetKeys;
 use cid::Cid;
-use rpc_client::{block, genesis, head, messages, new_client, read_obj};
+use rpc_client::{block, export, genesis, head, messages, new_client, read_obj};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -15,6 +16,33 @@ pub enum ChainCommands {
         cid: String,
     },
 
+    /// Exports the chain from the given tipset to a CAR file, which can be imported
+    /// with `--import-snapshot`. The file is written by the daemon to its export
+    /// directory, and must not exist yet.
+    #[structopt(about = "<OUTPUT> Export chain snapshot to a CAR file")]
+    Export {
+        #[structopt(
+            long,
+            help = "Tipset to export from, as comma separated CIDs (defaults to chain head)"
+        )]
+        tipset: Option<String>,
+        #[structopt(
+            long,
+            default_value = "900",
+            help = "Number of recent epochs to include state roots for"
+        )]
+        recent_stateroots: i64,
+        #[structopt(
+            long,
+            help = "Do not include messages older than the recent state roots"
+        )]
+        skip_old_msgs: bool,
+        #[structopt(
+            help = "File name of the snapshot in the daemon's export directory, must not exist"
+        )]
+        output: String,
+    },
+
     /// Prints out the genesis tipset
     #[structopt(about = "Prints genesis tipset", help = "Prints genesis tipset")]
     Genesis,
@@ -54,6 +82,30 @@ impl ChainCommands {
                     .unwrap();
                 println!("{}", serde_json::to_string_pretty(&blk).unwrap());
             }
+            Self::Export {
+                tipset,
+                recent_stateroots,
+                skip_old_msgs,
+                output,
+            } => {
+                let cids: Vec<Cid> = match tipset {
+                    Some(tipset) => tipset.split(',').map(|c| c.parse().unwrap()).collect(),
+                    None => Vec::new(),
+                };
+                let mut client = new_client();
+
+                let path = export(
+                    &mut client,
+                    TipsetKeys::new(cids),
+                    *recent_stateroots,
+                    *skip_old_msgs,
+                    output.clone(),
+                )
+                .await
+                .map_err(stringify_rpc_err)
+                .unwrap();
+                println!("Exported snapshot to {}", path);
+            }
             Self::Genesis => {
                 let mut client = new_client();
 
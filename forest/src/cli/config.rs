@@ -2,12 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use beacon::DrandPublic;
+use chain::DEFAULT_FORK_LENGTH_THRESHOLD;
+use fil_types::NetworkChain;
 use forest_libp2p::Libp2pConfig;
 use serde::Deserialize;
 use utils::get_home_dir;
 #[derive(Debug, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Network the node joins, which selects its network parameters.
+    pub chain: NetworkChain,
     pub network: Libp2pConfig,
     pub data_dir: String,
     pub genesis_file: Option<String>,
@@ -18,11 +22,34 @@ pub struct Config {
     /// Otherwise, we validate and compute the states.
     pub snapshot: bool,
     pub snapshot_path: Option<String>,
+    /// If this is true, blocks not reachable from recent state are periodically deleted.
+    pub enable_gc: bool,
+    /// Number of finality periods of state kept behind the head when garbage collecting.
+    pub gc_finality_count: u64,
+    /// Interval in seconds between garbage collection passes.
+    pub gc_interval: u64,
+    /// If this is true, recent state is kept in a hot store and garbage collection moves
+    /// blocks into a separate cold store instead of deleting them. Requires `enable_gc`.
+    pub enable_splitstore: bool,
+    /// Address of a miner actor to produce blocks for. Block production is disabled if unset.
+    /// The miner's worker key must be in the keystore.
+    pub miner_address: Option<String>,
+    /// Directory containing the miner's sealed sectors, used to generate winning PoSt proofs.
+    /// Defaults to `sectors` within the data directory.
+    pub sector_storage_path: Option<String>,
+    /// Directory snapshots exported through the `Filecoin.ChainExport` RPC are written to.
+    /// Defaults to `exports` within the data directory.
+    pub export_dir: Option<String>,
+    /// Maximum number of tipsets of the current chain reverted when switching to a heavier fork.
+    pub fork_length_threshold: u64,
+    /// Number of workers syncing separate targets in parallel while catching up with the chain.
+    pub sync_workers: usize,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            chain: NetworkChain::default(),
             network: Libp2pConfig::default(),
             data_dir: get_home_dir() + "/.forest",
             genesis_file: None,
@@ -31,6 +58,15 @@ impl Default for Config {
             rpc_port: "1234".to_string(),
             snapshot_path: None,
             snapshot: false,
+            enable_gc: false,
+            gc_finality_count: 2,
+            gc_interval: 60 * 60,
+            enable_splitstore: false,
+            miner_address: None,
+            sector_storage_path: None,
+            export_dir: None,
+            fork_length_threshold: DEFAULT_FORK_LENGTH_THRESHOLD,
+            sync_workers: 3,
         }
     }
 }
@@ -13,6 +13,7 @@ pub use self::config::Config;
 pub(super) use self::fetch_params_cmd::FetchCommands;
 pub(super) use self::genesis_cmd::GenesisCommands;
 
+use fil_types::NetworkChain;
 use jsonrpc_v2::Error as JsonRpcError;
 use std::cell::RefCell;
 use std::io;
@@ -64,6 +65,11 @@ pub struct DaemonOpts {
     pub config: Option<String>,
     #[structopt(short, long, help = "The genesis CAR file")]
     pub genesis: Option<String>,
+    #[structopt(
+        long,
+        help = "Network to join: mainnet, calibnet or devnet (default = mainnet)"
+    )]
+    pub chain: Option<NetworkChain>,
     #[structopt(short, long, help = "Allow rpc to be active or not (default = true)")]
     pub rpc: Option<bool>,
     #[structopt(short, long, help = "The port used for communication")]
@@ -92,6 +98,16 @@ impl DaemonOpts {
         if let Some(genesis_file) = &self.genesis {
             cfg.genesis_file = Some(genesis_file.to_owned());
         }
+        if let Some(chain) = self.chain {
+            cfg.chain = chain;
+        }
+        // Only mainnet's genesis is embedded in the binary
+        if cfg.genesis_file.is_none() && cfg.chain != NetworkChain::Mainnet {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("a genesis file must be specified to run on {}", cfg.chain),
+            ));
+        }
         if self.rpc.unwrap_or(cfg.enable_rpc) {
             cfg.enable_rpc = true;
             cfg.rpc_port = self.port.to_owned().unwrap_or(cfg.rpc_port);
@@ -113,8 +129,30 @@ impl DaemonOpts {
 
         cfg.network.kademlia = self.kademlia.unwrap_or(cfg.network.kademlia);
         cfg.network.mdns = self.mdns.unwrap_or(cfg.network.mdns);
+        if cfg.network.bootstrap_peers.is_empty() {
+            cfg.network.bootstrap_peers = cfg
+                .chain
+                .bootstrap_peers()
+                .iter()
+                .map(|addr| {
+                    addr.parse().map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid bootstrap peer {}: {}", addr, e),
+                        )
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+        }
         // (where to find these flags, should be easy to do with structops)
 
+        if cfg.enable_splitstore && !cfg.enable_gc {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "enable_splitstore requires enable_gc to move blocks to the cold store",
+            ));
+        }
+
         Ok(cfg)
     }
 }
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::cli::{block_until_sigint, Config};
-use actor::EPOCH_DURATION_SECONDS;
+use address::Address;
 use async_std::sync::RwLock;
 use async_std::task;
 use auth::{generate_priv_key, JWT_IDENTIFIER};
@@ -10,31 +10,30 @@ use beacon::{DrandBeacon, DEFAULT_DRAND_URL};
 use blocks::TipsetKeys;
 use chain::ChainStore;
 use chain_sync::ChainSyncer;
-use db::RocksDb;
+use db::{IterableStore, RocksDb};
 use encoding::Cbor;
 use fil_types::verifier::{FullVerifier, ProofVerifier};
 use flo_stream::{MessagePublisher, Publisher};
 use forest_car::load_car;
 use forest_libp2p::{get_keypair, Libp2pService};
+use forest_miner::{LocalProver, Miner};
 use genesis::initialize_genesis;
-use ipld_blockstore::BlockStore;
+use ipld_blockstore::{BlockStore, SplitStore};
 use libp2p::identity::{ed25519, Keypair};
 use log::{debug, info, trace};
 use message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
 use paramfetch::{get_params_default, SectorSizeOpt};
 use rpc::{start_rpc, RpcState};
-use state_manager::StateManager;
+use state_manager::{ChainGc, StateManager};
 use std::fs::File;
 use std::io::BufReader;
 use std::io::Read;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use utils::write_to_file;
 use wallet::{KeyStore, PersistentKeyStore};
 
-/// Number of tasks spawned for sync workers.
-// TODO benchmark and/or add this as a config option. (1 is temporary value to avoid overlap)
-const WORKER_TASKS: usize = 1;
-
 /// Import a chain from a CAR file
 async fn import_chain<V: ProofVerifier, R: Read, DB>(
     sm: &Arc<StateManager<DB>>,
@@ -66,7 +65,7 @@ where
 
 /// Starts daemon process
 pub(super) async fn start(config: Config) {
-    info!("Starting Forest daemon");
+    info!("Starting Forest daemon on {}", config.chain);
     let net_keypair = get_keypair(&format!("{}{}", &config.data_dir, "/libp2p/keypair"))
         .unwrap_or_else(|| {
             // Keypair not found, generate and save generated keypair
@@ -93,19 +92,42 @@ pub(super) async fn start(config: Config) {
     let keystore = Arc::new(RwLock::new(ks));
 
     // Initialize database
-    let mut db = RocksDb::new(config.data_dir + "/db");
+    let mut db = RocksDb::new(config.data_dir.clone() + "/db");
     db.open().unwrap();
+    if config.enable_splitstore {
+        let mut cold = RocksDb::new(config.data_dir.clone() + "/cold");
+        cold.open().unwrap();
+        run_node(config, net_keypair, keystore, SplitStore::new(db, cold)).await;
+    } else {
+        run_node(config, net_keypair, keystore, db).await;
+    }
+
+    info!("Forest finish shutdown");
+}
+
+/// Runs the node services on top of the given database until ctrl-c is hit
+async fn run_node<DB>(
+    config: Config,
+    net_keypair: Keypair,
+    keystore: Arc<RwLock<PersistentKeyStore>>,
+    db: DB,
+) where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+{
     let db = Arc::new(db);
 
     // Initialize StateManager
-    let chain_store = Arc::new(ChainStore::new(Arc::clone(&db)));
-    let state_manager = Arc::new(StateManager::new(Arc::clone(&chain_store)));
+    let chain_store = Arc::new(
+        ChainStore::new(Arc::clone(&db)).with_fork_length_threshold(config.fork_length_threshold),
+    );
+    let state_manager =
+        Arc::new(StateManager::new(Arc::clone(&chain_store)).with_network_chain(config.chain));
 
     // Sync from snapshot
     if let Some(path) = &config.snapshot_path {
         let file = File::open(path).expect("Snapshot file path not found!");
         let reader = BufReader::new(file);
-        import_chain::<FullVerifier, _, _>(&state_manager, reader, false)
+        import_chain::<FullVerifier, _, _>(&state_manager, reader, config.snapshot)
             .await
             .unwrap();
     }
@@ -119,6 +141,16 @@ pub(super) async fn start(config: Config) {
         .await
         .unwrap();
 
+    // Libp2p service setup
+    let p2p_service = Libp2pService::new(
+        config.network,
+        Arc::clone(&chain_store),
+        net_keypair,
+        &network_name,
+    );
+    let network_rx = p2p_service.network_receiver();
+    let network_send = p2p_service.network_sender();
+
     // Initialize mpool
     let publisher = chain_store.publisher();
     let subscriber = publisher.write().await.subscribe();
@@ -127,17 +159,13 @@ pub(super) async fn start(config: Config) {
         MessagePool::new(
             provider,
             network_name.clone(),
+            network_send.clone(),
             MpoolConfig::load_config(db.as_ref()).unwrap(),
         )
         .await
         .unwrap(),
     );
 
-    // Libp2p service setup
-    let p2p_service = Libp2pService::new(config.network, chain_store, net_keypair, &network_name);
-    let network_rx = p2p_service.network_receiver();
-    let network_send = p2p_service.network_sender();
-
     // Get Drand Coefficients
     let coeff = config.drand_public;
 
@@ -145,16 +173,17 @@ pub(super) async fn start(config: Config) {
         DEFAULT_DRAND_URL,
         coeff,
         genesis.blocks()[0].timestamp(),
-        EPOCH_DURATION_SECONDS as u64,
+        config.chain.block_delay_secs(),
     )
     .await
     .unwrap();
+    let beacon = Arc::new(beacon);
 
     // Initialize ChainSyncer
     // TODO allow for configuring validation strategy (defaulting to full validation)
     let chain_syncer = ChainSyncer::<_, _, FullVerifier, _>::new(
         Arc::clone(&state_manager),
-        Arc::new(beacon),
+        Arc::clone(&beacon),
         Arc::clone(&mpool),
         network_send.clone(),
         network_rx,
@@ -163,16 +192,74 @@ pub(super) async fn start(config: Config) {
     .unwrap();
     let bad_blocks = chain_syncer.bad_blocks_cloned();
     let sync_state = chain_syncer.sync_state_cloned();
-    let sync_task = task::spawn(async {
-        chain_syncer.start(WORKER_TASKS).await;
+    let sync_workers = config.sync_workers;
+    let sync_task = task::spawn(async move {
+        chain_syncer.start(sync_workers).await;
     });
 
+    // Start blockstore garbage collection
+    let gc_task = if config.enable_gc {
+        let gc_sync_state = sync_state.clone();
+        let gc_mpool = Arc::clone(&mpool);
+        let gc =
+            ChainGc::<_, FullVerifier>::new(Arc::clone(&state_manager), config.gc_finality_count)
+                .with_sync_check(move || {
+                    let sync_state = gc_sync_state.clone();
+                    async move {
+                        for state in sync_state.read().await.iter() {
+                            if state.read().await.is_syncing() {
+                                return true;
+                            }
+                        }
+                        false
+                    }
+                })
+                .with_retained_cids(move || {
+                    let mpool = Arc::clone(&gc_mpool);
+                    async move { mpool.message_cids().await.map_err(|e| e.to_string()) }
+                });
+        let interval = Duration::from_secs(config.gc_interval);
+        Some(task::spawn(async move {
+            gc.run(interval).await;
+        }))
+    } else {
+        None
+    };
+
+    // Start block production
+    let miner_task = if let Some(addr) = &config.miner_address {
+        let address = Address::from_str(addr).expect("Invalid miner address");
+        let storage_path = config
+            .sector_storage_path
+            .clone()
+            .unwrap_or_else(|| config.data_dir.clone() + "/sectors");
+        let miner = Miner::new(
+            address,
+            Arc::clone(&state_manager),
+            Arc::clone(&beacon),
+            Arc::clone(&mpool),
+            LocalProver::new(storage_path),
+            Arc::clone(&keystore),
+            network_send.clone(),
+            network_name.clone(),
+        );
+        Some(task::spawn(async move {
+            miner.run::<FullVerifier>().await;
+        }))
+    } else {
+        None
+    };
+
     // Start services
     let p2p_task = task::spawn(async {
         p2p_service.run().await;
     });
     let rpc_task = if config.enable_rpc {
         let keystore_rpc = Arc::clone(&keystore);
+        let export_dir = config
+            .export_dir
+            .clone()
+            .unwrap_or_else(|| config.data_dir.clone() + "/exports");
         let rpc_listen = format!("127.0.0.1:{}", &config.rpc_port);
         Some(task::spawn(async move {
             info!("JSON RPC Endpoint at {}", &rpc_listen);
@@ -186,6 +273,7 @@ pub(super) async fn start(config: Config) {
                     network_send,
                     network_name,
                     events_pubsub: Arc::new(RwLock::new(Publisher::new(1000))),
+                    export_dir: export_dir.into(),
                 },
                 &rpc_listen,
             )
@@ -206,12 +294,16 @@ pub(super) async fn start(config: Config) {
     // Cancel all async services
     p2p_task.cancel().await;
     sync_task.cancel().await;
+    if let Some(task) = gc_task {
+        task.cancel().await;
+    }
+    if let Some(task) = miner_task {
+        task.cancel().await;
+    }
     if let Some(task) = rpc_task {
         task.cancel().await;
     }
     keystore_write.await;
-
-    info!("Forest finish shutdown");
 }
 
 #[cfg(test)]
@@ -243,4 +335,21 @@ mod test {
             .await
             .expect("Failed to import chain");
     }
+
+    #[async_std::test]
+    async fn export_and_import_snapshot() {
+        let db = Arc::new(MemoryDB::default());
+        let cs = ChainStore::new(db.clone());
+        let file = File::open("test_files/chain4.car").expect("Snapshot file path not found!");
+        let cids = load_car(db.as_ref(), BufReader::new(file)).unwrap();
+        let ts = cs.tipset_from_keys(&TipsetKeys::new(cids)).unwrap();
+        let snapshot = cs.export(&ts, ts.epoch(), false, Vec::new()).unwrap();
+
+        let db = Arc::new(MemoryDB::default());
+        let cs = Arc::new(ChainStore::new(db));
+        let sm = Arc::new(StateManager::new(cs));
+        import_chain::<FullVerifier, _, _>(&sm, snapshot.as_slice(), false)
+            .await
+            .expect("Failed to import exported snapshot");
+    }
 }
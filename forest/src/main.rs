@@ -7,6 +7,8 @@ mod logger;
 mod subcommand;
 
 use cli::CLI;
+use log::error;
+use std::process;
 use structopt::StructOpt;
 
 #[async_std::main]
@@ -18,7 +20,13 @@ async fn main() {
         CLI {
             daemon_opts,
             cmd: None,
-        } => daemon::start(daemon_opts.to_config().unwrap()).await,
+        } => match daemon_opts.to_config() {
+            Ok(config) => daemon::start(config).await,
+            Err(e) => {
+                error!("Invalid daemon configuration: {}", e);
+                process::exit(1);
+            }
+        },
         CLI {
             cmd: Some(command), ..
         } => subcommand::process(command).await,
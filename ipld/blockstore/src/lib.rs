@@ -5,12 +5,17 @@
 mod buffered;
 #[cfg(feature = "resolve")]
 pub mod resolve;
+#[cfg(feature = "splitstore")]
+mod split;
 #[cfg(feature = "tracking")]
 mod tracking;
 
 #[cfg(feature = "buffered")]
 pub use self::buffered::BufferedBlockStore;
 
+#[cfg(feature = "splitstore")]
+pub use self::split::SplitStore;
+
 #[cfg(feature = "tracking")]
 pub use self::tracking::{BSStats, TrackingBlockStore};
 
@@ -23,7 +28,7 @@ use encoding::{de::DeserializeOwned, from_slice, ser::Serialize, to_vec};
 use std::error::Error as StdError;
 
 #[cfg(feature = "rocksdb")]
-use db::{RocksDb, WriteBatch};
+use db::{IterableStore, RocksDb, WriteBatch};
 
 /// Wrapper for database to handle inserting and retrieving ipld data with Cids
 pub trait BlockStore: Store {
@@ -93,7 +98,9 @@ impl BlockStore for RocksDb {
             .map(|v| {
                 let bz = to_vec(v)?;
                 let cid = Cid::new_from_cbor(&bz, hash);
-                batch.put(cid.to_bytes(), bz);
+                let key = cid.to_bytes();
+                self.write_log().record(&key);
+                batch.put(key, bz);
                 Ok(cid)
             })
             .collect::<Result<_, Box<dyn StdError>>>()?;
@@ -0,0 +1,228 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+#![cfg(feature = "splitstore")]
+
+use super::BlockStore;
+use cid::{
+    multihash::{MultihashDigest, U32},
+    Cid,
+};
+use db::{Error, IterableStore, Store, WriteLog};
+use encoding::ser::Serialize;
+use std::error::Error as StdError;
+
+/// Blockstore which splits data between a small hot store, where all new data is written, and a
+/// cold store holding data which is no longer reachable from recent state.
+///
+/// Reads check the hot store first and fall back to the cold store. Evicting keys moves them from
+/// the hot store to the cold store, so garbage collection of a `SplitStore` keeps the hot store
+/// small without losing any data.
+#[derive(Debug)]
+pub struct SplitStore<H, C> {
+    hot: H,
+    cold: C,
+}
+
+impl<H, C> SplitStore<H, C>
+where
+    H: Store,
+    C: Store,
+{
+    /// Creates a split store writing to the `hot` store, with evicted data moved to `cold`.
+    pub fn new(hot: H, cold: C) -> Self {
+        Self { hot, cold }
+    }
+
+    /// Returns a reference to the hot store.
+    pub fn hot(&self) -> &H {
+        &self.hot
+    }
+
+    /// Returns a reference to the cold store.
+    pub fn cold(&self) -> &C {
+        &self.cold
+    }
+}
+
+impl<H, C> Store for SplitStore<H, C>
+where
+    H: Store,
+    C: Store,
+{
+    fn read<K>(&self, key: K) -> Result<Option<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        match self.hot.read(key.as_ref())? {
+            Some(v) => Ok(Some(v)),
+            None => self.cold.read(key),
+        }
+    }
+
+    fn write<K, V>(&self, key: K, value: V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.hot.write(key, value)
+    }
+
+    fn delete<K>(&self, key: K) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.hot.delete(key.as_ref())?;
+        self.cold.delete(key)
+    }
+
+    fn exists<K>(&self, key: K) -> Result<bool, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        Ok(self.hot.exists(key.as_ref())? || self.cold.exists(key)?)
+    }
+
+    fn bulk_write<K, V>(&self, values: &[(K, V)]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.hot.bulk_write(values)
+    }
+
+    fn bulk_delete<K>(&self, keys: &[K]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.hot.bulk_delete(keys)?;
+        self.cold.bulk_delete(keys)
+    }
+}
+
+impl<H, C> IterableStore for SplitStore<H, C>
+where
+    H: IterableStore,
+    C: Store,
+{
+    /// Iterates over the keys in the hot store only.
+    fn for_each_key<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        self.hot.for_each_key(f)
+    }
+
+    /// Iterates over the keys with the prefix in the hot store only.
+    fn for_each_key_with_prefix<F>(&self, prefix: &[u8], f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        self.hot.for_each_key_with_prefix(prefix, f)
+    }
+
+    /// Moves the keys from the hot store to the cold store.
+    fn evict<K>(&self, keys: &[K]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let values = self.hot.bulk_read(keys)?;
+        let moved: Vec<(&[u8], Vec<u8>)> = keys
+            .iter()
+            .zip(values)
+            .filter_map(|(k, v)| v.map(|v| (k.as_ref(), v)))
+            .collect();
+        // Write to the cold store before deleting, so data is never missing from both
+        self.cold.bulk_write(&moved)?;
+        self.hot.bulk_delete(keys)
+    }
+
+    fn evict_deletes(&self) -> bool {
+        false
+    }
+
+    /// Returns the write log of the hot store, which all writes go to.
+    fn write_log(&self) -> &WriteLog {
+        self.hot.write_log()
+    }
+}
+
+impl<H, C> BlockStore for SplitStore<H, C>
+where
+    H: BlockStore,
+    C: Store,
+{
+    fn put_raw<T>(&self, bytes: Vec<u8>, hash: T) -> Result<Cid, Box<dyn StdError>>
+    where
+        T: MultihashDigest<AllocSize = U32>,
+    {
+        self.hot.put_raw(bytes, hash)
+    }
+
+    fn bulk_put<'a, S, T, V>(&self, values: V, hash: T) -> Result<Vec<Cid>, Box<dyn StdError>>
+    where
+        S: Serialize + 'a,
+        T: MultihashDigest<AllocSize = U32>,
+        V: IntoIterator<Item = &'a S>,
+    {
+        self.hot.bulk_put(values, hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::Code::Blake2b256;
+    use db::MemoryDB;
+
+    #[test]
+    fn reads_fall_back_to_cold() {
+        let store = SplitStore::new(MemoryDB::default(), MemoryDB::default());
+        let hot_cid = store.put(&"hot", Blake2b256).unwrap();
+        store.cold().write(b"cold", b"value").unwrap();
+
+        assert!(store.hot().exists(hot_cid.to_bytes()).unwrap());
+        assert_eq!(
+            store.get::<String>(&hot_cid).unwrap(),
+            Some("hot".to_owned())
+        );
+        assert_eq!(store.read(b"cold").unwrap(), Some(b"value".to_vec()));
+        assert!(store.exists(b"cold").unwrap());
+
+        store.delete(b"cold").unwrap();
+        assert!(!store.exists(b"cold").unwrap());
+    }
+
+    #[test]
+    fn evict_moves_to_cold() {
+        let store = SplitStore::new(MemoryDB::default(), MemoryDB::default());
+        let evicted = store.put(&"evicted", Blake2b256).unwrap();
+        let kept = store.put(&"kept", Blake2b256).unwrap();
+
+        // Keys missing from the hot store are skipped
+        store
+            .evict(&[evicted.to_bytes(), b"missing".to_vec()])
+            .unwrap();
+
+        assert!(!store.hot().exists(evicted.to_bytes()).unwrap());
+        assert!(store.cold().exists(evicted.to_bytes()).unwrap());
+        assert!(!store.cold().exists(b"missing").unwrap());
+        assert_eq!(
+            store.get::<String>(&evicted).unwrap(),
+            Some("evicted".to_owned())
+        );
+
+        assert!(store.hot().exists(kept.to_bytes()).unwrap());
+        assert!(!store.cold().exists(kept.to_bytes()).unwrap());
+        assert!(!store.evict_deletes());
+
+        let mut hot_keys = Vec::new();
+        store
+            .for_each_key(|k| {
+                hot_keys.push(k.to_vec());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(hot_keys, vec![kept.to_bytes()]);
+    }
+}
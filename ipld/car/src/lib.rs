@@ -7,10 +7,10 @@ mod util;
 use blockstore::BlockStore;
 use cid::Cid;
 use error::*;
-use forest_encoding::from_slice;
+use forest_encoding::{from_slice, to_vec};
 use serde::{Deserialize, Serialize};
-use std::io::Read;
-use util::{ld_read, read_node};
+use std::io::{Read, Write};
+use util::{ld_read, ld_write, read_node};
 
 /// CAR file header
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -58,6 +58,43 @@ where
     }
 }
 
+/// Writes CAR files to a writer, one block at a time
+pub struct CarWriter<W> {
+    writer: W,
+}
+
+impl<W> CarWriter<W>
+where
+    W: Write,
+{
+    /// Creates a new CarWriter and writes the CarHeader
+    pub fn new(mut writer: W, header: &CarHeader) -> Result<Self, Error> {
+        if header.roots.is_empty() {
+            return Err(Error::InvalidFile("CAR file must have a root".to_owned()));
+        }
+        let bz = to_vec(header).map_err(|e| Error::ParsingError(e.to_string()))?;
+        ld_write(&mut writer, &[&bz])?;
+        Ok(CarWriter { writer })
+    }
+
+    /// Writes an IPLD Block to the underlying writer
+    pub fn write_block(&mut self, cid: &Cid, data: &[u8]) -> Result<(), Error> {
+        ld_write(&mut self.writer, &[&cid.to_bytes(), data])
+    }
+
+    /// Flushes the underlying writer
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer
+            .flush()
+            .map_err(|e| Error::Other(e.to_string()))
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
 /// IPLD Block
 #[derive(Clone, Debug)]
 pub struct Block {
@@ -65,6 +102,18 @@ pub struct Block {
     data: Vec<u8>,
 }
 
+impl Block {
+    /// Returns the Cid of the block
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// Returns the raw bytes of the block
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
 /// Loads a CAR buffer into a BlockStore
 pub fn load_car<R: Read, B: BlockStore>(s: &B, reader: R) -> Result<Vec<Cid>, Error> {
     let mut car_reader = CarReader::new(reader)?;
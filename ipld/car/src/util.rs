@@ -3,7 +3,7 @@
 
 use super::error::Error;
 use cid::Cid;
-use std::io::Read;
+use std::io::{Read, Write};
 use unsigned_varint::io::ReadError;
 
 pub(crate) fn ld_read<R: Read>(mut reader: &mut R) -> Result<Option<Vec<u8>>, Error> {
@@ -26,6 +26,21 @@ pub(crate) fn ld_read<R: Read>(mut reader: &mut R) -> Result<Option<Vec<u8>>, Er
     Ok(Some(buf))
 }
 
+pub(crate) fn ld_write<W: Write>(writer: &mut W, chunks: &[&[u8]]) -> Result<(), Error> {
+    let len: usize = chunks.iter().map(|c| c.len()).sum();
+    let mut buf = unsigned_varint::encode::u64_buffer();
+    let len_bz = unsigned_varint::encode::u64(len as u64, &mut buf);
+    writer
+        .write_all(len_bz)
+        .map_err(|e| Error::Other(e.to_string()))?;
+    for chunk in chunks {
+        writer
+            .write_all(chunk)
+            .map_err(|e| Error::Other(e.to_string()))?;
+    }
+    Ok(())
+}
+
 pub(crate) fn read_node<R: Read>(buf_reader: &mut R) -> Result<Option<(Cid, Vec<u8>)>, Error> {
     match ld_read(buf_reader)? {
         Some(buf) => {
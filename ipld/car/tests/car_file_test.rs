@@ -14,3 +14,29 @@ fn load_into_blockstore() {
 
     let _ = load_car(&mut bs, buf_reader).unwrap();
 }
+
+#[test]
+fn write_and_read_car() {
+    let file = File::open("tests/test.car").unwrap();
+    let mut reader = CarReader::new(BufReader::new(file)).unwrap();
+
+    let mut blocks = Vec::new();
+    while let Some(block) = reader.next_block().unwrap() {
+        blocks.push(block);
+    }
+
+    let mut writer = CarWriter::new(Vec::new(), &reader.header).unwrap();
+    for block in blocks.iter() {
+        writer.write_block(block.cid(), block.data()).unwrap();
+    }
+
+    let bytes = writer.into_inner();
+    let mut written = CarReader::new(bytes.as_slice()).unwrap();
+    assert_eq!(written.header.roots, reader.header.roots);
+    for block in blocks.iter() {
+        let read = written.next_block().unwrap().unwrap();
+        assert_eq!(read.cid(), block.cid());
+        assert_eq!(read.data(), block.data());
+    }
+    assert!(written.next_block().unwrap().is_none());
+}
@@ -4,9 +4,11 @@
 mod errors;
 mod memory;
 mod rocks;
+mod write_log;
 
 pub use errors::Error;
 pub use memory::MemoryDB;
+pub use write_log::WriteLog;
 
 #[cfg(feature = "rocksdb")]
 pub use rocks::{RocksDb, WriteBatch};
@@ -68,3 +70,46 @@ pub trait Store {
         keys.iter().map(|key| self.delete(key)).collect()
     }
 }
+
+/// Store interface for key-value stores which can iterate over all of their keys.
+pub trait IterableStore: Store {
+    /// Calls the given function with every key in the data store, in no particular order.
+    /// Keys written or deleted during iteration may or may not be visited.
+    fn for_each_key<F>(&self, f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>;
+
+    /// Calls the given function with every key in the data store which starts with the prefix,
+    /// in no particular order. By default all keys are iterated over and filtered, but stores
+    /// with sorted keys can only visit the keys with the prefix.
+    fn for_each_key_with_prefix<F>(&self, prefix: &[u8], mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        self.for_each_key(|key| {
+            if key.starts_with(prefix) {
+                f(key)
+            } else {
+                Ok(())
+            }
+        })
+    }
+
+    /// Evicts keys which are no longer needed from the data store. By default the keys are
+    /// deleted, but tiered stores can move them to a different tier instead.
+    fn evict<K>(&self, keys: &[K]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.bulk_delete(keys)
+    }
+
+    /// Returns whether evicted keys are deleted, rather than moved to a different tier where
+    /// they can still be read.
+    fn evict_deletes(&self) -> bool {
+        true
+    }
+
+    /// Returns the log of keys written to the store, which every write is recorded in.
+    fn write_log(&self) -> &WriteLog;
+}
@@ -1,32 +1,22 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::{DatabaseService, Error, Store};
+use super::{DatabaseService, Error, IterableStore, Store, WriteLog};
 use parking_lot::RwLock;
-use std::collections::{hash_map::DefaultHasher, HashMap};
-use std::hash::{Hash, Hasher};
+use std::collections::HashMap;
 
 /// A thread-safe `HashMap` wrapper.
 #[derive(Debug)]
 pub struct MemoryDB {
-    db: RwLock<HashMap<u64, Vec<u8>>>,
-}
-
-impl MemoryDB {
-    fn db_index<K>(key: K) -> u64
-    where
-        K: AsRef<[u8]>,
-    {
-        let mut hasher = DefaultHasher::new();
-        key.as_ref().hash::<DefaultHasher>(&mut hasher);
-        hasher.finish()
-    }
+    db: RwLock<HashMap<Vec<u8>, Vec<u8>>>,
+    written: WriteLog,
 }
 
 impl Clone for MemoryDB {
     fn clone(&self) -> Self {
         Self {
             db: RwLock::new(self.db.read().clone()),
+            written: WriteLog::default(),
         }
     }
 }
@@ -35,6 +25,7 @@ impl Default for MemoryDB {
     fn default() -> Self {
         Self {
             db: RwLock::new(HashMap::new()),
+            written: WriteLog::default(),
         }
     }
 }
@@ -47,9 +38,10 @@ impl Store for MemoryDB {
         K: AsRef<[u8]>,
         V: AsRef<[u8]>,
     {
+        self.written.record(&key);
         self.db
             .write()
-            .insert(Self::db_index(key), value.as_ref().to_vec());
+            .insert(key.as_ref().to_vec(), value.as_ref().to_vec());
         Ok(())
     }
 
@@ -57,7 +49,7 @@ impl Store for MemoryDB {
     where
         K: AsRef<[u8]>,
     {
-        self.db.write().remove(&Self::db_index(key));
+        self.db.write().remove(key.as_ref());
         Ok(())
     }
 
@@ -65,13 +57,31 @@ impl Store for MemoryDB {
     where
         K: AsRef<[u8]>,
     {
-        Ok(self.db.read().get(&Self::db_index(key)).cloned())
+        Ok(self.db.read().get(key.as_ref()).cloned())
     }
 
     fn exists<K>(&self, key: K) -> Result<bool, Error>
     where
         K: AsRef<[u8]>,
     {
-        Ok(self.db.read().contains_key(&Self::db_index(key)))
+        Ok(self.db.read().contains_key(key.as_ref()))
+    }
+}
+
+impl IterableStore for MemoryDB {
+    /// Iterates over a snapshot of the keys, so keys written during iteration are not visited.
+    fn for_each_key<F>(&self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        let keys: Vec<Vec<u8>> = self.db.read().keys().cloned().collect();
+        for key in keys {
+            f(&key)?;
+        }
+        Ok(())
+    }
+
+    fn write_log(&self) -> &WriteLog {
+        &self.written
     }
 }
@@ -4,7 +4,7 @@
 #![cfg(feature = "rocksdb")]
 
 use super::errors::Error;
-use super::{DatabaseService, Store};
+use super::{DatabaseService, IterableStore, Store, WriteLog};
 pub use rocksdb::{Options, WriteBatch, DB};
 use std::env::temp_dir;
 use std::path::{Path, PathBuf};
@@ -24,6 +24,7 @@ impl Default for DbStatus {
 #[derive(Debug, Default)]
 pub struct RocksDb {
     status: DbStatus,
+    written: WriteLog,
 }
 
 /// RocksDb is used as the KV store for Forest
@@ -42,6 +43,7 @@ impl RocksDb {
     {
         Self {
             status: DbStatus::Unopened(path.as_ref().to_path_buf()),
+            written: WriteLog::default(),
         }
     }
 
@@ -79,6 +81,7 @@ impl Store for RocksDb {
         K: AsRef<[u8]>,
         V: AsRef<[u8]>,
     {
+        self.written.record(&key);
         Ok(self.db()?.put(key, value)?)
     }
 
@@ -96,11 +99,23 @@ impl Store for RocksDb {
     {
         let mut batch = WriteBatch::default();
         for (k, v) in values {
+            self.written.record(k);
             batch.put(k, v);
         }
         Ok(self.db()?.write(batch)?)
     }
 
+    fn bulk_delete<K>(&self, keys: &[K]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut batch = WriteBatch::default();
+        for k in keys {
+            batch.delete(k);
+        }
+        Ok(self.db()?.write(batch)?)
+    }
+
     fn read<K>(&self, key: K) -> Result<Option<Vec<u8>>, Error>
     where
         K: AsRef<[u8]>,
@@ -118,3 +133,41 @@ impl Store for RocksDb {
             .map_err(Error::from)
     }
 }
+
+impl IterableStore for RocksDb {
+    fn for_each_key<F>(&self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        let mut iter = self.db()?.raw_iterator();
+        iter.seek_to_first();
+        while iter.valid() {
+            if let Some(key) = iter.key() {
+                f(key)?;
+            }
+            iter.next();
+        }
+        Ok(iter.status()?)
+    }
+
+    fn for_each_key_with_prefix<F>(&self, prefix: &[u8], mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&[u8]) -> Result<(), Error>,
+    {
+        // Keys are sorted, so the keys with the prefix directly follow the seek position
+        let mut iter = self.db()?.raw_iterator();
+        iter.seek(prefix);
+        while iter.valid() {
+            match iter.key() {
+                Some(key) if key.starts_with(prefix) => f(key)?,
+                _ => break,
+            }
+            iter.next();
+        }
+        Ok(iter.status()?)
+    }
+
+    fn write_log(&self) -> &WriteLog {
+        &self.written
+    }
+}
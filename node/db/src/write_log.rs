@@ -0,0 +1,48 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::Error;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+
+/// Log of the keys written to a store while recording, used to keep keys written during a
+/// garbage collection from being evicted.
+#[derive(Debug, Default)]
+pub struct WriteLog {
+    keys: Mutex<Option<HashSet<Vec<u8>>>>,
+}
+
+impl WriteLog {
+    /// Starts recording written keys, discarding any keys recorded before.
+    pub fn start(&self) {
+        *self.keys.lock() = Some(HashSet::new());
+    }
+
+    /// Stops recording written keys.
+    pub fn stop(&self) {
+        *self.keys.lock() = None;
+    }
+
+    /// Records the key as written if the log is recording.
+    pub fn record<K>(&self, key: K)
+    where
+        K: AsRef<[u8]>,
+    {
+        if let Some(keys) = self.keys.lock().as_mut() {
+            keys.insert(key.as_ref().to_vec());
+        }
+    }
+
+    /// Removes the keys which were written since recording started, then evicts the remaining
+    /// keys. Writes made while evicting wait for the eviction, so they are never lost.
+    pub fn evict_unwritten<F>(&self, keys: &mut Vec<Vec<u8>>, evict: F) -> Result<(), Error>
+    where
+        F: FnOnce(&[Vec<u8>]) -> Result<(), Error>,
+    {
+        let written = self.keys.lock();
+        if let Some(written) = written.as_ref() {
+            keys.retain(|key| !written.contains(key));
+        }
+        evict(keys)
+    }
+}
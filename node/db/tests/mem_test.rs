@@ -3,7 +3,7 @@
 
 mod subtests;
 
-use db::MemoryDB;
+use db::{IterableStore, MemoryDB, Store};
 
 #[test]
 fn mem_db_open() {
@@ -60,3 +60,22 @@ fn mem_db_bulk_delete() {
     let db = MemoryDB::default();
     subtests::bulk_delete(&db);
 }
+
+#[test]
+fn mem_db_for_each_key() {
+    let db = MemoryDB::default();
+    let keys = [[0], [1], [2]];
+    let kvs: Vec<_> = keys.iter().zip(keys.iter()).collect();
+    db.bulk_write(&kvs).unwrap();
+
+    // Keys can be deleted while iterating
+    let mut visited = Vec::new();
+    db.for_each_key(|k| {
+        visited.push(k.to_vec());
+        db.delete(k)
+    })
+    .unwrap();
+    visited.sort();
+    assert_eq!(visited, keys.iter().map(|k| k.to_vec()).collect::<Vec<_>>());
+    assert!(!db.exists([0]).unwrap());
+}
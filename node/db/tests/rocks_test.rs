@@ -6,7 +6,7 @@
 mod db_utils;
 mod subtests;
 
-use db::RocksDb;
+use db::{IterableStore, RocksDb, Store};
 use db_utils::DBPath;
 
 #[test]
@@ -81,3 +81,40 @@ fn rocks_db_bulk_delete() {
     subtests::open(&mut db);
     subtests::bulk_delete(&db);
 }
+
+#[test]
+fn rocks_db_for_each_key() {
+    let path = DBPath::new("for_each_key_rocks_test");
+    let mut db = RocksDb::new(path.as_ref());
+    subtests::open(&mut db);
+    let keys = [[0], [1], [2]];
+    let kvs: Vec<_> = keys.iter().zip(keys.iter()).collect();
+    db.bulk_write(&kvs).unwrap();
+
+    let mut visited = Vec::new();
+    db.for_each_key(|k| {
+        visited.push(k.to_vec());
+        Ok(())
+    })
+    .unwrap();
+    visited.sort();
+    assert_eq!(visited, keys.iter().map(|k| k.to_vec()).collect::<Vec<_>>());
+}
+
+#[test]
+fn rocks_db_for_each_key_with_prefix() {
+    let path = DBPath::new("for_each_key_with_prefix_rocks_test");
+    let mut db = RocksDb::new(path.as_ref());
+    subtests::open(&mut db);
+    let keys = ["a", "b/0", "b/1", "c"];
+    let kvs: Vec<_> = keys.iter().zip(keys.iter()).collect();
+    db.bulk_write(&kvs).unwrap();
+
+    let mut visited = Vec::new();
+    db.for_each_key_with_prefix(b"b/", |k| {
+        visited.push(k.to_vec());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(visited, vec![b"b/0".to_vec(), b"b/1".to_vec()]);
+}
@@ -7,10 +7,12 @@ mod behaviour;
 pub mod blocksync;
 mod config;
 pub mod hello;
+mod reputation;
 pub mod rpc;
 mod service;
 
 pub use self::behaviour::*;
 pub use self::blocksync::{BlockSyncRequest, MESSAGES};
 pub use self::config::*;
+pub use self::reputation::*;
 pub use self::service::*;
@@ -0,0 +1,179 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Misbehaviour of a peer, penalized by the `PeerReputation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Misbehaviour {
+    /// Peer sent a block which is invalid.
+    InvalidBlock,
+    /// Peer gossiped a block or message which could not be decoded.
+    UndecodableGossip,
+    /// Peer answered a blocksync request with malformed data.
+    InvalidBlockSyncResponse,
+}
+
+impl Misbehaviour {
+    /// Penalty added to the peer for the misbehaviour.
+    pub fn penalty(self) -> u32 {
+        match self {
+            Misbehaviour::InvalidBlock => 50,
+            Misbehaviour::UndecodableGossip => 10,
+            Misbehaviour::InvalidBlockSyncResponse => 20,
+        }
+    }
+}
+
+/// Tracks the penalties of misbehaving peers, banning peers once their penalties reach the ban
+/// threshold. Penalties are halved every half life, so that only repeated misbehaviour leads to
+/// a ban.
+pub struct PeerReputation {
+    /// Penalties accumulated by peers which aren't banned, with the time they were last decayed.
+    penalties: HashMap<PeerId, (u32, Instant)>,
+    /// Banned peers with the time their ban expires, or `None` for manual bans.
+    banned: HashMap<PeerId, Option<Instant>>,
+    ban_threshold: u32,
+    ban_duration: Duration,
+    penalty_half_life: Duration,
+}
+
+impl PeerReputation {
+    pub fn new(ban_threshold: u32, ban_duration: Duration, penalty_half_life: Duration) -> Self {
+        Self {
+            penalties: HashMap::new(),
+            banned: HashMap::new(),
+            ban_threshold,
+            ban_duration,
+            penalty_half_life,
+        }
+    }
+
+    /// Adds the penalty of the misbehaviour to the peer. Returns true if the peer reached the
+    /// ban threshold and was banned for the ban duration.
+    pub fn penalize(&mut self, peer_id: &PeerId, misbehaviour: Misbehaviour) -> bool {
+        if self.banned.contains_key(peer_id) {
+            return false;
+        }
+        let (penalty, _) = self
+            .penalties
+            .entry(peer_id.clone())
+            .or_insert_with(|| (0, Instant::now()));
+        *penalty = penalty.saturating_add(misbehaviour.penalty());
+        if *penalty < self.ban_threshold {
+            return false;
+        }
+        self.penalties.remove(peer_id);
+        self.banned
+            .insert(peer_id.clone(), Some(Instant::now() + self.ban_duration));
+        true
+    }
+
+    /// Bans the peer until it is manually unbanned. Returns false if the peer was already banned.
+    pub fn ban(&mut self, peer_id: &PeerId) -> bool {
+        self.penalties.remove(peer_id);
+        self.banned.insert(peer_id.clone(), None).is_none()
+    }
+
+    /// Returns true if the peer is banned.
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.banned.contains_key(peer_id)
+    }
+
+    /// Returns the peers currently banned.
+    pub fn banned_peers(&self) -> Vec<PeerId> {
+        self.banned.keys().cloned().collect()
+    }
+
+    /// Lifts the bans which expired before `now`, returning the peers unbanned.
+    pub fn expire_bans(&mut self, now: Instant) -> Vec<PeerId> {
+        let expired: Vec<PeerId> = self
+            .banned
+            .iter()
+            .filter(|(_, until)| until.map_or(false, |until| until <= now))
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        for peer_id in &expired {
+            self.banned.remove(peer_id);
+        }
+        expired
+    }
+
+    /// Halves the penalties of peers for every half life elapsed until `now`, forgetting the
+    /// peers without penalties left.
+    pub fn decay_penalties(&mut self, now: Instant) {
+        let half_life = self.penalty_half_life;
+        self.penalties.retain(|_, (penalty, decayed)| {
+            while *penalty > 0 && now.saturating_duration_since(*decayed) >= half_life {
+                *penalty /= 2;
+                *decayed += half_life;
+            }
+            *penalty > 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ban_after_threshold() {
+        let mut rep = PeerReputation::new(60, Duration::from_secs(10), Duration::from_secs(60));
+        let peer = PeerId::random();
+
+        assert!(!rep.penalize(&peer, Misbehaviour::InvalidBlockSyncResponse));
+        assert!(!rep.penalize(&peer, Misbehaviour::InvalidBlockSyncResponse));
+        assert!(!rep.is_banned(&peer));
+        assert!(rep.penalize(&peer, Misbehaviour::InvalidBlockSyncResponse));
+        assert!(rep.is_banned(&peer));
+        assert!(!rep.penalize(&peer, Misbehaviour::InvalidBlock));
+
+        let now = Instant::now();
+        assert!(rep.expire_bans(now).is_empty());
+        assert_eq!(
+            rep.expire_bans(now + Duration::from_secs(11)),
+            vec![peer.clone()]
+        );
+        assert!(!rep.is_banned(&peer));
+
+        // Penalties start over after a ban.
+        assert!(!rep.penalize(&peer, Misbehaviour::InvalidBlock));
+    }
+
+    #[test]
+    fn manual_ban_never_expires() {
+        let mut rep = PeerReputation::new(60, Duration::from_secs(10), Duration::from_secs(60));
+        let peer = PeerId::random();
+
+        assert!(rep.ban(&peer));
+        assert!(!rep.ban(&peer));
+        assert_eq!(rep.banned_peers(), vec![peer.clone()]);
+        assert!(rep
+            .expire_bans(Instant::now() + Duration::from_secs(3600))
+            .is_empty());
+        assert!(rep.is_banned(&peer));
+    }
+
+    #[test]
+    fn penalties_decay() {
+        let mut rep = PeerReputation::new(60, Duration::from_secs(10), Duration::from_secs(60));
+        let peer = PeerId::random();
+
+        assert!(!rep.penalize(&peer, Misbehaviour::InvalidBlock));
+        let now = Instant::now();
+        rep.decay_penalties(now);
+        assert_eq!(rep.penalties[&peer].0, 50);
+
+        // Half of the penalty is forgiven after a half life.
+        rep.decay_penalties(now + Duration::from_secs(61));
+        assert_eq!(rep.penalties[&peer].0, 25);
+        assert!(!rep.penalize(&peer, Misbehaviour::InvalidBlockSyncResponse));
+
+        // Peers are forgotten once their penalties decayed entirely.
+        rep.decay_penalties(now + Duration::from_secs(3600));
+        assert!(!rep.penalties.contains_key(&peer));
+    }
+}
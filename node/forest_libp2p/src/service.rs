@@ -3,7 +3,7 @@
 
 use super::blocksync::{make_blocksync_response, BlockSyncRequest, BlockSyncResponse};
 use super::rpc::RPCRequest;
-use super::{ForestBehaviour, ForestBehaviourEvent, Libp2pConfig};
+use super::{ForestBehaviour, ForestBehaviourEvent, Libp2pConfig, Misbehaviour, PeerReputation};
 use crate::hello::{HelloRequest, HelloResponse};
 use async_std::sync::{channel, Receiver, Sender};
 use async_std::{stream, task};
@@ -29,7 +29,7 @@ use log::{debug, error, info, trace, warn};
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use utils::read_file_to_vec;
 
 pub const PUBSUB_BLOCK_STR: &str = "/fil/blocks";
@@ -66,6 +66,9 @@ pub enum NetworkEvent {
     BitswapBlock {
         cid: Cid,
     },
+    PeerBanned {
+        peer_id: PeerId,
+    },
 }
 
 /// Message types that can come over GossipSub
@@ -97,6 +100,19 @@ pub enum NetworkMessage {
         cid: Cid,
         response_channel: OneShotSender<()>,
     },
+    /// Penalizes a peer for misbehaving, banning it once its penalties reach the threshold.
+    ReportPeer {
+        peer_id: PeerId,
+        misbehaviour: Misbehaviour,
+    },
+    /// Bans a peer until the node is restarted.
+    BanPeer {
+        peer_id: PeerId,
+    },
+    /// Requests the peers currently banned.
+    BannedPeers {
+        response_channel: OneShotSender<Vec<PeerId>>,
+    },
 }
 /// The Libp2pService listens to events from the Libp2p swarm.
 pub struct Libp2pService<DB> {
@@ -110,6 +126,7 @@ pub struct Libp2pService<DB> {
     network_sender_out: Sender<NetworkEvent>,
     network_name: String,
     bitswap_response_channels: HashMap<Cid, Vec<OneShotSender<()>>>,
+    reputation: PeerReputation,
 }
 
 impl<DB> Libp2pService<DB>
@@ -127,6 +144,12 @@ where
 
         let transport = build_transport(net_keypair.clone());
 
+        let reputation = PeerReputation::new(
+            config.ban_threshold,
+            Duration::from_secs(config.ban_duration),
+            Duration::from_secs(config.penalty_half_life),
+        );
+
         let mut swarm = {
             let be = ForestBehaviour::new(&net_keypair, &config, network_name);
             Swarm::new(transport, be, peer_id)
@@ -158,6 +181,7 @@ where
             network_sender_out,
             network_name: network_name.to_owned(),
             bitswap_response_channels: Default::default(),
+            reputation,
         }
     }
 
@@ -204,7 +228,12 @@ where
                                             message: PubsubMessage::Block(b),
                                         }).await;
                                     }
-                                    Err(e) => warn!("Gossip Block from peer {:?} could not be deserialized: {}", source, e)
+                                    Err(e) => {
+                                        warn!("Gossip Block from peer {:?} could not be deserialized: {}", source, e);
+                                        if let Some(peer_id) = &source {
+                                            report_peer(swarm_stream.get_mut(), &mut self.reputation, &self.network_sender_out, peer_id, Misbehaviour::UndecodableGossip).await;
+                                        }
+                                    }
                                 }
                             } else if topic == pubsub_msg_str {
                                 match from_slice::<SignedMessage>(&message) {
@@ -214,7 +243,12 @@ where
                                             message: PubsubMessage::Message(m),
                                         }).await;
                                     }
-                                    Err(e) => warn!("Gossip Message from peer {:?} could not be deserialized: {}", source, e)
+                                    Err(e) => {
+                                        warn!("Gossip Message from peer {:?} could not be deserialized: {}", source, e);
+                                        if let Some(peer_id) = &source {
+                                            report_peer(swarm_stream.get_mut(), &mut self.reputation, &self.network_sender_out, peer_id, Misbehaviour::UndecodableGossip).await;
+                                        }
+                                    }
                                 }
                             } else {
                                 warn!("Getting gossip messages from unknown topic: {}", topic);
@@ -321,11 +355,31 @@ where
                                     self.bitswap_response_channels.insert(cid, vec![response_channel]);
                                 }
                         }
+                        NetworkMessage::ReportPeer { peer_id, misbehaviour } => {
+                            report_peer(swarm_stream.get_mut(), &mut self.reputation, &self.network_sender_out, &peer_id, misbehaviour).await;
+                        }
+                        NetworkMessage::BanPeer { peer_id } => {
+                            if self.reputation.ban(&peer_id) {
+                                info!("Banning peer {}", peer_id);
+                                ban_peer(swarm_stream.get_mut(), &self.network_sender_out, peer_id).await;
+                            }
+                        }
+                        NetworkMessage::BannedPeers { response_channel } => {
+                            if response_channel.send(self.reputation.banned_peers()).is_err() {
+                                debug!("Banned peers response channel send failed");
+                            }
+                        }
                     }
                     None => { break; }
                 },
                 interval_event = interval.next() => if interval_event.is_some() {
                     info!("Peers connected: {}", swarm_stream.get_ref().peers().len());
+                    let now = Instant::now();
+                    for peer_id in self.reputation.expire_bans(now) {
+                        debug!("Ban of peer {} expired", peer_id);
+                        Swarm::unban_peer_id(swarm_stream.get_mut(), peer_id);
+                    }
+                    self.reputation.decay_penalties(now);
                 }
             };
         }
@@ -341,6 +395,35 @@ where
         self.network_receiver_out.clone()
     }
 }
+
+/// Penalizes a peer for misbehaving, and bans it if it reached the ban threshold.
+async fn report_peer(
+    swarm: &mut Swarm<ForestBehaviour>,
+    reputation: &mut PeerReputation,
+    sender: &Sender<NetworkEvent>,
+    peer_id: &PeerId,
+    misbehaviour: Misbehaviour,
+) {
+    debug!("Peer {} misbehaved: {:?}", peer_id, misbehaviour);
+    if reputation.penalize(peer_id, misbehaviour) {
+        info!("Banning misbehaving peer {}", peer_id);
+        ban_peer(swarm, sender, peer_id.clone()).await;
+    }
+}
+
+/// Disconnects the peer and refuses its connections until it is unbanned. The ban event is
+/// never dropped, even if the event channel is full, so that the peer is removed from the peer
+/// manager.
+async fn ban_peer(
+    swarm: &mut Swarm<ForestBehaviour>,
+    sender: &Sender<NetworkEvent>,
+    peer_id: PeerId,
+) {
+    swarm.remove_peer(&peer_id);
+    Swarm::ban_peer_id(swarm, peer_id.clone());
+    sender.send(NetworkEvent::PeerBanned { peer_id }).await;
+}
+
 async fn emit_event(sender: &Sender<NetworkEvent>, event: NetworkEvent) {
     if !sender.is_full() {
         sender.send(event).await
@@ -2,8 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::client::Filecoin;
-use blocks::{header::json::BlockHeaderJson, tipset_json::TipsetJson};
+use blocks::{header::json::BlockHeaderJson, tipset_json::TipsetJson, TipsetKeys};
 use cid::{json::CidJson, Cid};
+use clock::ChainEpoch;
 use jsonrpc_v2::Error as JsonRpcError;
 use jsonrpsee::raw::RawClient;
 use jsonrpsee::transport::http::HttpTransportClient as HTC;
@@ -14,6 +15,18 @@ pub async fn block(client: &mut RawClient<HTC>, cid: Cid) -> Result<BlockHeaderJ
     Ok(Filecoin::chain_get_block(client, CidJson(cid)).await?)
 }
 
+/// Exports the chain from the given tipset to a snapshot CAR file in the daemon's export
+/// directory via RPC, returning the path of the snapshot
+pub async fn export(
+    client: &mut RawClient<HTC>,
+    tsk: TipsetKeys,
+    recent_roots: ChainEpoch,
+    skip_old_msgs: bool,
+    file_name: String,
+) -> Result<String, JsonRpcError> {
+    Ok(Filecoin::chain_export(client, recent_roots, skip_old_msgs, file_name, tsk).await?)
+}
+
 /// Returns genesis tipset from chain via RPC
 pub async fn genesis(client: &mut RawClient<HTC>) -> Result<TipsetJson, JsonRpcError> {
     Ok(Filecoin::chain_get_genesis(client).await?)
@@ -4,8 +4,9 @@
 #![allow(clippy::all)]
 #![allow(unused_variables, dead_code)]
 
-use blocks::{header::json::BlockHeaderJson, tipset_json::TipsetJson};
+use blocks::{header::json::BlockHeaderJson, tipset_json::TipsetJson, TipsetKeys};
 use cid::json::CidJson;
+use clock::ChainEpoch;
 use jsonrpsee::raw::RawClient;
 use jsonrpsee::transport::http::HttpTransportClient;
 use message::unsigned_message::json::UnsignedMessageJson;
@@ -19,6 +20,9 @@ jsonrpsee::rpc_api! {
         #[rpc(method = "Filecoin.ChainGetBlock", positional_params)]
         fn chain_get_block(cid: CidJson) -> BlockHeaderJson;
 
+        #[rpc(method = "Filecoin.ChainExport", positional_params)]
+        fn chain_export(recent_roots: ChainEpoch, skip_old_msgs: bool, file_name: String, tsk: TipsetKeys) -> String;
+
         #[rpc(method = "Filecoin.ChainGetGenesis")]
         fn chain_get_genesis() -> TipsetJson;
 
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::RpcState;
+use async_std::task;
 use blocks::{
     header::json::BlockHeaderJson, tipset_json::TipsetJson, BlockHeader, Tipset, TipsetKeys,
 };
@@ -18,6 +19,10 @@ use message::{
 };
 use num_traits::FromPrimitive;
 use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::BufWriter;
+use std::path::{Component, Path};
+use std::sync::Arc;
 use wallet::KeyStore;
 
 #[derive(Serialize, Deserialize)]
@@ -75,6 +80,59 @@ where
     Ok(index)
 }
 
+/// Exports the chain from the given tipset to a snapshot CAR file with the given name in the
+/// daemon's export directory, and returns the path of the snapshot. An existing file is never
+/// overwritten, and the file is removed if the export fails.
+pub(crate) async fn chain_export<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(ChainEpoch, bool, String, TipsetKeys)>,
+) -> Result<String, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (recent_roots, skip_old_msgs, file_name, tsk) = params;
+    let mut components = Path::new(&file_name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err("output must be a file name within the export directory".into());
+    }
+    let chain_store = data.state_manager.chain_store().clone();
+    let ts = if tsk.cids().is_empty() {
+        chain_store
+            .heaviest_tipset()
+            .await
+            .ok_or("can't find heaviest tipset")?
+    } else {
+        Arc::new(chain_store.tipset_from_keys(&tsk)?)
+    };
+    if recent_roots < 0 {
+        return Err("recent state roots must be non-negative".into());
+    }
+
+    fs::create_dir_all(&data.export_dir)?;
+    let output_path = data.export_dir.join(file_name);
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&output_path)?;
+    // Export is streamed directly to the output file, as the snapshot does not fit in memory
+    let res = task::spawn_blocking(move || {
+        chain_store
+            .export(&ts, recent_roots, skip_old_msgs, BufWriter::new(file))
+            .map(|_| ())
+    })
+    .await;
+    if let Err(e) = res {
+        fs::remove_file(&output_path)?;
+        return Err(e.into());
+    }
+
+    Ok(output_path.to_string_lossy().into_owned())
+}
+
 pub(crate) async fn chain_read_obj<DB, KS>(
     data: Data<RpcState<DB, KS>>,
     Params(params): Params<(CidJson,)>,
@@ -1,16 +1,24 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use fil_types::build_version::{
-    user_version, APIVersion, Version, BLOCK_DELAY_SECS, RUNNING_NODE_TYPE,
-};
-use jsonrpc_v2::Error as JsonRpcError;
+use crate::RpcState;
+use blockstore::BlockStore;
+use fil_types::build_version::{user_version, APIVersion, Version, RUNNING_NODE_TYPE};
+use jsonrpc_v2::{Data, Error as JsonRpcError};
 use std::convert::TryInto;
-pub(crate) async fn version() -> Result<APIVersion, JsonRpcError> {
+use wallet::KeyStore;
+
+pub(crate) async fn version<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+) -> Result<APIVersion, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
     let v: Version = (&*RUNNING_NODE_TYPE.read().await).try_into()?;
     Ok(APIVersion {
         version: user_version().await,
         api_version: v,
-        block_delay: BLOCK_DELAY_SECS,
+        block_delay: data.state_manager.network_chain().block_delay_secs(),
     })
 }
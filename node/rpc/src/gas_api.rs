@@ -5,17 +5,32 @@ use crate::RpcState;
 use address::Address;
 use blocks::TipsetKeys;
 use blockstore::BlockStore;
-use chain::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE};
+use chain::MINIMUM_BASE_FEE;
+use db::IterableStore;
 use fil_types::{verifier::FullVerifier, BLOCK_GAS_LIMIT};
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use message::unsigned_message::json::UnsignedMessageJson;
-use message::{ChainMessage, Message};
-use num_bigint::BigInt;
-use num_traits::{FromPrimitive, Zero};
-use rand_distr::{Distribution, Normal};
+use message::{ChainMessage, Message, UnsignedMessage};
+use message_pool::{cap_gas_fee, DEFAULT_MAX_FEE};
+use num_bigint::{bigint_ser, BigInt};
+use num_traits::Zero;
+use serde::Deserialize;
+use std::sync::Arc;
 use wallet::KeyStore;
-const MIN_GAS_PREMIUM: f64 = 100000.0;
-const MAX_SPEND_ON_FEE_DENOM: i64 = 100;
+
+/// Number of epochs a message is estimated to be included within when estimating its premium.
+const ESTIMATE_NBLOCKSINCL: u64 = 10;
+/// Number of epochs the base fee is allowed to increase for when estimating a fee cap.
+const ESTIMATE_MAX_QUEUE_BLKS: i64 = 20;
+
+/// Options for estimating the gas of, and sending, a message.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct MessageSendSpec {
+    /// Maximum total fee paid for the message, zero for the default maximum.
+    #[serde(default, with = "bigint_ser::json")]
+    pub max_fee: BigInt,
+}
 
 /// Estimate the fee cap
 pub(crate) async fn gas_estimate_fee_cap<DB, KS>(
@@ -23,163 +38,134 @@ pub(crate) async fn gas_estimate_fee_cap<DB, KS>(
     Params(params): Params<(UnsignedMessageJson, i64, TipsetKeys)>,
 ) -> Result<String, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
-    let (UnsignedMessageJson(msg), max_queue_blks, _tsk) = params;
-
-    let ts = data
-        .state_manager
-        .chain_store()
-        .heaviest_tipset()
-        .await
-        .ok_or("can't find heaviest tipset")?;
-
-    let act = data
-        .state_manager
-        .get_actor(msg.from(), ts.parent_state())?
-        .ok_or("could not load actor")?;
-
-    let parent_base_fee = ts.blocks()[0].parent_base_fee();
-    let increase_factor =
-        (1.0 + (BASE_FEE_MAX_CHANGE_DENOM as f64).recip()).powf(max_queue_blks as f64);
-
-    let fee_in_future = parent_base_fee
-        * BigInt::from_f64(increase_factor * (1 << 8) as f64)
-            .ok_or("failed to convert fee_in_future f64 to bigint")?;
-    let fee_in_future = fee_in_future / (1 << 8);
+    let (UnsignedMessageJson(msg), max_queue_blks, tsk) = params;
 
-    let gas_limit_big: BigInt = msg.gas_limit().into();
-    let max_accepted = act.balance / MAX_SPEND_ON_FEE_DENOM;
-    let expected_fee = &fee_in_future * &gas_limit_big;
-
-    let out = if expected_fee > max_accepted {
-        max_accepted / gas_limit_big
-    } else {
-        fee_in_future
-    };
-    Ok(out.to_string())
+    let fee_cap = data
+        .mpool
+        .estimate_fee_cap(msg.gas_premium(), max_queue_blks, tsk)
+        .await?;
+    Ok(fee_cap.to_string())
 }
 
-/// Estimate the fee cap
+/// Estimate the gas premium
 pub(crate) async fn gas_estimate_gas_premium<DB, KS>(
     data: Data<RpcState<DB, KS>>,
     Params(params): Params<(u64, Address, i64, TipsetKeys)>,
 ) -> Result<String, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
-    let (mut nblocksincl, _sender, _gas_limit, _) = params;
+    let (nblocksincl, sender, gas_limit, tsk) = params;
 
-    if nblocksincl == 0 {
-        nblocksincl = 1;
-    }
+    let premium = data
+        .mpool
+        .estimate_gas_premium(nblocksincl, sender, gas_limit, tsk)
+        .await?;
+    Ok(premium.to_string())
+}
 
-    struct GasMeta {
-        pub price: BigInt,
-        pub limit: i64,
-    }
+/// Estimate the gas limit
+pub(crate) async fn gas_estimate_gas_limit<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(UnsignedMessageJson, TipsetKeys)>,
+) -> Result<i64, JsonRpcError>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (UnsignedMessageJson(msg), tsk) = params;
+    estimate_gas_limit(&data, msg, &tsk).await
+}
 
-    let mut prices: Vec<GasMeta> = Vec::new();
-    let mut blocks = 0;
+/// Estimate the gas limit, premium and fee cap of a message, for the fields which are not set
+pub(crate) async fn gas_estimate_message_gas<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(UnsignedMessageJson, Option<MessageSendSpec>, TipsetKeys)>,
+) -> Result<UnsignedMessageJson, JsonRpcError>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (UnsignedMessageJson(msg), spec, tsk) = params;
+    let msg = estimate_message_gas(&data, msg, spec.unwrap_or_default(), &tsk).await?;
+    Ok(UnsignedMessageJson(msg))
+}
 
-    let mut ts = data
-        .state_manager
-        .chain_store()
-        .heaviest_tipset()
-        .await
-        .ok_or("cant get heaviest tipset")?
-        .as_ref()
-        .clone();
-
-    for _ in 0..(nblocksincl * 2) {
-        if ts.parents().cids().is_empty() {
-            break;
-        }
-        let pts = data
-            .state_manager
-            .chain_store()
-            .tipset_from_keys(ts.parents())?;
-        blocks += pts.blocks().len();
-        let msgs = chain::messages_for_tipset(data.state_manager.blockstore(), &pts)?;
-
-        prices.append(
-            &mut msgs
-                .iter()
-                .map(|msg| GasMeta {
-                    price: msg.gas_premium().clone(),
-                    limit: msg.gas_limit(),
-                })
-                .collect(),
-        );
-        ts = pts;
+/// Fills in the gas limit, premium and fee cap of a message which are not set, and caps the total
+/// fee of the message to the maximum fee of the send spec. Estimates are made on the given
+/// tipset, or on the current tipset of the message pool if the keys are empty.
+pub(crate) async fn estimate_message_gas<DB, KS>(
+    data: &Data<RpcState<DB, KS>>,
+    mut msg: UnsignedMessage,
+    spec: MessageSendSpec,
+    tsk: &TipsetKeys,
+) -> Result<UnsignedMessage, JsonRpcError>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    if msg.gas_limit() == 0 {
+        let gas_limit = estimate_gas_limit(data, msg.clone(), tsk).await?;
+        let overestimation = data.mpool.get_config().await.gas_limit_overestimation();
+        msg.set_gas_limit((gas_limit as f64 * overestimation) as i64);
     }
-
-    prices.sort_by(|a, b| b.price.cmp(&a.price));
-    // TODO: From lotus, account for how full blocks are
-    let mut at = BLOCK_GAS_TARGET * blocks as i64 / 2;
-    let mut prev = BigInt::zero();
-    let mut premium = BigInt::zero();
-
-    for price in prices {
-        at -= price.limit;
-        if at > 0 {
-            prev = price.price;
-            continue;
-        }
-        if prev == 0.into() {
-            let ret: BigInt = price.price + 1;
-            return Ok(ret.to_string());
-        }
-        premium = (&price.price + &prev) / 2 + 1
+    if msg.gas_premium().is_zero() {
+        let premium = data
+            .mpool
+            .estimate_gas_premium(
+                ESTIMATE_NBLOCKSINCL,
+                *msg.from(),
+                msg.gas_limit(),
+                tsk.clone(),
+            )
+            .await?;
+        msg.set_gas_premium(premium);
     }
-
-    if premium == 0.into() {
-        premium = BigInt::from_f64(match nblocksincl {
-            1 => MIN_GAS_PREMIUM * 2.0,
-            2 => MIN_GAS_PREMIUM * 1.5,
-            _ => MIN_GAS_PREMIUM,
-        })
-        .ok_or("failed to convert gas premium f64 to bigint")?;
+    if msg.gas_fee_cap().is_zero() {
+        let fee_cap = data
+            .mpool
+            .estimate_fee_cap(msg.gas_premium(), ESTIMATE_MAX_QUEUE_BLKS, tsk.clone())
+            .await?;
+        msg.set_gas_fee_cap(fee_cap);
     }
 
-    let precision = 32;
-
-    // mean 1, stddev 0.005 => 95% within +-1%
-    let noise: f64 = Normal::new(1.0, 0.005)
-        .unwrap()
-        .sample(&mut rand::thread_rng());
-    premium *= BigInt::from_f64(noise * (1 << precision) as f64)
-        .ok_or("failed to converrt gas premium f64 to bigint")?;
-    premium /= 1 << precision;
-
-    Ok(premium.to_string())
+    let max_fee = if spec.max_fee.is_zero() {
+        BigInt::from(DEFAULT_MAX_FEE)
+    } else {
+        spec.max_fee
+    };
+    cap_gas_fee(&mut msg, &max_fee);
+    Ok(msg)
 }
 
-/// Estimate the gas limit
-pub(crate) async fn gas_estimate_gas_limit<DB, KS>(
-    data: Data<RpcState<DB, KS>>,
-    Params(params): Params<(UnsignedMessageJson, TipsetKeys)>,
+/// Estimates the gas used by a message, by applying it on top of the pending messages of its
+/// sender, on the given tipset or on the current tipset of the message pool if the keys are
+/// empty. Fails with the exit code and error if the message fails to execute.
+async fn estimate_gas_limit<DB, KS>(
+    data: &Data<RpcState<DB, KS>>,
+    mut msg: UnsignedMessage,
+    tsk: &TipsetKeys,
 ) -> Result<i64, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
-    let (UnsignedMessageJson(mut msg), _) = params;
     msg.set_gas_limit(BLOCK_GAS_LIMIT);
     msg.set_gas_fee_cap(MINIMUM_BASE_FEE.clone() + 1);
     msg.set_gas_premium(1.into());
 
-    let curr_ts = data
-        .state_manager
-        .chain_store()
-        .heaviest_tipset()
-        .await
-        .ok_or("cant find the current heaviest tipset")?;
+    let ts = if tsk.cids().is_empty() {
+        data.mpool.cur_tipset.as_ref().read().await.clone()
+    } else {
+        Arc::new(data.state_manager.chain_store().tipset_from_keys(tsk)?)
+    };
     let from_a = data
         .state_manager
-        .resolve_to_key_addr::<FullVerifier>(msg.from(), &curr_ts)
+        .resolve_to_key_addr::<FullVerifier>(msg.from(), &ts)
         .await?;
 
     let pending = data.mpool.pending_for(&from_a).await;
@@ -188,19 +174,32 @@ where
         .unwrap_or_default();
     let res = data
         .state_manager
-        .call_with_gas::<FullVerifier>(
-            &mut ChainMessage::Unsigned(msg),
-            &prior_messages,
-            Some(data.mpool.cur_tipset.as_ref().read().await.clone()),
-        )
+        .call_with_gas::<FullVerifier>(&mut ChainMessage::Unsigned(msg), &prior_messages, Some(ts))
         .await?;
-    match res.msg_rct {
-        Some(rct) => {
-            if rct.exit_code as u64 != 0 {
-                return Ok(-1);
-            }
-            Ok(rct.gas_used)
-        }
-        None => Ok(-1),
+    let rct = res
+        .msg_rct
+        .ok_or("message execution failed: no receipt returned")?;
+    if !rct.exit_code.is_success() {
+        return Err(format!(
+            "message execution failed: exit {:?}, reason: {}",
+            rct.exit_code,
+            res.error.unwrap_or_default()
+        )
+        .into());
+    }
+    Ok(rct.gas_used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_send_spec_default_max_fee() {
+        let spec: MessageSendSpec = serde_json::from_str("{}").unwrap();
+        assert!(spec.max_fee.is_zero());
+
+        let spec: MessageSendSpec = serde_json::from_str(r#"{"MaxFee":"1000"}"#).unwrap();
+        assert_eq!(spec.max_fee, BigInt::from(1000));
     }
 }
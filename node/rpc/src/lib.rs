@@ -6,6 +6,7 @@ mod chain_api;
 mod common_api;
 mod gas_api;
 mod mpool_api;
+mod net_api;
 mod state_api;
 mod sync_api;
 mod wallet_api;
@@ -22,6 +23,7 @@ use auth::{has_perms, Error as AuthError, JWT_IDENTIFIER, WRITE_ACCESS};
 use blockstore::BlockStore;
 use chain::{headchange_json::HeadChangeJson, EventsPayload};
 use chain_sync::{BadBlockCache, SyncState};
+use db::IterableStore;
 use flo_stream::{MessagePublisher, Publisher, Subscriber};
 use forest_libp2p::NetworkMessage;
 use futures::future;
@@ -36,6 +38,7 @@ use log::{debug, error, info, warn};
 use message_pool::{MessagePool, MpoolRpcProvider};
 use serde::Serialize;
 use state_manager::StateManager;
+use std::path::PathBuf;
 use utils::get_home_dir;
 use wallet::KeyStore;
 use wallet::PersistentKeyStore;
@@ -64,17 +67,20 @@ where
     pub sync_state: Arc<RwLock<Vec<Arc<RwLock<SyncState>>>>>,
     pub network_send: Sender<NetworkMessage>,
     pub network_name: String,
+    /// Directory chain snapshots are exported to.
+    pub export_dir: PathBuf,
 }
 
 pub async fn start_rpc<DB, KS>(state: RpcState<DB, KS>, rpc_endpoint: &str)
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
     use auth_api::*;
     use chain_api::*;
     use gas_api::*;
     use mpool_api::*;
+    use net_api::*;
     use sync_api::*;
     use wallet_api::*;
     let events_pubsub = state.events_pubsub.clone();
@@ -89,6 +95,7 @@ where
             chain_api::chain_get_message::<DB, KS>,
             false,
         )
+        .with_method("Filecoin.ChainExport", chain_export::<DB, KS>, false)
         .with_method("Filecoin.ChainGetObj", chain_read_obj::<DB, KS>, false)
         .with_method("Filecoin.ChainHasObj", chain_has_obj::<DB, KS>, false)
         .with_method(
@@ -137,6 +144,13 @@ where
         )
         .with_method("Filecoin.MpoolPending", mpool_pending::<DB, KS>, false)
         .with_method("Filecoin.MpoolPush", mpool_push::<DB, KS>, false)
+        .with_method("Filecoin.MpoolSelect", mpool_select::<DB, KS>, false)
+        .with_method("Filecoin.MpoolClear", mpool_clear::<DB, KS>, false)
+        .with_method(
+            "Filecoin.MpoolPruneMetrics",
+            mpool_prune_metrics::<DB, KS>,
+            false,
+        )
         .with_method(
             "Filecoin.MpoolPushMessage",
             mpool_push_message::<DB, KS>,
@@ -219,6 +233,7 @@ where
             false,
         )
         .with_method("Filecoin.StateReplay", state_replay::<DB, KS>, false)
+        .with_method("Filecoin.StateCompute", state_compute::<DB, KS>, false)
         .with_method("Filecoin.StateGetActor", state_get_actor::<DB, KS>, false)
         .with_method(
             "Filecoin.StateAccountKey",
@@ -254,8 +269,16 @@ where
             gas_estimate_fee_cap::<DB, KS>,
             false,
         )
+        .with_method(
+            "Filecoin.GasEstimateMessageGas",
+            gas_estimate_message_gas::<DB, KS>,
+            false,
+        )
+        // Net API
+        .with_method("Filecoin.NetBlockAdd", net_block_add::<DB, KS>, false)
+        .with_method("Filecoin.NetBlockList", net_block_list::<DB, KS>, false)
         // Common
-        .with_method("Filecoin.Version", version, false)
+        .with_method("Filecoin.Version", version::<DB, KS>, false)
         .finish_unwrapped();
 
     let try_socket = TcpListener::bind(rpc_endpoint).await;
@@ -1,12 +1,14 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::gas_api::{estimate_message_gas, MessageSendSpec};
 use crate::RpcState;
 
 use address::Address;
 use blocks::TipsetKeys;
 use blockstore::BlockStore;
 use cid::json::{vec::CidJsonVec, CidJson};
+use db::IterableStore;
 use encoding::Cbor;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use message::Message;
@@ -14,24 +16,38 @@ use message::{
     signed_message::json::SignedMessageJson, unsigned_message::json::UnsignedMessageJson,
     SignedMessage,
 };
+use serde::Serialize;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::str::FromStr;
 use wallet::KeyStore;
 
+/// Statistics on the pruning of the message pool
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct MpoolPruneMetrics {
+    prunes: u64,
+    skipped_prunes: u64,
+    pruned_msgs: u64,
+    /// Duration of the last prune in milliseconds
+    last_prune_duration_ms: u64,
+}
+
 /// Estimate the gas price for an Address
 pub(crate) async fn estimate_gas_premium<DB, KS>(
     data: Data<RpcState<DB, KS>>,
-    Params(params): Params<(u64, String, u64, TipsetKeys)>,
+    Params(params): Params<(u64, String, i64, TipsetKeys)>,
 ) -> Result<String, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
     let (nblocks, sender_str, gas_limit, tsk) = params;
     let sender = Address::from_str(&sender_str)?;
     let price = data
         .mpool
-        .estimate_gas_premium(nblocks, sender, gas_limit, tsk)?;
+        .estimate_gas_premium(nblocks, sender, gas_limit, tsk)
+        .await?;
     Ok(price.to_string())
 }
 
@@ -41,7 +57,7 @@ pub(crate) async fn mpool_get_sequence<DB, KS>(
     Params(params): Params<(String,)>,
 ) -> Result<u64, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
     let (addr_str,) = params;
@@ -56,7 +72,7 @@ pub(crate) async fn mpool_pending<DB, KS>(
     Params(params): Params<(CidJsonVec,)>,
 ) -> Result<Vec<SignedMessage>, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
     let (CidJsonVec(cid_vec),) = params;
@@ -110,13 +126,62 @@ where
     }
 }
 
+/// Select messages from mpool for a block mined on top of the tipset with the given ticket quality
+pub(crate) async fn mpool_select<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(CidJsonVec, f64)>,
+) -> Result<Vec<SignedMessageJson>, JsonRpcError>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (CidJsonVec(cid_vec), ticket_quality) = params;
+    let tsk = TipsetKeys::new(cid_vec);
+    let ts = data.state_manager.chain_store().tipset_from_keys(&tsk)?;
+
+    let msgs = data.mpool.select_messages(&ts, ticket_quality).await?;
+    Ok(msgs.into_iter().map(SignedMessageJson).collect())
+}
+
+/// Clear all pending messages from the mpool, including local ones if `local` is set
+pub(crate) async fn mpool_clear<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(bool,)>,
+) -> Result<(), JsonRpcError>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (local,) = params;
+    data.mpool.clear(local).await?;
+    Ok(())
+}
+
+/// Return the statistics on the pruning of the mpool
+pub(crate) async fn mpool_prune_metrics<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+) -> Result<MpoolPruneMetrics, JsonRpcError>
+where
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let metrics = data.mpool.prune_metrics().await;
+    Ok(MpoolPruneMetrics {
+        prunes: metrics.prunes,
+        skipped_prunes: metrics.skipped_prunes,
+        pruned_msgs: metrics.pruned_msgs,
+        last_prune_duration_ms: u64::try_from(metrics.last_prune_duration.as_millis())
+            .unwrap_or(u64::MAX),
+    })
+}
+
 /// Add SignedMessage to mpool, return msg CID
 pub(crate) async fn mpool_push<DB, KS>(
     data: Data<RpcState<DB, KS>>,
     Params(params): Params<(SignedMessageJson,)>,
 ) -> Result<CidJson, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
     let (SignedMessageJson(smsg),) = params;
@@ -129,14 +194,21 @@ where
 /// Sign given UnsignedMessage and add it to mpool, return SignedMessage
 pub(crate) async fn mpool_push_message<DB, KS>(
     data: Data<RpcState<DB, KS>>,
-    Params(params): Params<(UnsignedMessageJson,)>,
+    Params(params): Params<(UnsignedMessageJson, Option<MessageSendSpec>)>,
 ) -> Result<SignedMessageJson, JsonRpcError>
 where
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 {
-    // TODO handle defaults for sequence, gas limit and gas price
-    let (UnsignedMessageJson(umsg),) = params;
+    // TODO handle default for sequence
+    let (UnsignedMessageJson(umsg), spec) = params;
+    let umsg = estimate_message_gas(
+        &data,
+        umsg,
+        spec.unwrap_or_default(),
+        &TipsetKeys::default(),
+    )
+    .await?;
 
     let from = umsg.from();
 
@@ -0,0 +1,71 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::RpcState;
+use blockstore::BlockStore;
+use forest_libp2p::NetworkMessage;
+use futures::channel::oneshot;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use wallet::KeyStore;
+
+/// Peers, IP addresses and subnets blocked from connecting to the node.
+#[derive(Serialize, Deserialize, Default)]
+pub struct NetBlockList {
+    #[serde(rename = "Peers", default)]
+    pub peers: Vec<String>,
+    #[serde(rename = "IPAddrs", default)]
+    pub ip_addrs: Vec<String>,
+    #[serde(rename = "IPSubnets", default)]
+    pub ip_subnets: Vec<String>,
+}
+
+/// Bans the peers of the block list until the node is restarted. Blocking IP addresses and
+/// subnets is not supported.
+pub(crate) async fn net_block_add<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(NetBlockList,)>,
+) -> Result<(), JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (block_list,) = params;
+    if !block_list.ip_addrs.is_empty() || !block_list.ip_subnets.is_empty() {
+        return Err("blocking IP addresses and subnets is not supported".into());
+    }
+    let peers = block_list
+        .peers
+        .iter()
+        .map(|p| PeerId::from_str(p).map_err(|e| format!("invalid peer id {}: {}", p, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    for peer_id in peers {
+        data.network_send
+            .send(NetworkMessage::BanPeer { peer_id })
+            .await;
+    }
+    Ok(())
+}
+
+/// Returns the peers currently banned, for misbehaving or through `NetBlockAdd`.
+pub(crate) async fn net_block_list<DB, KS>(
+    data: Data<RpcState<DB, KS>>,
+) -> Result<NetBlockList, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    data.network_send
+        .send(NetworkMessage::BannedPeers {
+            response_channel: tx,
+        })
+        .await;
+    let peers = rx.await?;
+    Ok(NetBlockList {
+        peers: peers.iter().map(PeerId::to_string).collect(),
+        ..Default::default()
+    })
+}
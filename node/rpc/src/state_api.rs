@@ -13,14 +13,16 @@ use blocks::{tipset_json::TipsetJson, Tipset, TipsetKeys};
 use blockstore::BlockStore;
 use cid::{json::CidJson, Cid};
 use clock::ChainEpoch;
+use db::IterableStore;
 use fil_types::{deadlines::DeadlineInfo, verifier::FullVerifier, SectorNumber};
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use message::{
     message_receipt::json::MessageReceiptJson,
     unsigned_message::{json::UnsignedMessageJson, UnsignedMessage},
+    ChainMessage, Message,
 };
 use serde::Serialize;
-use state_manager::{InvocResult, MarketBalance, StateManager};
+use state_manager::{ComputeStateOutput, InvocResult, MarketBalance, StateManager};
 use state_tree::StateTree;
 use std::sync::Arc;
 use wallet::KeyStore;
@@ -55,9 +57,10 @@ pub(crate) async fn state_miner_sector<
         .map_err(|e| e.into())
 }
 
-/// runs the given message and returns its result without any persisted changes.
+/// runs the given message and returns its result without any persisted changes. Pending messages
+/// of the sender in the message pool are applied first, in sequence order.
 pub(crate) async fn state_call<
-    DB: BlockStore + Send + Sync + 'static,
+    DB: BlockStore + IterableStore + Send + Sync + 'static,
     KS: KeyStore + Send + Sync + 'static,
 >(
     data: Data<RpcState<DB, KS>>,
@@ -66,9 +69,31 @@ pub(crate) async fn state_call<
     let state_manager = &data.state_manager;
     let (unsigned_msg_json, key) = params;
     let mut message: UnsignedMessage = unsigned_msg_json.into();
-    let tipset = data.state_manager.chain_store().tipset_from_keys(&key)?;
+    let tipset = Arc::new(data.state_manager.chain_store().tipset_from_keys(&key)?);
+
+    // Pending messages only apply on top of the current head.
+    let head = state_manager.chain_store().heaviest_tipset().await;
+    let prior_messages: Vec<ChainMessage> = if head.map_or(false, |h| h.key() == tipset.key()) {
+        match state_manager
+            .resolve_to_key_addr::<FullVerifier>(message.from(), &tipset)
+            .await
+        {
+            Ok(from) => data
+                .mpool
+                .pending_for(&from)
+                .await
+                .map(|s| s.into_iter().map(ChainMessage::Signed).collect())
+                .unwrap_or_default(),
+            // A sender which can't be resolved, such as one not created yet, has no pending
+            // messages.
+            Err(_) => Vec::new(),
+        }
+    } else {
+        Vec::new()
+    };
+
     Ok(state_manager
-        .call::<FullVerifier>(&mut message, Some(Arc::new(tipset)))
+        .call::<FullVerifier>(&mut message, &prior_messages, Some(tipset))
         .await?)
 }
 
@@ -250,9 +275,30 @@ pub(crate) async fn state_replay<
         msg,
         msg_rct: Some(ret.msg_receipt),
         error: ret.act_error.map(|e| e.to_string()),
+        execution_trace: ret.execution_trace,
     })
 }
 
+/// re-executes the given tipset and applies the given messages on top of it at the given height,
+/// returning the resulting state root and the execution traces of all messages.
+pub(crate) async fn state_compute<
+    DB: BlockStore + Send + Sync + 'static,
+    KS: KeyStore + Send + Sync + 'static,
+>(
+    data: Data<RpcState<DB, KS>>,
+    Params(params): Params<(ChainEpoch, Vec<UnsignedMessageJson>, TipsetKeys)>,
+) -> Result<ComputeStateOutput, JsonRpcError> {
+    let (height, msgs, key) = params;
+    let msgs = msgs
+        .into_iter()
+        .map(|m| ChainMessage::Unsigned(m.into()))
+        .collect();
+    Ok(data
+        .state_manager
+        .compute_tipset_with_messages::<FullVerifier>(height, msgs, &key)
+        .await?)
+}
+
 /// returns the indicated actor's nonce and balance.
 pub(crate) async fn state_get_actor<
     DB: BlockStore + Send + Sync + 'static,
@@ -120,6 +120,7 @@ mod tests {
         let cs = Arc::new(ChainStore::new(db.clone()));
         let state_manager = Arc::new(StateManager::new(cs.clone()));
         let state_manager_for_thread = state_manager.clone();
+        let network_send_for_thread = network_send.clone();
         let pool = task::block_on(async move {
             let bz = hex::decode("904300e80781586082cb7477a801f55c1f2ea5e5d1167661feea60a39f697e1099af132682b81cc5047beacf5b6e80d5f52b9fd90323fb8510a5396416dd076c13c85619e176558582744053a3faef6764829aa02132a1571a76aabdc498a638ea0054d3bb57f41d82015860812d2396cc4592cdf7f829374b01ffd03c5469a4b0a9acc5ccc642797aa0a5498b97b28d90820fedc6f79ff0a6005f5c15dbaca3b8a45720af7ed53000555667207a0ccb50073cd24510995abd4c4e45c1e9e114905018b2da9454190499941e818201582012dd0a6a7d0e222a97926da03adb5a7768d31cc7c5c2bd6828e14a7d25fa3a608182004b76616c69642070726f6f6681d82a5827000171a0e4022030f89a8b0373ad69079dbcbc5addfe9b34dce932189786e50d3eb432ede3ba9c43000f0001d82a5827000171a0e4022052238c7d15c100c1b9ebf849541810c9e3c2d86e826512c6c416d2318fcd496dd82a5827000171a0e40220e5658b3d18cd06e1db9015b4b0ec55c123a24d5be1ea24d83938c5b8397b4f2fd82a5827000171a0e4022018d351341c302a21786b585708c9873565a0d07c42521d4aaf52da3ff6f2e461586102c000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001a5f2c5439586102b5cd48724dce0fec8799d77fd6c5113276e7f470c8391faa0b5a6033a3eaf357d635705c36abe10309d73592727289680515afd9d424793ba4796b052682d21b03c5c8a37d94827fecc59cdc5750e198fdf20dee012f4d627c6665132298ab95004500053724e0").unwrap();
             let header = BlockHeader::unmarshal_cbor(&bz).unwrap();
@@ -134,9 +135,14 @@ mod tests {
                 db.write(i.key(), bz2).unwrap();
             }
             let provider = MpoolRpcProvider::new(subscriber, state_manager_for_thread.clone());
-            MessagePool::new(provider, "test".to_string(), Default::default())
-                .await
-                .unwrap()
+            MessagePool::new(
+                provider,
+                "test".to_string(),
+                network_send_for_thread,
+                Default::default(),
+            )
+            .await
+            .unwrap()
         });
 
         let state = Arc::new(RpcState {
@@ -148,6 +154,7 @@ mod tests {
             network_send,
             network_name: TEST_NET_NAME.to_owned(),
             events_pubsub: Arc::new(RwLock::new(Publisher::new(1000))),
+            export_dir: Default::default(),
         });
         (state, network_rx)
     }
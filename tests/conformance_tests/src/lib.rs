@@ -12,7 +12,7 @@ pub use self::message::*;
 pub use self::rand_replay::*;
 pub use self::stubs::*;
 pub use self::tipset::*;
-use actor::CHAOS_ACTOR_CODE_ID;
+use actor::{BuiltinActor, CHAOS_ACTOR_CODE_ID};
 use address::{Address, Protocol};
 use blockstore::BlockStore;
 use cid::Cid;
@@ -2,8 +2,8 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::*;
-use fil_types::get_network_version_default;
-use interpreter::CircSupplyCalc;
+use fil_types::{get_network_version_default, NetworkChain};
+use interpreter::{CircSupplyCalc, PriceSchedule};
 use state_tree::StateTree;
 use vm::TokenAmount;
 
@@ -48,6 +48,8 @@ pub fn execute_message(
         &params.randomness,
         params.basefee,
         get_network_version_default,
+        NetworkChain::Mainnet,
+        &PriceSchedule::mainnet(),
         &circ_supply,
     )?;
 
@@ -57,7 +59,7 @@ pub fn execute_message(
             .map(|s| s == "true")
             .unwrap_or_default()
         {
-            vm.register_actor(*CHAOS_ACTOR_CODE_ID);
+            vm.register_actor(*CHAOS_ACTOR_CODE_ID, BuiltinActor::Chaos);
         }
     }
 
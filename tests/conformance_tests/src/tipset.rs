@@ -82,6 +82,7 @@ pub fn execute_tipset(
     let mut applied_results = Vec::new();
     let (post_state_root, receipts_root) = sm.apply_blocks::<_, FullVerifier, _>(
         parent_epoch,
+        None,
         pre_root,
         &tipset.blocks,
         exec_epoch,
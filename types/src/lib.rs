@@ -3,6 +3,7 @@
 
 pub mod build_version;
 pub mod deadlines;
+mod network_params;
 mod piece;
 mod randomness;
 pub mod sector;
@@ -14,12 +15,13 @@ pub mod genesis;
 #[cfg(feature = "proofs")]
 pub mod verifier;
 
+pub use self::network_params::*;
 pub use self::piece::*;
 pub use self::randomness::*;
 pub use self::sector::*;
 pub use self::version::*;
 
-use clock::{ChainEpoch, EPOCH_DURATION_SECONDS};
+use clock::ChainEpoch;
 use num_bigint::BigInt;
 
 #[macro_use]
@@ -49,34 +51,5 @@ pub const BLOCKS_PER_EPOCH: u64 = 5;
 /// Ratio of integer values to token value.
 pub const FILECOIN_PRECISION: i64 = 1_000_000_000_000_000_000;
 
-/// Block delay, or epoch duration, to be used in blockchain system.
-pub const BLOCK_DELAY_SECS: u64 = EPOCH_DURATION_SECONDS as u64;
-
 /// Allowable clock drift in validations.
 pub const ALLOWABLE_CLOCK_DRIFT: u64 = 1;
-
-/// Config trait which handles different network configurations.
-pub trait NetworkParams {
-    /// Total filecoin available to network.
-    const TOTAL_FILECOIN: i64;
-
-    /// Available rewards for mining.
-    const MINING_REWARD_TOTAL: i64;
-
-    /// Initial reward actor balance. This function is only called in genesis setting up state.
-    fn initial_reward_balance() -> BigInt {
-        BigInt::from(Self::MINING_REWARD_TOTAL) * Self::TOTAL_FILECOIN
-    }
-
-    /// Convert integer value of tokens into BigInt based on the token precision.
-    fn from_fil(i: i64) -> BigInt {
-        BigInt::from(i) * FILECOIN_PRECISION
-    }
-}
-
-// Devnet Parameters, not yet finalized
-pub struct DevnetParams;
-impl NetworkParams for DevnetParams {
-    const TOTAL_FILECOIN: i64 = TOTAL_FILECOIN_BASE;
-    const MINING_REWARD_TOTAL: i64 = 1_400_000_000;
-}
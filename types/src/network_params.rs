@@ -0,0 +1,227 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{RegisteredSealProof, StoragePower, FILECOIN_PRECISION, TOTAL_FILECOIN_BASE};
+use clock::{ChainEpoch, EPOCH_DURATION_SECONDS};
+use num_bigint::BigInt;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Config trait which handles different network configurations.
+pub trait NetworkParams {
+    /// Total filecoin available to network.
+    const TOTAL_FILECOIN: i64;
+
+    /// Available rewards for mining.
+    const MINING_REWARD_TOTAL: i64;
+
+    /// Block delay, or epoch duration, in seconds.
+    const BLOCK_DELAY_SECS: u64;
+
+    /// Minimum quality adjusted power, in bytes, of a miner to be eligible for leader election.
+    const CONSENSUS_MINER_MIN_POWER: i64;
+
+    /// Seal proof types which can be used by new miners and new sectors.
+    const SUPPORTED_PROOF_TYPES: &'static [RegisteredSealProof];
+
+    /// Number of epochs between publishing a pre-commit and when the challenge for
+    /// interactive PoRep is drawn.
+    const PRE_COMMIT_CHALLENGE_DELAY: ChainEpoch;
+
+    /// Multiaddresses of the peers dialed to join the network.
+    const BOOTSTRAP_PEERS: &'static [&'static str];
+
+    /// Initial reward actor balance. This function is only called in genesis setting up state.
+    fn initial_reward_balance() -> BigInt {
+        BigInt::from(Self::MINING_REWARD_TOTAL) * Self::TOTAL_FILECOIN
+    }
+
+    /// Convert integer value of tokens into BigInt based on the token precision.
+    fn from_fil(i: i64) -> BigInt {
+        BigInt::from(i) * FILECOIN_PRECISION
+    }
+}
+
+/// Mainnet parameters.
+pub struct MainnetParams;
+impl NetworkParams for MainnetParams {
+    const TOTAL_FILECOIN: i64 = TOTAL_FILECOIN_BASE;
+    const MINING_REWARD_TOTAL: i64 = 1_400_000_000;
+    const BLOCK_DELAY_SECS: u64 = EPOCH_DURATION_SECONDS as u64;
+    const CONSENSUS_MINER_MIN_POWER: i64 = 10 << 40;
+    const SUPPORTED_PROOF_TYPES: &'static [RegisteredSealProof] = &[
+        RegisteredSealProof::StackedDRG32GiBV1,
+        RegisteredSealProof::StackedDRG64GiBV1,
+    ];
+    const PRE_COMMIT_CHALLENGE_DELAY: ChainEpoch = 150;
+    const BOOTSTRAP_PEERS: &'static [&'static str] = &[
+        "/dns4/bootstrap-0.mainnet.filops.net/tcp/1347/p2p/12D3KooWCVe8MmsEMes2FzgTpt9fXtmCY7wrq91GRiaC8PHSCCBj",
+        "/dns4/bootstrap-1.mainnet.filops.net/tcp/1347/p2p/12D3KooWCwevHg1yLCvktf2nvLu7L9894mcrJR4MsBCcm4syShVc",
+        "/dns4/bootstrap-2.mainnet.filops.net/tcp/1347/p2p/12D3KooWEWVwHGn2yR36gKLozmb4YjDJGerotAPGxmdWZx2nxMC4",
+        "/dns4/bootstrap-3.mainnet.filops.net/tcp/1347/p2p/12D3KooWKhgq8c7NQ9iGjbyK7v7phXvG6492HQfiDaGHLHLQjk7R",
+        "/dns4/bootstrap-4.mainnet.filops.net/tcp/1347/p2p/12D3KooWL6PsFNPhYftrJzGgF5U18hFoaVhfGk7xwzD8yVrHJ3Uc",
+        "/dns4/bootstrap-5.mainnet.filops.net/tcp/1347/p2p/12D3KooWLFynvDQiUpXoHroV1YxKHhPJgysQGH2k3ZGwtWzR4dFH",
+        "/dns4/bootstrap-6.mainnet.filops.net/tcp/1347/p2p/12D3KooWP5MwCiqdMETF9ub1P3MbCvQCcfconnYHbWg6sUJcDRQQ",
+        "/dns4/bootstrap-7.mainnet.filops.net/tcp/1347/p2p/12D3KooWRs3aY1p3juFjPy8gPN95PEQChm2QKGUCAdcDCC4EBMKf",
+        "/dns4/bootstrap-8.mainnet.filops.net/tcp/1347/p2p/12D3KooWScFR7385LTyR4zU1bYdzSiiAb5rnNABfVahPvVSzyTkR",
+        "/dns4/lotus-bootstrap.forceup.cn/tcp/41778/p2p/12D3KooWFQsv3nRMUevZNWWsY1Wu6NUzUbawnWU5NcRhgKuJA37C",
+        "/dns4/bootstrap-0.starpool.in/tcp/12757/p2p/12D3KooWGHpBMeZbestVEWkfdnC9u7p6uFHXL1n7m1ZBqsEmiUzz",
+        "/dns4/bootstrap-1.starpool.in/tcp/12757/p2p/12D3KooWQZrGH1PxSNZPum99M1zNvjNFM33d1AAu5DcvdHptuU7u",
+        "/dns4/node.glif.io/tcp/1235/p2p/12D3KooWBF8cpp65hp2u9LK5mh19x67ftAam84z9LsfaquTDSBpt",
+        "/dns4/bootstrap-0.ipfsmain.cn/tcp/34721/p2p/12D3KooWQnwEGNqcM2nAcPtRR9rAX8Hrg4k9kJLCHoTR5chJfz6d",
+        "/dns4/bootstrap-1.ipfsmain.cn/tcp/34723/p2p/12D3KooWMKxMkD5DMpSWsW7dBddKxKT7L2GgbNuckz9otxvkvByP",
+    ];
+}
+
+/// Calibration network parameters.
+pub struct CalibnetParams;
+impl NetworkParams for CalibnetParams {
+    const TOTAL_FILECOIN: i64 = TOTAL_FILECOIN_BASE;
+    const MINING_REWARD_TOTAL: i64 = 1_400_000_000;
+    const BLOCK_DELAY_SECS: u64 = EPOCH_DURATION_SECONDS as u64;
+    const CONSENSUS_MINER_MIN_POWER: i64 = 32 << 30;
+    const SUPPORTED_PROOF_TYPES: &'static [RegisteredSealProof] = &[
+        RegisteredSealProof::StackedDRG32GiBV1,
+        RegisteredSealProof::StackedDRG64GiBV1,
+    ];
+    const PRE_COMMIT_CHALLENGE_DELAY: ChainEpoch = 150;
+    const BOOTSTRAP_PEERS: &'static [&'static str] = &[
+        "/dns4/bootstrap-0.calibration.fildev.network/tcp/1347/p2p/12D3KooWJkikQQkxS58spo76BYzFt4fotaT5NpV2zngvrqm4u5ow",
+        "/dns4/bootstrap-1.calibration.fildev.network/tcp/1347/p2p/12D3KooWLce5FDHR4EX4CrYavphA5xS3uDsX6aoowXh5tzDUxJav",
+        "/dns4/bootstrap-2.calibration.fildev.network/tcp/1347/p2p/12D3KooWA9hFfQG9GjP6bHeuQQbMD3FDtZLdW1NayxKXUT26PQZu",
+        "/dns4/bootstrap-3.calibration.fildev.network/tcp/1347/p2p/12D3KooWMHDtverWWjJ1LmDfVyJYmCTA7Ra7Y4wrBg3wzW2j2qpb",
+    ];
+}
+
+/// Parameters of a local 2k devnet, which uses 2KiB sectors and short epochs.
+pub struct DevnetParams;
+impl NetworkParams for DevnetParams {
+    const TOTAL_FILECOIN: i64 = TOTAL_FILECOIN_BASE;
+    const MINING_REWARD_TOTAL: i64 = 1_400_000_000;
+    const BLOCK_DELAY_SECS: u64 = 4;
+    const CONSENSUS_MINER_MIN_POWER: i64 = 2048;
+    const SUPPORTED_PROOF_TYPES: &'static [RegisteredSealProof] =
+        &[RegisteredSealProof::StackedDRG2KiBV1];
+    const PRE_COMMIT_CHALLENGE_DELAY: ChainEpoch = 10;
+    const BOOTSTRAP_PEERS: &'static [&'static str] = &[];
+}
+
+/// Network the node runs on, selecting the `NetworkParams` preset used at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkChain {
+    Mainnet,
+    Calibnet,
+    Devnet,
+}
+
+impl Default for NetworkChain {
+    fn default() -> Self {
+        NetworkChain::Mainnet
+    }
+}
+
+/// Evaluates an expression generically over the `NetworkParams` preset of the chain.
+macro_rules! with_params {
+    ($chain:expr, $params:ident => $e:expr) => {
+        match $chain {
+            NetworkChain::Mainnet => {
+                type $params = MainnetParams;
+                $e
+            }
+            NetworkChain::Calibnet => {
+                type $params = CalibnetParams;
+                $e
+            }
+            NetworkChain::Devnet => {
+                type $params = DevnetParams;
+                $e
+            }
+        }
+    };
+}
+
+impl NetworkChain {
+    /// Block delay, or epoch duration, in seconds.
+    pub fn block_delay_secs(self) -> u64 {
+        with_params!(self, P => P::BLOCK_DELAY_SECS)
+    }
+
+    /// Minimum power of a miner to be eligible for leader election.
+    pub fn consensus_miner_min_power(self) -> StoragePower {
+        with_params!(self, P => StoragePower::from(P::CONSENSUS_MINER_MIN_POWER))
+    }
+
+    /// Seal proof types which can be used by new miners and new sectors.
+    pub fn supported_proof_types(self) -> &'static [RegisteredSealProof] {
+        with_params!(self, P => P::SUPPORTED_PROOF_TYPES)
+    }
+
+    /// Number of epochs between publishing a pre-commit and when the challenge for
+    /// interactive PoRep is drawn.
+    pub fn pre_commit_challenge_delay(self) -> ChainEpoch {
+        with_params!(self, P => P::PRE_COMMIT_CHALLENGE_DELAY)
+    }
+
+    /// Multiaddresses of the peers dialed to join the network.
+    pub fn bootstrap_peers(self) -> &'static [&'static str] {
+        with_params!(self, P => P::BOOTSTRAP_PEERS)
+    }
+}
+
+impl FromStr for NetworkChain {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mainnet" => Ok(NetworkChain::Mainnet),
+            "calibnet" => Ok(NetworkChain::Calibnet),
+            "devnet" | "2k" => Ok(NetworkChain::Devnet),
+            _ => Err(format!("unknown network {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for NetworkChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NetworkChain::Mainnet => "mainnet",
+            NetworkChain::Calibnet => "calibnet",
+            NetworkChain::Devnet => "devnet",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_from_str() {
+        for chain in &[
+            NetworkChain::Mainnet,
+            NetworkChain::Calibnet,
+            NetworkChain::Devnet,
+        ] {
+            assert_eq!(chain.to_string().parse::<NetworkChain>(), Ok(*chain));
+        }
+        assert_eq!("2k".parse::<NetworkChain>(), Ok(NetworkChain::Devnet));
+        assert!("testnet".parse::<NetworkChain>().is_err());
+    }
+
+    #[test]
+    fn chain_params() {
+        assert_eq!(NetworkChain::Mainnet.pre_commit_challenge_delay(), 150);
+        assert_eq!(NetworkChain::Devnet.block_delay_secs(), 4);
+        assert_eq!(
+            NetworkChain::Calibnet.consensus_miner_min_power(),
+            StoragePower::from(32u64 << 30)
+        );
+        assert!(NetworkChain::Devnet
+            .supported_proof_types()
+            .contains(&RegisteredSealProof::StackedDRG2KiBV1));
+        assert!(NetworkChain::Devnet.bootstrap_peers().is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use encoding::{Byte32De, BytesSer};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 /// String of random bytes
-#[derive(PartialEq, Eq, Default, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Default, Copy, Clone, Debug)]
 pub struct Randomness(pub [u8; 32]);
 
 impl Serialize for Randomness {
@@ -11,7 +11,7 @@ pub type SealRandomness = Randomness;
 pub type InteractiveSealRandomness = Randomness;
 
 /// Information needed to verify a seal proof.
-#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize_tuple, Deserialize_tuple)]
 pub struct SealVerifyInfo {
     pub registered_proof: RegisteredSealProof,
     pub sector_id: SectorID,
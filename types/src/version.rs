@@ -9,19 +9,41 @@ pub const UPGRADE_BREEZE_HEIGHT: ChainEpoch = 41280;
 pub const UPGRADE_SMOKE_HEIGHT: ChainEpoch = 51000;
 /// V3 network upgrade
 pub const UPGRADE_IGNITION_HEIGHT: ChainEpoch = 94000;
+/// Resets the vesting of the reserve multisigs, without changing the network version
+pub const UPGRADE_REFUEL_HEIGHT: ChainEpoch = 130800;
 /// V4 network upgrade
 pub const UPGRADE_ACTORS_V2_HEIGHT: ChainEpoch = 138720;
 /// V5 network upgrade
 pub const UPGRADE_TAPE_HEIGHT: ChainEpoch = 140760;
+/// Renames the network to mainnet, without changing the network version
+pub const UPGRADE_LIFTOFF_HEIGHT: ChainEpoch = 148888;
+/// V6 network upgrade
+pub const UPGRADE_KUMQUAT_HEIGHT: ChainEpoch = 170000;
+/// V7 network upgrade, which also increases the storage gas multiplier and reprices PoSt
+/// verification
+pub const UPGRADE_CALICO_HEIGHT: ChainEpoch = 265200;
 
-pub const UPGRADE_LIFTOFF_HEIGHT: i64 = 148888;
+/// V1 network upgrade of the calibration network, before its genesis
+pub const UPGRADE_CALIBNET_BREEZE_HEIGHT: ChainEpoch = -3;
+/// V2 network upgrade of the calibration network, before its genesis
+pub const UPGRADE_CALIBNET_SMOKE_HEIGHT: ChainEpoch = -2;
+/// V3 network upgrade of the calibration network, before its genesis
+pub const UPGRADE_CALIBNET_IGNITION_HEIGHT: ChainEpoch = -1;
+/// V4 network upgrade of the calibration network
+pub const UPGRADE_CALIBNET_ACTORS_V2_HEIGHT: ChainEpoch = 30;
+/// V5 network upgrade of the calibration network
+pub const UPGRADE_CALIBNET_TAPE_HEIGHT: ChainEpoch = 60;
+/// V6 network upgrade of the calibration network
+pub const UPGRADE_CALIBNET_KUMQUAT_HEIGHT: ChainEpoch = 90;
+/// V7 network upgrade of the calibration network
+pub const UPGRADE_CALIBNET_CALICO_HEIGHT: ChainEpoch = 100;
 
 struct Upgrade {
     height: ChainEpoch,
     network: NetworkVersion,
 }
 
-const MAINNET_SCHEDULE: [Upgrade; 5] = [
+const MAINNET_SCHEDULE: [Upgrade; 7] = [
     Upgrade {
         height: UPGRADE_BREEZE_HEIGHT,
         network: NetworkVersion::V1,
@@ -42,6 +64,14 @@ const MAINNET_SCHEDULE: [Upgrade; 5] = [
         height: UPGRADE_TAPE_HEIGHT,
         network: NetworkVersion::V5,
     },
+    Upgrade {
+        height: UPGRADE_KUMQUAT_HEIGHT,
+        network: NetworkVersion::V6,
+    },
+    Upgrade {
+        height: UPGRADE_CALICO_HEIGHT,
+        network: NetworkVersion::V7,
+    },
 ];
 
 /// Specifies the network version
@@ -60,6 +90,10 @@ pub enum NetworkVersion {
     V4,
     /// tape (increases max prove commit size by 10x)
     V5,
+    /// kumquat (specs-actors v2.2)
+    V6,
+    /// calico (specs-actors v2.3)
+    V7,
 }
 
 /// Gets network version from epoch using default Mainnet schedule
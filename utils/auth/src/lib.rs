@@ -21,8 +21,11 @@ pub const WRITE: [&str; 2] = ["read", "write"];
 /// Reading permissions
 pub const READ: [&str; 1] = ["read"];
 /// All methods that require write permission
-pub const WRITE_ACCESS: [&str; 6] = [
+pub const WRITE_ACCESS: [&str; 9] = [
     "Filecoin.MpoolPush",
+    "Filecoin.MpoolClear",
+    "Filecoin.ChainExport",
+    "Filecoin.NetBlockAdd",
     "Filecoin.WalletNew",
     "Filecoin.WalletHas",
     "Filecoin.WalletList",
@@ -1,6 +1,7 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use super::builtin_actor;
 use cid::{multihash::MultihashDigest, Cid, Code::Identity, Codec};
 
 lazy_static! {
@@ -26,30 +27,14 @@ fn make_builtin(bz: &[u8]) -> Cid {
     Cid::new_v1(Codec::Raw, Identity.digest(bz))
 }
 
-/// Returns true if the code `Cid` belongs to a builtin actor.
+/// Returns true if the code `Cid` belongs to a builtin actor of any generation.
 pub fn is_builtin_actor(code: &Cid) -> bool {
-    code == &*SYSTEM_ACTOR_CODE_ID
-        || code == &*INIT_ACTOR_CODE_ID
-        || code == &*CRON_ACTOR_CODE_ID
-        || code == &*ACCOUNT_ACTOR_CODE_ID
-        || code == &*POWER_ACTOR_CODE_ID
-        || code == &*MINER_ACTOR_CODE_ID
-        || code == &*MARKET_ACTOR_CODE_ID
-        || code == &*PAYCH_ACTOR_CODE_ID
-        || code == &*MULTISIG_ACTOR_CODE_ID
-        || code == &*REWARD_ACTOR_CODE_ID
-        || code == &*VERIFREG_ACTOR_CODE_ID
+    builtin_actor(code).is_some()
 }
 
 /// Returns true if the code belongs to a singleton actor.
 pub fn is_singleton_actor(code: &Cid) -> bool {
-    code == &*SYSTEM_ACTOR_CODE_ID
-        || code == &*INIT_ACTOR_CODE_ID
-        || code == &*REWARD_ACTOR_CODE_ID
-        || code == &*CRON_ACTOR_CODE_ID
-        || code == &*POWER_ACTOR_CODE_ID
-        || code == &*MARKET_ACTOR_CODE_ID
-        || code == &*VERIFREG_ACTOR_CODE_ID
+    builtin_actor(code).map_or(false, |actor| actor.is_singleton())
 }
 
 /// Returns true if the code belongs to an account actor.
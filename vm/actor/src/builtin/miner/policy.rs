@@ -4,7 +4,7 @@
 use super::types::SectorOnChainInfo;
 use crate::{network::*, DealWeight};
 use clock::ChainEpoch;
-use fil_types::{RegisteredSealProof, SectorQuality, SectorSize, StoragePower};
+use fil_types::{NetworkChain, RegisteredSealProof, SectorQuality, SectorSize, StoragePower};
 use num_bigint::BigUint;
 use num_bigint::{BigInt, Integer};
 use num_traits::Pow;
@@ -55,10 +55,9 @@ pub const SEALED_CID_PREFIX: cid::Prefix = cid::Prefix {
     mh_len: 32,
 };
 
-/// List of proof types which can be used when creating new miner actors
-pub fn check_supported_proof_types(proof: RegisteredSealProof) -> bool {
-    use RegisteredSealProof::*;
-    matches!(proof, StackedDRG32GiBV1 | StackedDRG64GiBV1)
+/// Checks if the proof type can be used when creating new miner actors on the network
+pub fn check_supported_proof_types(chain: NetworkChain, proof: RegisteredSealProof) -> bool {
+    chain.supported_proof_types().contains(&proof)
 }
 /// Maximum duration to allow for the sealing process for seal algorithms.
 /// Dependent on algorithm and sector size
@@ -70,10 +69,6 @@ pub fn max_seal_duration(proof: RegisteredSealProof) -> Option<ChainEpoch> {
         _ => None,
     }
 }
-/// Number of epochs between publishing the precommit and when the challenge for interactive PoRep is drawn
-/// used to ensure it is not predictable by miner.
-pub const PRE_COMMIT_CHALLENGE_DELAY: ChainEpoch = 150;
-
 /// Lookback from the current epoch for state view for leader elections.
 pub const ELECTION_LOOKBACK: ChainEpoch = 1; // PARAM_FINISH
 
@@ -11,6 +11,7 @@ pub mod multisig;
 pub mod network;
 pub mod paych;
 pub mod power;
+mod registry;
 pub mod reward;
 mod shared;
 pub mod singletons;
@@ -18,6 +19,7 @@ pub mod system;
 pub mod verifreg;
 
 pub use self::codes::*;
+pub use self::registry::*;
 pub(crate) use self::shared::*;
 pub use self::singletons::*;
 pub use network::*;
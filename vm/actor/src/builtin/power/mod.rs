@@ -152,6 +152,7 @@ impl Actor {
     {
         rt.validate_immediate_caller_type(std::iter::once(&*MINER_ACTOR_CODE_ID))?;
         let miner_addr = *rt.message().caller();
+        let min_power = rt.network_chain().consensus_miner_min_power();
 
         rt.transaction(|st: &mut State, rt| {
             let mut claims = make_map_with_root(&st.claims, rt.store()).map_err(|e| {
@@ -163,6 +164,7 @@ impl Actor {
                 &miner_addr,
                 &params.raw_byte_delta,
                 &params.quality_adjusted_delta,
+                &min_power,
             )
             .map_err(|e| {
                 e.downcast_default(
@@ -276,6 +278,7 @@ impl Actor {
     {
         rt.validate_immediate_caller_type(std::iter::once(&*MINER_ACTOR_CODE_ID))?;
         let miner_addr = *rt.message().caller();
+        let min_power = rt.network_chain().consensus_miner_min_power();
 
         rt.transaction(|st: &mut State, rt| {
             let mut claims = make_map_with_root(&st.claims, rt.store()).map_err(|e| {
@@ -307,6 +310,7 @@ impl Actor {
                 &miner_addr,
                 &raw_byte_power.neg(),
                 &quality_adj_power.neg(),
+                &min_power,
             )
             .map_err(|e| {
                 e.downcast_default(
@@ -575,6 +579,7 @@ impl Actor {
                 failed_miner_crons.push(event.miner_addr)
             }
         }
+        let min_power = rt.network_chain().consensus_miner_min_power();
         rt.transaction(|st: &mut State, rt| {
             let mut claims = make_map_with_root(&st.claims, rt.store()).map_err(|e| {
                 e.downcast_default(ExitCode::ErrIllegalState, "failed to load claims")
@@ -606,7 +611,8 @@ impl Actor {
                 };
 
                 // zero out miner power
-                let res = st.add_to_claim(&mut claims, &miner_addr, &rbp.neg(), &qap.neg());
+                let res =
+                    st.add_to_claim(&mut claims, &miner_addr, &rbp.neg(), &qap.neg(), &min_power);
                 if let Err(e) = res {
                     log::warn!(
                         "failed to remove power for miner {} after to failed cron: {}",
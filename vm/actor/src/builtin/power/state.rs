@@ -1,7 +1,7 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::{CONSENSUS_MINER_MIN_MINERS, CONSENSUS_MINER_MIN_POWER};
+use super::CONSENSUS_MINER_MIN_MINERS;
 use crate::{
     make_map_with_root,
     smooth::{AlphaBetaFilter, FilterEstimate, DEFAULT_ALPHA, DEFAULT_BETA},
@@ -90,6 +90,7 @@ impl State {
         &self,
         s: &BS,
         miner: &Address,
+        min_power: &StoragePower,
     ) -> Result<bool, Box<dyn StdError>> {
         let claims = make_map_with_root(&self.claims, s)?;
 
@@ -98,7 +99,7 @@ impl State {
 
         let miner_nominal_power = &claim.quality_adj_power;
 
-        if miner_nominal_power >= &CONSENSUS_MINER_MIN_POWER {
+        if miner_nominal_power >= min_power {
             // If miner is larger than min power requirement, valid
             Ok(true)
         } else if self.miner_above_min_power_count >= CONSENSUS_MINER_MIN_MINERS {
@@ -116,6 +117,7 @@ impl State {
         miner: &Address,
         power: &StoragePower,
         qa_power: &StoragePower,
+        min_power: &StoragePower,
     ) -> Result<(), Box<dyn StdError>> {
         let old_claim = get_claim(claims, miner)?
             .ok_or_else(|| actor_error!(ErrNotFound; "no claim for actor {}", miner))?;
@@ -128,9 +130,8 @@ impl State {
             quality_adj_power: old_claim.quality_adj_power.clone() + qa_power,
         };
 
-        let min_power_ref: &StoragePower = &*CONSENSUS_MINER_MIN_POWER;
-        let prev_below: bool = &old_claim.quality_adj_power < min_power_ref;
-        let still_below: bool = &new_claim.quality_adj_power < min_power_ref;
+        let prev_below: bool = &old_claim.quality_adj_power < min_power;
+        let still_below: bool = &new_claim.quality_adj_power < min_power;
 
         if prev_below && !still_below {
             // Just passed min miner size
@@ -0,0 +1,149 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::*;
+use crate::chaos;
+use cid::Cid;
+use ipld_blockstore::BlockStore;
+use runtime::{ActorCode, Runtime};
+use std::collections::HashMap;
+use vm::{ActorError, MethodNum, Serialized};
+
+lazy_static! {
+    /// Builtin actors of every generation, keyed by code cid.
+    pub static ref BUILTIN_ACTORS: ActorRegistry = ActorRegistry::builtin();
+}
+
+/// Generation of the builtin actor code. Network upgrades which change the behaviour of actors
+/// introduce a new generation with its own code cids, so that actors of different generations
+/// can exist side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ActorVersion {
+    /// specs-actors v0.9
+    V0,
+}
+
+impl ActorVersion {
+    /// All generations of builtin actor code.
+    pub const ALL: [ActorVersion; 1] = [ActorVersion::V0];
+
+    /// Returns the code cids of the builtin actors of the generation, with their implementation.
+    pub fn builtin_actors(self) -> Vec<(Cid, BuiltinActor)> {
+        match self {
+            ActorVersion::V0 => vec![
+                (*SYSTEM_ACTOR_CODE_ID, BuiltinActor::System),
+                (*INIT_ACTOR_CODE_ID, BuiltinActor::Init),
+                (*CRON_ACTOR_CODE_ID, BuiltinActor::Cron),
+                (*ACCOUNT_ACTOR_CODE_ID, BuiltinActor::Account),
+                (*POWER_ACTOR_CODE_ID, BuiltinActor::Power),
+                (*MINER_ACTOR_CODE_ID, BuiltinActor::Miner),
+                (*MARKET_ACTOR_CODE_ID, BuiltinActor::Market),
+                (*PAYCH_ACTOR_CODE_ID, BuiltinActor::PaymentChannel),
+                (*MULTISIG_ACTOR_CODE_ID, BuiltinActor::Multisig),
+                (*REWARD_ACTOR_CODE_ID, BuiltinActor::Reward),
+                (*VERIFREG_ACTOR_CODE_ID, BuiltinActor::VerifiedRegistry),
+            ],
+        }
+    }
+}
+
+/// Implementation of actor code which can be invoked by the VM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinActor {
+    System,
+    Init,
+    Cron,
+    Account,
+    Power,
+    Miner,
+    Market,
+    PaymentChannel,
+    Multisig,
+    Reward,
+    VerifiedRegistry,
+    /// Actor used by test vectors to exercise the VM, never deployed on a network.
+    Chaos,
+}
+
+impl BuiltinActor {
+    /// Invokes a method of the actor.
+    pub fn invoke_method<BS, RT>(
+        self,
+        rt: &mut RT,
+        method_num: MethodNum,
+        params: &Serialized,
+    ) -> Result<Serialized, ActorError>
+    where
+        BS: BlockStore,
+        RT: Runtime<BS>,
+    {
+        match self {
+            BuiltinActor::System => system::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Init => init::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Cron => cron::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Account => account::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Power => power::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Miner => miner::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Market => market::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::PaymentChannel => paych::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Multisig => multisig::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::Reward => reward::Actor::invoke_method(rt, method_num, params),
+            BuiltinActor::VerifiedRegistry => {
+                verifreg::Actor::invoke_method(rt, method_num, params)
+            }
+            BuiltinActor::Chaos => chaos::Actor::invoke_method(rt, method_num, params),
+        }
+    }
+
+    /// Returns true if there can only be one instance of the actor.
+    pub fn is_singleton(self) -> bool {
+        matches!(
+            self,
+            BuiltinActor::System
+                | BuiltinActor::Init
+                | BuiltinActor::Reward
+                | BuiltinActor::Cron
+                | BuiltinActor::Power
+                | BuiltinActor::Market
+                | BuiltinActor::VerifiedRegistry
+        )
+    }
+}
+
+/// Actor code which can be invoked, keyed by code cid.
+#[derive(Debug, Clone)]
+pub struct ActorRegistry {
+    actors: HashMap<Cid, BuiltinActor>,
+}
+
+impl ActorRegistry {
+    /// Registry of the builtin actors of every generation.
+    pub fn builtin() -> Self {
+        let actors = ActorVersion::ALL
+            .iter()
+            .flat_map(|version| version.builtin_actors())
+            .collect();
+        Self { actors }
+    }
+
+    /// Registers actor code under the given code cid.
+    /// Returns false if the code cid was already registered.
+    pub fn register(&mut self, code: Cid, actor: BuiltinActor) -> bool {
+        self.actors.insert(code, actor).is_none()
+    }
+
+    /// Returns the actor code registered under the code cid.
+    pub fn get(&self, code: &Cid) -> Option<BuiltinActor> {
+        self.actors.get(code).copied()
+    }
+
+    /// Returns true if actor code is registered under the code cid.
+    pub fn contains(&self, code: &Cid) -> bool {
+        self.actors.contains_key(code)
+    }
+}
+
+/// Returns the builtin actor, of any generation, with the given code cid.
+pub fn builtin_actor(code: &Cid) -> Option<BuiltinActor> {
+    BUILTIN_ACTORS.get(code)
+}
@@ -21,7 +21,7 @@ use fil_types::HAMT_BIT_WIDTH;
 use ipld_blockstore::BlockStore;
 use ipld_hamt::{BytesKey, Error as HamtError, Hamt};
 use num_bigint::BigInt;
-use runtime::{ActorCode, Runtime};
+use runtime::Runtime;
 use serde::{de::DeserializeOwned, Serialize};
 use unsigned_varint::decode::Error as UVarintError;
 
@@ -75,6 +75,8 @@ pub fn parse_uint_key(s: &[u8]) -> Result<u64, UVarintError> {
     Ok(v)
 }
 
+/// Invokes a method on the builtin actor, of any generation, with the given code cid.
+/// Returns `None` if the code cid does not belong to a builtin actor.
 pub fn invoke_code<RT, BS>(
     code: &Cid,
     rt: &mut RT,
@@ -85,29 +87,5 @@ where
     BS: BlockStore,
     RT: Runtime<BS>,
 {
-    if code == &*SYSTEM_ACTOR_CODE_ID {
-        Some(system::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*INIT_ACTOR_CODE_ID {
-        Some(init::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*CRON_ACTOR_CODE_ID {
-        Some(cron::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*ACCOUNT_ACTOR_CODE_ID {
-        Some(account::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*POWER_ACTOR_CODE_ID {
-        Some(power::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*MINER_ACTOR_CODE_ID {
-        Some(miner::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*MARKET_ACTOR_CODE_ID {
-        Some(market::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*PAYCH_ACTOR_CODE_ID {
-        Some(paych::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*MULTISIG_ACTOR_CODE_ID {
-        Some(multisig::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*REWARD_ACTOR_CODE_ID {
-        Some(reward::Actor::invoke_method(rt, method_num, params))
-    } else if code == &*VERIFREG_ACTOR_CODE_ID {
-        Some(verifreg::Actor::invoke_method(rt, method_num, params))
-    } else {
-        None
-    }
+    builtin_actor(code).map(|actor| actor.invoke_method(rt, method_num, params))
 }
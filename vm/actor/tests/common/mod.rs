@@ -8,7 +8,7 @@ use crypto::{DomainSeparationTag, Signature};
 use db::MemoryDB;
 use encoding::{blake2b_256, de::DeserializeOwned, Cbor};
 use fil_types::{
-    NetworkVersion, PieceInfo, Randomness, RegisteredSealProof, SealVerifyInfo,
+    NetworkChain, NetworkVersion, PieceInfo, Randomness, RegisteredSealProof, SealVerifyInfo,
     WindowPoStVerifyInfo,
 };
 use ipld_blockstore::BlockStore;
@@ -55,6 +55,7 @@ pub struct MockRuntime {
     pub expect_verify_consensus_fault: RefCell<Option<ExpectVerifyConsensusFault>>,
     pub hash_func: Box<dyn Fn(&[u8]) -> [u8; 32]>,
     pub network_version: NetworkVersion,
+    pub network_chain: NetworkChain,
 }
 
 impl Default for MockRuntime {
@@ -88,6 +89,7 @@ impl Default for MockRuntime {
             expect_verify_consensus_fault: Default::default(),
             hash_func: Box::new(|_| [0u8; 32]),
             network_version: NetworkVersion::V0,
+            network_chain: NetworkChain::Mainnet,
         }
     }
 }
@@ -379,6 +381,10 @@ impl Runtime<MemoryDB> for MockRuntime {
         self.network_version
     }
 
+    fn network_chain(&self) -> NetworkChain {
+        self.network_chain
+    }
+
     fn message(&self) -> &dyn MessageInfo {
         self.require_in_call();
         self
@@ -0,0 +1,41 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use actor::{
+    builtin_actor, is_builtin_actor, is_singleton_actor, ActorRegistry, ActorVersion, BuiltinActor,
+    ACCOUNT_ACTOR_CODE_ID, CHAOS_ACTOR_CODE_ID, INIT_ACTOR_CODE_ID, MINER_ACTOR_CODE_ID,
+};
+
+#[test]
+fn builtin_registry() {
+    let registry = ActorRegistry::builtin();
+    for version in ActorVersion::ALL.iter() {
+        for (code, actor) in version.builtin_actors() {
+            assert_eq!(registry.get(&code), Some(actor));
+            assert_eq!(builtin_actor(&code), Some(actor));
+        }
+    }
+
+    assert_eq!(builtin_actor(&INIT_ACTOR_CODE_ID), Some(BuiltinActor::Init));
+    assert!(is_builtin_actor(&ACCOUNT_ACTOR_CODE_ID));
+    assert!(is_singleton_actor(&INIT_ACTOR_CODE_ID));
+    assert!(!is_singleton_actor(&MINER_ACTOR_CODE_ID));
+
+    // Chaos actor is only invocable once registered explicitly.
+    assert!(!is_builtin_actor(&CHAOS_ACTOR_CODE_ID));
+    assert!(!registry.contains(&CHAOS_ACTOR_CODE_ID));
+}
+
+#[test]
+fn register_actor() {
+    let mut registry = ActorRegistry::builtin();
+    assert!(registry.register(*CHAOS_ACTOR_CODE_ID, BuiltinActor::Chaos));
+    assert!(!registry.register(*CHAOS_ACTOR_CODE_ID, BuiltinActor::Chaos));
+    assert_eq!(
+        registry.get(&CHAOS_ACTOR_CODE_ID),
+        Some(BuiltinActor::Chaos)
+    );
+
+    // Registering on a copy does not change the global set of builtin actors.
+    assert!(builtin_actor(&CHAOS_ACTOR_CODE_ID).is_none());
+}
@@ -2,8 +2,9 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::gas_block_store::GasBlockStore;
-use super::gas_tracker::{price_list_by_epoch, GasCharge, GasTracker, PriceList};
-use super::{CircSupplyCalc, Rand};
+use super::gas_tracker::{GasCharge, GasTracker, PriceList, PriceSchedule};
+use super::seal_verification::{batch_verify_seals, verify_seal_cached};
+use super::{CircSupplyCalc, ExecutionTrace, Rand};
 use actor::*;
 use address::{Address, Protocol};
 use blocks::BlockHeader;
@@ -11,25 +12,25 @@ use byteorder::{BigEndian, WriteBytesExt};
 use cid::{Cid, Code::Blake2b256};
 use clock::ChainEpoch;
 use crypto::{DomainSeparationTag, Signature};
-use fil_types::{verifier::ProofVerifier, DevnetParams, NetworkParams, NetworkVersion, Randomness};
+use fil_types::{verifier::ProofVerifier, NetworkChain, NetworkVersion, Randomness};
 use fil_types::{PieceInfo, RegisteredSealProof, SealVerifyInfo, WindowPoStVerifyInfo};
 use forest_encoding::{blake2b_256, to_vec, Cbor};
 use ipld_blockstore::BlockStore;
 use log::warn;
-use message::{Message, UnsignedMessage};
+use message::{Message, MessageReceipt, UnsignedMessage};
 use num_bigint::BigInt;
 use num_traits::Zero;
-use rayon::prelude::*;
 use runtime::{
     compute_unsealed_sector_cid, ActorCode, ConsensusFault, ConsensusFaultType, MessageInfo,
     Runtime, Syscalls,
 };
 use state_tree::StateTree;
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::error::Error as StdError;
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::time::Instant;
 use vm::{
     actor_error, ActorError, ActorState, ExitCode, MethodNum, Serialized, TokenAmount,
     EMPTY_ARR_CID, METHOD_SEND,
@@ -62,8 +63,9 @@ impl MessageInfo for VMMsg {
 }
 
 /// Implementation of the Runtime trait.
-pub struct DefaultRuntime<'db, 'vm, BS, R, C, V, P = DevnetParams> {
+pub struct DefaultRuntime<'db, 'vm, BS, R, C, V> {
     version: NetworkVersion,
+    chain: NetworkChain,
     state: &'vm mut StateTree<'db, BS>,
     store: GasBlockStore<'db, BS>,
     gas_tracker: Rc<RefCell<GasTracker>>,
@@ -76,17 +78,17 @@ pub struct DefaultRuntime<'db, 'vm, BS, R, C, V, P = DevnetParams> {
     rand: &'vm R,
     caller_validated: bool,
     allow_internal: bool,
-    registered_actors: &'vm HashSet<Cid>,
+    registered_actors: &'vm ActorRegistry,
     circ_supply_calc: &'vm C,
+    /// Trace of the message currently being executed, only recorded if tracing is enabled
+    execution_trace: Option<ExecutionTrace>,
     verifier: PhantomData<V>,
-    params: PhantomData<P>,
 }
 
-impl<'db, 'vm, BS, R, C, V, P> DefaultRuntime<'db, 'vm, BS, R, C, V, P>
+impl<'db, 'vm, BS, R, C, V> DefaultRuntime<'db, 'vm, BS, R, C, V>
 where
     BS: BlockStore,
     V: ProofVerifier,
-    P: NetworkParams,
     R: Rand,
     C: CircSupplyCalc,
 {
@@ -94,6 +96,8 @@ where
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         version: NetworkVersion,
+        chain: NetworkChain,
+        price_schedule: &PriceSchedule,
         state: &'vm mut StateTree<'db, BS>,
         store: &'db BS,
         gas_used: i64,
@@ -103,10 +107,10 @@ where
         origin_nonce: u64,
         num_actors_created: u64,
         rand: &'vm R,
-        registered_actors: &'vm HashSet<Cid>,
+        registered_actors: &'vm ActorRegistry,
         circ_supply_calc: &'vm C,
     ) -> Result<Self, ActorError> {
-        let price_list = price_list_by_epoch(epoch);
+        let price_list = price_schedule.price_list(epoch).clone();
         let gas_tracker = Rc::new(RefCell::new(GasTracker::new(message.gas_limit(), gas_used)));
         let gas_block_store = GasBlockStore {
             price_list: price_list.clone(),
@@ -129,6 +133,7 @@ where
 
         Ok(DefaultRuntime {
             version,
+            chain,
             state,
             store: gas_block_store,
             gas_tracker,
@@ -141,9 +146,9 @@ where
             rand,
             registered_actors,
             circ_supply_calc,
+            execution_trace: None,
             allow_internal: true,
             caller_validated: false,
-            params: PhantomData,
             verifier: PhantomData,
         })
     }
@@ -164,6 +169,23 @@ where
         self.gas_tracker.borrow().gas_available()
     }
 
+    /// Starts recording the execution trace of the given message, including the gas charged and
+    /// every subcall sent while executing it.
+    pub fn enable_tracing(&mut self, msg: &UnsignedMessage) {
+        self.gas_tracker.borrow_mut().enable_tracing();
+        self.execution_trace = Some(ExecutionTrace::new(msg.clone()));
+    }
+
+    /// Returns the execution trace recorded by the runtime, if tracing was enabled.
+    /// The receipt of the trace is left for the caller to fill in.
+    pub fn take_execution_trace(&mut self) -> Option<ExecutionTrace> {
+        let mut trace = self.execution_trace.take()?;
+        trace
+            .gas_charges
+            .extend(self.gas_tracker.borrow_mut().take_gas_charges());
+        Some(trace)
+    }
+
     /// Returns the price list for gas charges within the runtime
     pub fn price_list(&self) -> &PriceList {
         &self.price_list
@@ -275,7 +297,32 @@ where
         };
         self.caller_validated = false;
 
-        let send_res = vm_send::<BS, R, C, V, P>(self, &msg, None);
+        // Record the subcall in its own trace, which is added to the caller's once it returns
+        let prev_trace = self.take_execution_trace();
+        if prev_trace.is_some() {
+            self.execution_trace = Some(ExecutionTrace::new(msg.clone()));
+        }
+        let gas_used_before = self.gas_used();
+        let start = Instant::now();
+
+        let send_res = vm_send::<BS, R, C, V>(self, &msg, None);
+
+        if let Some(mut prev_trace) = prev_trace {
+            if let Some(mut trace) = self.take_execution_trace() {
+                trace.msg_rct = MessageReceipt {
+                    exit_code: send_res
+                        .as_ref()
+                        .err()
+                        .map_or(ExitCode::Ok, |e| e.exit_code()),
+                    return_data: send_res.as_ref().ok().cloned().unwrap_or_default(),
+                    gas_used: self.gas_used() - gas_used_before,
+                };
+                trace.error = send_res.as_ref().err().map(|e| e.to_string());
+                trace.duration = start.elapsed();
+                prev_trace.subcalls.push(trace);
+            }
+            self.execution_trace = Some(prev_trace);
+        }
 
         // Reset values back to their values before the call
         self.vm_msg = prev_msg;
@@ -353,18 +400,19 @@ where
     }
 }
 
-impl<'bs, BS, R, CS, V, P> Runtime<GasBlockStore<'bs, BS>>
-    for DefaultRuntime<'bs, '_, BS, R, CS, V, P>
+impl<'bs, BS, R, CS, V> Runtime<GasBlockStore<'bs, BS>> for DefaultRuntime<'bs, '_, BS, R, CS, V>
 where
     BS: BlockStore,
     V: ProofVerifier,
-    P: NetworkParams,
     R: Rand,
     CS: CircSupplyCalc,
 {
     fn network_version(&self) -> NetworkVersion {
         self.version
     }
+    fn network_chain(&self) -> NetworkChain {
+        self.chain
+    }
     fn message(&self) -> &dyn MessageInfo {
         &self.vm_msg
     }
@@ -631,11 +679,10 @@ where
     }
 }
 
-impl<'bs, BS, R, C, V, P> Syscalls for DefaultRuntime<'bs, '_, BS, R, C, V, P>
+impl<'bs, BS, R, C, V> Syscalls for DefaultRuntime<'bs, '_, BS, R, C, V>
 where
     BS: BlockStore,
     V: ProofVerifier,
-    P: NetworkParams,
     R: Rand,
     C: CircSupplyCalc,
 {
@@ -677,7 +724,7 @@ where
             .borrow_mut()
             .charge_gas(self.price_list.on_verify_seal(vi))?;
 
-        V::verify_seal(vi)
+        verify_seal_cached::<V>(vi)
     }
     fn verify_post(&self, vi: &WindowPoStVerifyInfo) -> Result<(), Box<dyn StdError>> {
         self.gas_tracker
@@ -791,42 +838,20 @@ where
         vis: &[(&Address, &Vec<SealVerifyInfo>)],
     ) -> Result<HashMap<Address, Vec<bool>>, Box<dyn StdError>> {
         // Gas charged for batch verify in actor
-
-        let out = vis
-            .par_iter()
-            .map(|(&addr, seals)| {
-                let results = seals
-                    .par_iter()
-                    .map(|s| {
-                        if let Err(err) = V::verify_seal(s) {
-                            warn!(
-                                "seal verify in batch failed (miner: {}) (err: {})",
-                                addr, err
-                            );
-                            false
-                        } else {
-                            true
-                        }
-                    })
-                    .collect();
-                (addr, results)
-            })
-            .collect();
-        Ok(out)
+        Ok(batch_verify_seals::<V>(vis))
     }
 }
 
 /// Shared logic between the DefaultRuntime and the Interpreter.
 /// It invokes methods on different Actors based on the Message.
-pub fn vm_send<'db, 'vm, BS, R, C, V, P>(
-    rt: &mut DefaultRuntime<'db, 'vm, BS, R, C, V, P>,
+pub fn vm_send<'db, 'vm, BS, R, C, V>(
+    rt: &mut DefaultRuntime<'db, 'vm, BS, R, C, V>,
     msg: &UnsignedMessage,
     gas_cost: Option<GasCharge>,
 ) -> Result<Serialized, ActorError>
 where
     BS: BlockStore,
     V: ProofVerifier,
-    P: NetworkParams,
     R: Rand,
     C: CircSupplyCalc,
 {
@@ -927,8 +952,8 @@ fn transfer<BS: BlockStore>(
 }
 
 /// Calls actor code with method and parameters.
-fn invoke<'db, 'vm, BS, R, C, V, P>(
-    rt: &mut DefaultRuntime<'db, 'vm, BS, R, C, V, P>,
+fn invoke<'db, 'vm, BS, R, C, V>(
+    rt: &mut DefaultRuntime<'db, 'vm, BS, R, C, V>,
     code: Cid,
     method_num: MethodNum,
     params: &Serialized,
@@ -937,20 +962,16 @@ fn invoke<'db, 'vm, BS, R, C, V, P>(
 where
     BS: BlockStore,
     V: ProofVerifier,
-    P: NetworkParams,
     R: Rand,
     C: CircSupplyCalc,
 {
-    let ret = if let Some(ret) = actor::invoke_code(&code, rt, method_num, params) {
-        ret
-    } else if code == *CHAOS_ACTOR_CODE_ID && rt.registered_actors.contains(&code) {
-        chaos::Actor::invoke_method(rt, method_num, params)
-    } else {
-        Err(actor_error!(
+    let ret = match rt.registered_actors.get(&code) {
+        Some(actor) => actor.invoke_method(rt, method_num, params),
+        None => Err(actor_error!(
             SysErrorIllegalActor,
             "no code for actor at address {}",
             to
-        ))
+        )),
     }?;
 
     if !rt.caller_validated {
@@ -0,0 +1,296 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::gas_tracker::GasCharge;
+use message::{MessageReceipt, UnsignedMessage};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use vm::{ExitCode, Serialized};
+
+/// Trace of the execution of a message, including every gas charge made while executing it and
+/// the traces of the subcalls sent by the invoked actor. The JSON format matches Lotus.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExecutionTrace {
+    pub msg: UnsignedMessage,
+    pub msg_rct: MessageReceipt,
+    pub error: Option<String>,
+    /// Time taken to execute the message.
+    pub duration: Duration,
+    pub gas_charges: Vec<GasTrace>,
+    pub subcalls: Vec<ExecutionTrace>,
+}
+
+impl ExecutionTrace {
+    /// Creates an empty trace of the given message, the receipt is set once execution finishes.
+    pub fn new(msg: UnsignedMessage) -> Self {
+        Self {
+            msg,
+            msg_rct: MessageReceipt {
+                exit_code: ExitCode::Ok,
+                return_data: Serialized::default(),
+                gas_used: 0,
+            },
+            error: None,
+            duration: Duration::default(),
+            gas_charges: Vec::new(),
+            subcalls: Vec::new(),
+        }
+    }
+}
+
+/// Gas charged during the execution of a message.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GasTrace {
+    #[serde(rename = "Name")]
+    pub name: String,
+    /// Source locations which made the charge. These are not recorded, the field only exists
+    /// for compatibility with Lotus traces.
+    #[serde(rename = "loc", default)]
+    pub location: Vec<Loc>,
+    #[serde(rename = "tg")]
+    pub total_gas: i64,
+    #[serde(rename = "cg")]
+    pub compute_gas: i64,
+    #[serde(rename = "sg")]
+    pub storage_gas: i64,
+    #[serde(rename = "vtg")]
+    pub total_virtual_gas: i64,
+    #[serde(rename = "vcg")]
+    pub virtual_compute_gas: i64,
+    #[serde(rename = "vsg")]
+    pub virtual_storage_gas: i64,
+    /// Time elapsed since the previous charge.
+    #[serde(rename = "tt", with = "nanos")]
+    pub time_taken: Duration,
+    #[serde(rename = "ex", default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<serde_json::Value>,
+}
+
+impl From<&GasCharge> for GasTrace {
+    fn from(charge: &GasCharge) -> Self {
+        Self {
+            name: charge.name.to_owned(),
+            location: Vec::new(),
+            total_gas: charge.total(),
+            compute_gas: charge.compute_gas,
+            storage_gas: charge.storage_gas,
+            total_virtual_gas: 0,
+            virtual_compute_gas: 0,
+            virtual_storage_gas: 0,
+            time_taken: Duration::default(),
+            extra: None,
+        }
+    }
+}
+
+/// Source location of a gas charge.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Loc {
+    pub file: String,
+    pub line: i64,
+    pub function: String,
+}
+
+/// Serializes durations as integer nanoseconds, like Go's `time.Duration`.
+mod nanos {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (d.as_nanos() as i64).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let nanos = i64::deserialize(deserializer)?;
+        Ok(Duration::from_nanos(nanos.max(0) as u64))
+    }
+}
+
+#[cfg(feature = "json")]
+pub mod json {
+    use super::*;
+    use message::{message_receipt, unsigned_message};
+    use serde::{Deserializer, Serializer};
+
+    /// Wrapper for serializing and deserializing an ExecutionTrace from JSON.
+    #[derive(Deserialize, Serialize)]
+    #[serde(transparent)]
+    pub struct ExecutionTraceJson(#[serde(with = "self")] pub ExecutionTrace);
+
+    /// Wrapper for serializing an ExecutionTrace reference to JSON.
+    #[derive(Serialize)]
+    #[serde(transparent)]
+    pub struct ExecutionTraceJsonRef<'a>(#[serde(with = "self")] pub &'a ExecutionTrace);
+
+    impl From<ExecutionTraceJson> for ExecutionTrace {
+        fn from(wrapper: ExecutionTraceJson) -> Self {
+            wrapper.0
+        }
+    }
+
+    pub fn serialize<S>(m: &ExecutionTrace, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct ExecutionTraceSer<'a> {
+            #[serde(with = "unsigned_message::json")]
+            msg: &'a UnsignedMessage,
+            #[serde(with = "message_receipt::json")]
+            msg_rct: &'a MessageReceipt,
+            error: &'a str,
+            #[serde(with = "nanos")]
+            duration: &'a Duration,
+            gas_charges: &'a [GasTrace],
+            subcalls: Vec<ExecutionTraceJsonRef<'a>>,
+        }
+        ExecutionTraceSer {
+            msg: &m.msg,
+            msg_rct: &m.msg_rct,
+            error: m.error.as_deref().unwrap_or_default(),
+            duration: &m.duration,
+            gas_charges: &m.gas_charges,
+            subcalls: m.subcalls.iter().map(ExecutionTraceJsonRef).collect(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<ExecutionTrace, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "PascalCase")]
+        struct ExecutionTraceDe {
+            #[serde(with = "unsigned_message::json")]
+            msg: UnsignedMessage,
+            #[serde(with = "message_receipt::json")]
+            msg_rct: MessageReceipt,
+            #[serde(default)]
+            error: Option<String>,
+            #[serde(default, with = "nanos")]
+            duration: Duration,
+            #[serde(default)]
+            gas_charges: Option<Vec<GasTrace>>,
+            #[serde(default)]
+            subcalls: Option<Vec<ExecutionTraceJson>>,
+        }
+        let ExecutionTraceDe {
+            msg,
+            msg_rct,
+            error,
+            duration,
+            gas_charges,
+            subcalls,
+        } = Deserialize::deserialize(deserializer)?;
+        Ok(ExecutionTrace {
+            msg,
+            msg_rct,
+            // Lotus encodes the absence of an error as an empty string
+            error: error.filter(|e| !e.is_empty()),
+            duration,
+            gas_charges: gas_charges.unwrap_or_default(),
+            subcalls: subcalls
+                .unwrap_or_default()
+                .into_iter()
+                .map(|s| s.0)
+                .collect(),
+        })
+    }
+
+    pub mod opt {
+        use super::*;
+
+        pub fn serialize<S>(v: &Option<ExecutionTrace>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            v.as_ref().map(ExecutionTraceJsonRef).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<ExecutionTrace>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            let s: Option<ExecutionTraceJson> = Deserialize::deserialize(deserializer)?;
+            Ok(s.map(|s| s.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gas_trace_json() {
+        let charge = GasCharge::new("on_chain_message", 10, 20);
+        let mut trace = GasTrace::from(&charge);
+        trace.time_taken = Duration::from_nanos(1500);
+
+        let value = serde_json::to_value(&trace).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "Name": "on_chain_message",
+                "loc": [],
+                "tg": charge.total(),
+                "cg": 10,
+                "sg": 20,
+                "vtg": 0,
+                "vcg": 0,
+                "vsg": 0,
+                "tt": 1500,
+            })
+        );
+        assert_eq!(serde_json::from_value::<GasTrace>(value).unwrap(), trace);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn execution_trace_json() {
+        let msg = UnsignedMessage::builder()
+            .from(address::Address::new_id(1))
+            .to(address::Address::new_id(2))
+            .build()
+            .unwrap();
+        let mut trace = ExecutionTrace::new(msg.clone());
+        trace.duration = Duration::from_millis(2);
+        trace
+            .gas_charges
+            .push(GasTrace::from(&GasCharge::new("on_chain_message", 10, 20)));
+        let mut subcall = ExecutionTrace::new(msg);
+        subcall.error = Some("failed".to_owned());
+        trace.subcalls.push(subcall);
+
+        let value = serde_json::to_value(json::ExecutionTraceJsonRef(&trace)).unwrap();
+        let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "Duration",
+                "Error",
+                "GasCharges",
+                "Msg",
+                "MsgRct",
+                "Subcalls"
+            ]
+        );
+        assert_eq!(value["Duration"], 2_000_000);
+        assert_eq!(value["Error"], "");
+        assert_eq!(value["GasCharges"][0]["Name"], "on_chain_message");
+        assert_eq!(value["Subcalls"][0]["Error"], "failed");
+
+        let json::ExecutionTraceJson(decoded) = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded, trace);
+    }
+}
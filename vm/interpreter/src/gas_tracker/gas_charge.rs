@@ -1,7 +1,6 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-const GAS_STORAGE_MULTI: i64 = 1000;
 const GAS_COMPUTE_MULTI: i64 = 1;
 
 pub struct GasCharge {
@@ -19,8 +18,9 @@ impl GasCharge {
         }
     }
 
-    /// Calculates total gas charge based on compute and storage multipliers.
+    /// Calculates total gas charge based on the compute multiplier. Storage gas is already
+    /// scaled by the storage multiplier of the price list the charge was created from.
     pub fn total(&self) -> i64 {
-        self.compute_gas * GAS_COMPUTE_MULTI + self.storage_gas * GAS_STORAGE_MULTI
+        self.compute_gas * GAS_COMPUTE_MULTI + self.storage_gas
     }
 }
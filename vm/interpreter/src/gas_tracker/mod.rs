@@ -5,12 +5,18 @@ mod gas_charge;
 mod price_list;
 
 pub use self::gas_charge::GasCharge;
-pub use self::price_list::{price_list_by_epoch, PriceList};
+pub use self::price_list::{PriceList, PriceSchedule};
+use super::GasTrace;
+use std::time::Instant;
 use vm::{actor_error, ActorError, ExitCode};
 
 pub(crate) struct GasTracker {
     gas_available: i64,
     gas_used: i64,
+    /// Gas charges recorded since the last call to `take_gas_charges`, if tracing is enabled
+    gas_charges: Option<Vec<GasTrace>>,
+    /// Time of the last gas charge, or of the start of tracing
+    last_charge_time: Instant,
 }
 
 impl GasTracker {
@@ -18,11 +24,34 @@ impl GasTracker {
         Self {
             gas_available,
             gas_used,
+            gas_charges: None,
+            last_charge_time: Instant::now(),
         }
     }
 
+    /// Starts recording every gas charge
+    pub fn enable_tracing(&mut self) {
+        self.gas_charges.get_or_insert_with(Vec::new);
+        self.last_charge_time = Instant::now();
+    }
+
+    /// Returns the gas charges recorded so far, and resets the record
+    pub fn take_gas_charges(&mut self) -> Vec<GasTrace> {
+        self.gas_charges
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     /// Safely consumes gas
     pub fn charge_gas(&mut self, charge: GasCharge) -> Result<(), ActorError> {
+        if let Some(gas_charges) = &mut self.gas_charges {
+            let now = Instant::now();
+            let mut trace = GasTrace::from(&charge);
+            trace.time_taken = now - self.last_charge_time;
+            self.last_charge_time = now;
+            gas_charges.push(trace);
+        }
         let to_use = charge.total();
         let used = self.gas_used + to_use;
         if used > self.gas_available {
@@ -61,4 +90,22 @@ mod tests {
         assert_eq!(t.gas_used(), 20);
         assert!(t.charge_gas(GasCharge::new("", 1, 0)).is_err())
     }
+
+    #[test]
+    fn gas_tracker_tracing() {
+        let mut t = GasTracker::new(20, 0);
+        t.charge_gas(GasCharge::new("untraced", 5, 0)).unwrap();
+        assert!(t.take_gas_charges().is_empty());
+
+        t.enable_tracing();
+        t.charge_gas(GasCharge::new("first", 2, 0)).unwrap();
+        assert!(t.charge_gas(GasCharge::new("second", 1, 1000)).is_err());
+        let charges = t.take_gas_charges();
+        assert_eq!(
+            charges.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            vec!["first", "second"]
+        );
+        assert_eq!(charges[1].total_gas, 1001);
+        assert!(t.take_gas_charges().is_empty());
+    }
 }
@@ -6,13 +6,17 @@ use ahash::AHashMap;
 use clock::ChainEpoch;
 use crypto::SignatureType;
 use fil_types::{
-    PieceInfo, RegisteredPoStProof, RegisteredSealProof, SealVerifyInfo, WindowPoStVerifyInfo,
+    NetworkChain, PieceInfo, RegisteredPoStProof, RegisteredSealProof, SealVerifyInfo,
+    WindowPoStVerifyInfo, UPGRADE_CALIBNET_CALICO_HEIGHT, UPGRADE_CALICO_HEIGHT,
 };
 use num_traits::Zero;
 use vm::{MethodNum, TokenAmount, METHOD_SEND};
 
 lazy_static! {
     static ref BASE_PRICES: PriceList = PriceList {
+        compute_gas_multiplier: 1,
+        storage_gas_multiplier: 1000,
+
         on_chain_message_compute_base: 38863,
         on_chain_message_storage_base: 36,
         on_chain_message_storage_per_byte: 1,
@@ -66,6 +70,44 @@ lazy_static! {
         .iter()
         .copied()
         .collect(),
+        verify_post_discount: true,
+    };
+
+    static ref CALICO_PRICES: PriceList = PriceList {
+        storage_gas_multiplier: 1300,
+
+        ipld_get_base: 114617,
+        ipld_put_base: 353640,
+
+        verify_post_lookup: [
+            (
+                RegisteredPoStProof::StackedDRGWindow512MiBV1,
+                ScalingCost {
+                    flat: 117680921,
+                    scale: 43780,
+                },
+            ),
+            (
+                RegisteredPoStProof::StackedDRGWindow32GiBV1,
+                ScalingCost {
+                    flat: 117680921,
+                    scale: 43780,
+                },
+            ),
+            (
+                RegisteredPoStProof::StackedDRGWindow64GiBV1,
+                ScalingCost {
+                    flat: 117680921,
+                    scale: 43780,
+                },
+            ),
+        ]
+        .iter()
+        .copied()
+        .collect(),
+        verify_post_discount: false,
+
+        ..BASE_PRICES.clone()
     };
 }
 
@@ -78,6 +120,11 @@ pub struct ScalingCost {
 /// Provides prices for operations in the VM
 #[derive(Clone, Debug)]
 pub struct PriceList {
+    /// Multiplier applied to the compute gas of every charge.
+    pub compute_gas_multiplier: i64,
+    /// Multiplier applied to the storage gas of every charge, converting bytes stored to gas.
+    pub storage_gas_multiplier: i64,
+
     /// Gas cost charged to the originator of an on-chain message (regardless of
     /// whether it succeeds or fails in application) is given by:
     ///   OnChainMessageBase + len(serialized message)*OnChainMessagePerByte
@@ -146,14 +193,25 @@ pub struct PriceList {
     pub compute_unsealed_sector_cid_base: i64,
     pub verify_seal_base: i64,
     pub verify_post_lookup: AHashMap<RegisteredPoStProof, ScalingCost>,
+    /// Halves the cost of PoSt verification when set.
+    pub verify_post_discount: bool,
     pub verify_consensus_fault: i64,
 }
 
 impl PriceList {
+    /// Creates a gas charge, scaling the compute and storage gas by the multipliers of the list.
+    #[inline]
+    fn charge(&self, name: &'static str, compute_gas: i64, storage_gas: i64) -> GasCharge {
+        GasCharge::new(
+            name,
+            compute_gas * self.compute_gas_multiplier,
+            storage_gas * self.storage_gas_multiplier,
+        )
+    }
     /// Returns the gas required for storing a message of a given size in the chain.
     #[inline]
     pub fn on_chain_message(&self, msg_size: usize) -> GasCharge {
-        GasCharge::new(
+        self.charge(
             "on_chain_message",
             self.on_chain_message_compute_base,
             self.on_chain_message_storage_base
@@ -163,7 +221,7 @@ impl PriceList {
     /// Returns the gas required for storing the response of a message in the chain.
     #[inline]
     pub fn on_chain_return_value(&self, data_size: usize) -> GasCharge {
-        GasCharge::new(
+        self.charge(
             "on_chain_return_value",
             0,
             data_size as i64 * self.on_chain_return_value_per_byte,
@@ -182,17 +240,17 @@ impl PriceList {
         if method_num != METHOD_SEND {
             ret += self.send_invoke_method;
         }
-        GasCharge::new("on_method_invocation", ret, 0)
+        self.charge("on_method_invocation", ret, 0)
     }
     /// Returns the gas required for storing an object
     #[inline]
     pub fn on_ipld_get(&self) -> GasCharge {
-        GasCharge::new("on_ipld_get", self.ipld_get_base, 0)
+        self.charge("on_ipld_get", self.ipld_get_base, 0)
     }
     /// Returns the gas required for storing an object
     #[inline]
     pub fn on_ipld_put(&self, data_size: usize) -> GasCharge {
-        GasCharge::new(
+        self.charge(
             "on_ipld_put",
             self.ipld_put_base,
             data_size as i64 * self.ipld_put_per_byte,
@@ -201,7 +259,7 @@ impl PriceList {
     /// Returns the gas required for creating an actor
     #[inline]
     pub fn on_create_actor(&self) -> GasCharge {
-        GasCharge::new(
+        self.charge(
             "on_create_actor",
             self.create_actor_compute,
             self.create_actor_storage,
@@ -210,7 +268,7 @@ impl PriceList {
     /// Returns the gas required for deleting an actor
     #[inline]
     pub fn on_delete_actor(&self) -> GasCharge {
-        GasCharge::new("on_delete_actor", 0, self.delete_actor)
+        self.charge("on_delete_actor", 0, self.delete_actor)
     }
     /// Returns gas required for signature verification
     #[inline]
@@ -219,12 +277,12 @@ impl PriceList {
             SignatureType::BLS => self.bls_sig_cost,
             SignatureType::Secp256k1 => self.secp256k1_sig_cost,
         };
-        GasCharge::new("on_verify_signature", val, 0)
+        self.charge("on_verify_signature", val, 0)
     }
     /// Returns gas required for hashing data
     #[inline]
     pub fn on_hashing(&self, _: usize) -> GasCharge {
-        GasCharge::new("on_hashing", self.hashing_base, 0)
+        self.charge("on_hashing", self.hashing_base, 0)
     }
     /// Returns gas required for computing unsealed sector Cid
     #[inline]
@@ -233,7 +291,7 @@ impl PriceList {
         _proof: RegisteredSealProof,
         _pieces: &[PieceInfo],
     ) -> GasCharge {
-        GasCharge::new(
+        self.charge(
             "on_compute_unsealed_sector_cid",
             self.compute_unsealed_sector_cid_base,
             0,
@@ -242,7 +300,7 @@ impl PriceList {
     /// Returns gas required for seal verification
     #[inline]
     pub fn on_verify_seal(&self, _info: &SealVerifyInfo) -> GasCharge {
-        GasCharge::new("on_verify_seal", self.verify_seal_base, 0)
+        self.charge("on_verify_seal", self.verify_seal_base, 0)
     }
     /// Returns gas required for PoSt verification
     #[inline]
@@ -259,14 +317,16 @@ impl PriceList {
         });
 
         let mut gas_used = cost.flat + info.challenged_sectors.len() as i64 * cost.scale;
-        gas_used /= 2;
+        if self.verify_post_discount {
+            gas_used /= 2;
+        }
 
-        GasCharge::new("on_verify_post", gas_used, 0)
+        self.charge("on_verify_post", gas_used, 0)
     }
     /// Returns gas required for verifying consensus fault
     #[inline]
     pub fn on_verify_consensus_fault(&self) -> GasCharge {
-        GasCharge::new("on_verify_consensus_fault", self.verify_consensus_fault, 0)
+        self.charge("on_verify_consensus_fault", self.verify_consensus_fault, 0)
     }
 }
 
@@ -276,8 +336,179 @@ impl Default for PriceList {
     }
 }
 
-/// Returns gas price list by Epoch for gas consumption
-pub fn price_list_by_epoch(_epoch: ChainEpoch) -> PriceList {
-    // In future will match on epoch and select matching price lists when config options allowed
-    BASE_PRICES.clone()
+/// Schedule of the price lists of a network, each taking effect from its starting epoch.
+#[derive(Clone, Debug)]
+pub struct PriceSchedule {
+    prices: Vec<(ChainEpoch, PriceList)>,
+}
+
+impl PriceSchedule {
+    /// Creates a schedule from price lists and the epoch they take effect at. Epochs must be
+    /// strictly increasing, and the first price list must take effect at genesis.
+    pub fn new(prices: Vec<(ChainEpoch, PriceList)>) -> Result<Self, String> {
+        match prices.first() {
+            Some((0, _)) => (),
+            _ => return Err("price schedule must start at genesis".to_owned()),
+        }
+        if let Some(pair) = prices.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+            return Err(format!(
+                "price list at epoch {} is not after price list at epoch {}",
+                pair[1].0, pair[0].0
+            ));
+        }
+        Ok(Self { prices })
+    }
+
+    /// Price schedule of mainnet.
+    pub fn mainnet() -> Self {
+        Self {
+            prices: vec![
+                (0, BASE_PRICES.clone()),
+                (UPGRADE_CALICO_HEIGHT, CALICO_PRICES.clone()),
+            ],
+        }
+    }
+
+    /// Price schedule of the calibration network.
+    pub fn calibnet() -> Self {
+        Self {
+            prices: vec![
+                (0, BASE_PRICES.clone()),
+                (UPGRADE_CALIBNET_CALICO_HEIGHT, CALICO_PRICES.clone()),
+            ],
+        }
+    }
+
+    /// Price schedule of local devnets, which use the latest price list from genesis.
+    pub fn devnet() -> Self {
+        Self {
+            prices: vec![(0, CALICO_PRICES.clone())],
+        }
+    }
+
+    /// Price schedule of the given network.
+    pub fn for_chain(chain: NetworkChain) -> Self {
+        match chain {
+            NetworkChain::Mainnet => Self::mainnet(),
+            NetworkChain::Calibnet => Self::calibnet(),
+            NetworkChain::Devnet => Self::devnet(),
+        }
+    }
+
+    /// Returns the price list in effect at the given epoch.
+    pub fn price_list(&self, epoch: ChainEpoch) -> &PriceList {
+        self.prices
+            .iter()
+            .take_while(|(start, _)| *start <= epoch)
+            .last()
+            .map(|(_, prices)| prices)
+            .unwrap_or(&self.prices[0].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fil_types::SectorInfo;
+
+    #[test]
+    fn price_list_selection() {
+        let mainnet = PriceSchedule::mainnet();
+        let base = mainnet.price_list(0);
+        assert_eq!(base.storage_gas_multiplier, 1000);
+        assert_eq!(
+            mainnet
+                .price_list(UPGRADE_CALICO_HEIGHT - 1)
+                .storage_gas_multiplier,
+            1000
+        );
+
+        let calico = mainnet.price_list(UPGRADE_CALICO_HEIGHT);
+        assert_eq!(calico.storage_gas_multiplier, 1300);
+        assert_eq!(
+            mainnet
+                .price_list(UPGRADE_CALICO_HEIGHT + 1000)
+                .storage_gas_multiplier,
+            1300
+        );
+
+        // Storage gas is scaled by the multiplier of the list, compute gas is not.
+        assert_eq!(base.on_chain_message(64).storage_gas, (36 + 64) * 1000);
+        assert_eq!(calico.on_chain_message(64).storage_gas, (36 + 64) * 1300);
+        assert_eq!(
+            base.on_chain_message(64).compute_gas,
+            calico.on_chain_message(64).compute_gas
+        );
+    }
+
+    #[test]
+    fn price_schedule_for_chain() {
+        let calibnet = PriceSchedule::for_chain(NetworkChain::Calibnet);
+        assert_eq!(
+            calibnet
+                .price_list(UPGRADE_CALIBNET_CALICO_HEIGHT - 1)
+                .storage_gas_multiplier,
+            1000
+        );
+        assert_eq!(
+            calibnet
+                .price_list(UPGRADE_CALIBNET_CALICO_HEIGHT)
+                .storage_gas_multiplier,
+            1300
+        );
+
+        let devnet = PriceSchedule::for_chain(NetworkChain::Devnet);
+        assert_eq!(devnet.price_list(0).storage_gas_multiplier, 1300);
+
+        let mainnet = PriceSchedule::for_chain(NetworkChain::Mainnet);
+        assert_eq!(
+            mainnet
+                .price_list(UPGRADE_CALIBNET_CALICO_HEIGHT)
+                .storage_gas_multiplier,
+            1000
+        );
+    }
+
+    #[test]
+    fn verify_post_pricing() {
+        let sector = SectorInfo {
+            proof: RegisteredSealProof::StackedDRG512MiBV1,
+            sector_number: 0,
+            sealed_cid: Default::default(),
+        };
+        let info = WindowPoStVerifyInfo {
+            challenged_sectors: vec![sector; 10],
+            ..Default::default()
+        };
+        let mainnet = PriceSchedule::mainnet();
+        assert_eq!(
+            mainnet.price_list(0).on_verify_post(&info).compute_gas,
+            (123861062 + 10 * 9226981) / 2
+        );
+        assert_eq!(
+            mainnet
+                .price_list(UPGRADE_CALICO_HEIGHT)
+                .on_verify_post(&info)
+                .compute_gas,
+            117680921 + 10 * 43780
+        );
+    }
+
+    #[test]
+    fn price_schedule_validation() {
+        assert!(PriceSchedule::new(vec![]).is_err());
+        assert!(PriceSchedule::new(vec![(10, PriceList::default())]).is_err());
+        assert!(PriceSchedule::new(vec![
+            (0, PriceList::default()),
+            (20, PriceList::default()),
+            (20, PriceList::default()),
+        ])
+        .is_err());
+
+        let schedule =
+            PriceSchedule::new(vec![(0, PriceList::default()), (20, CALICO_PRICES.clone())])
+                .unwrap();
+        assert_eq!(schedule.price_list(19).storage_gas_multiplier, 1000);
+        assert_eq!(schedule.price_list(20).storage_gas_multiplier, 1300);
+    }
 }
@@ -5,12 +5,15 @@
 extern crate lazy_static;
 
 mod default_runtime;
+pub mod execution_trace;
 mod gas_block_store;
 mod gas_tracker;
 mod rand;
+mod seal_verification;
 mod vm;
 
 pub use self::default_runtime::*;
+pub use self::execution_trace::{ExecutionTrace, GasTrace, Loc};
 pub use self::gas_tracker::*;
 pub use self::rand::*;
 pub use self::vm::*;
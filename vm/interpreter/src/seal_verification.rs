@@ -0,0 +1,134 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use address::Address;
+use fil_types::{verifier::ProofVerifier, SealVerifyInfo};
+use log::warn;
+use lru::LruCache;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::sync::Mutex;
+
+/// Number of seal verification results kept, enough to cover the proofs of a few epochs.
+const SEAL_VERIFY_CACHE_SIZE: usize = 4096;
+
+/// Seal verification results are keyed by the verifier used, as well as the seal, so that
+/// results of different verifiers are never mixed up.
+type SealVerifyKey = (&'static str, SealVerifyInfo);
+
+lazy_static! {
+    /// Pool seals are verified on, so that batches of seal verifications don't starve other
+    /// users of the global rayon pool.
+    static ref SEAL_VERIFY_POOL: ThreadPool = ThreadPoolBuilder::new()
+        .thread_name(|i| format!("seal-verify-{}", i))
+        .build()
+        .expect("failed to build seal verification thread pool");
+
+    /// Results of seals verified recently. Verification is deterministic, so re-executing a
+    /// tipset, or applying it again after a reorg, doesn't need to verify its seals again.
+    static ref SEAL_VERIFY_CACHE: Mutex<LruCache<SealVerifyKey, Result<(), String>>> =
+        Mutex::new(LruCache::new(SEAL_VERIFY_CACHE_SIZE));
+}
+
+/// Verifies a seal, using the result of a previous verification of the same seal if cached.
+pub(crate) fn verify_seal_cached<V>(vi: &SealVerifyInfo) -> Result<(), Box<dyn StdError>>
+where
+    V: ProofVerifier,
+{
+    let key = (std::any::type_name::<V>(), vi.clone());
+    if let Some(res) = SEAL_VERIFY_CACHE.lock().unwrap().get(&key) {
+        return res.clone().map_err(|e| e.into());
+    }
+
+    let res = V::verify_seal(vi).map_err(|e| e.to_string());
+    SEAL_VERIFY_CACHE.lock().unwrap().put(key, res.clone());
+    res.map_err(|e| e.into())
+}
+
+/// Verifies batches of seals of each miner in parallel on the seal verification pool.
+pub(crate) fn batch_verify_seals<V>(
+    vis: &[(&Address, &Vec<SealVerifyInfo>)],
+) -> HashMap<Address, Vec<bool>>
+where
+    V: ProofVerifier,
+{
+    SEAL_VERIFY_POOL.install(|| {
+        vis.par_iter()
+            .map(|(&addr, seals)| {
+                let results = seals
+                    .par_iter()
+                    .map(|s| {
+                        if let Err(err) = verify_seal_cached::<V>(s) {
+                            warn!(
+                                "seal verify in batch failed (miner: {}) (err: {})",
+                                addr, err
+                            );
+                            false
+                        } else {
+                            true
+                        }
+                    })
+                    .collect();
+                (addr, results)
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fil_types::SectorID;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static VERIFIED: AtomicUsize = AtomicUsize::new(0);
+
+    /// Verifier which only accepts seals of even sector numbers, and counts verifications.
+    enum CountingVerifier {}
+
+    impl ProofVerifier for CountingVerifier {
+        fn verify_seal(vi: &SealVerifyInfo) -> Result<(), Box<dyn StdError>> {
+            VERIFIED.fetch_add(1, Ordering::SeqCst);
+            if vi.sector_id.number % 2 == 0 {
+                Ok(())
+            } else {
+                Err("odd sector".into())
+            }
+        }
+    }
+
+    fn seal_info(number: u64) -> SealVerifyInfo {
+        SealVerifyInfo {
+            registered_proof: fil_types::RegisteredSealProof::StackedDRG2KiBV1,
+            sector_id: SectorID { miner: 1, number },
+            deal_ids: Vec::new(),
+            randomness: Default::default(),
+            interactive_randomness: Default::default(),
+            proof: vec![number as u8],
+            sealed_cid: Default::default(),
+            unsealed_cid: Default::default(),
+        }
+    }
+
+    #[test]
+    fn batch_verify_uses_cache() {
+        let miner = Address::new_id(1);
+        let seals: Vec<_> = (0..4).map(seal_info).collect();
+        let batch = [(&miner, &seals)];
+
+        let res = batch_verify_seals::<CountingVerifier>(&batch);
+        assert_eq!(res[&miner], vec![true, false, true, false]);
+        assert_eq!(VERIFIED.load(Ordering::SeqCst), 4);
+
+        // Re-verifying the batch is served from the cache.
+        let res = batch_verify_seals::<CountingVerifier>(&batch);
+        assert_eq!(res[&miner], vec![true, false, true, false]);
+        assert_eq!(VERIFIED.load(Ordering::SeqCst), 4);
+
+        assert!(verify_seal_cached::<CountingVerifier>(&seals[0]).is_ok());
+        assert!(verify_seal_cached::<CountingVerifier>(&seals[1]).is_err());
+        assert_eq!(VERIFIED.load(Ordering::SeqCst), 4);
+    }
+}
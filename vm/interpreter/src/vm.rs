@@ -2,12 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::{
-    gas_tracker::{price_list_by_epoch, GasCharge},
-    vm_send, DefaultRuntime, Rand,
+    gas_tracker::{GasCharge, PriceSchedule},
+    vm_send, DefaultRuntime, ExecutionTrace, Rand,
 };
 use actor::{
-    cron, reward, ACCOUNT_ACTOR_CODE_ID, BURNT_FUNDS_ACTOR_ADDR, CRON_ACTOR_ADDR,
-    REWARD_ACTOR_ADDR, SYSTEM_ACTOR_ADDR,
+    cron, reward, ActorRegistry, BuiltinActor, ACCOUNT_ACTOR_CODE_ID, BUILTIN_ACTORS,
+    BURNT_FUNDS_ACTOR_ADDR, CRON_ACTOR_ADDR, REWARD_ACTOR_ADDR, SYSTEM_ACTOR_ADDR,
 };
 use address::Address;
 use cid::Cid;
@@ -15,7 +15,7 @@ use clock::ChainEpoch;
 use fil_types::BLOCK_GAS_LIMIT;
 use fil_types::{
     verifier::{FullVerifier, ProofVerifier},
-    DevnetParams, NetworkParams, NetworkVersion,
+    NetworkChain, NetworkVersion,
 };
 use forest_encoding::Cbor;
 use ipld_blockstore::BlockStore;
@@ -24,10 +24,12 @@ use message::{ChainMessage, Message, MessageReceipt, UnsignedMessage};
 use num_bigint::{BigInt, Sign};
 use num_traits::Zero;
 use state_tree::StateTree;
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::error::Error as StdError;
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
 use vm::{actor_error, ActorError, ExitCode, Serialized, TokenAmount};
 const GAS_OVERUSE_NUM: i64 = 11;
 const GAS_OVERUSE_DENOM: i64 = 10;
@@ -49,24 +51,26 @@ pub trait CircSupplyCalc {
 
 /// Interpreter which handles execution of state transitioning messages and returns receipts
 /// from the vm execution.
-pub struct VM<'db, 'r, DB, R, N, C, V = FullVerifier, P = DevnetParams> {
+pub struct VM<'db, 'r, DB, R, N, C, V = FullVerifier> {
     state: StateTree<'db, DB>,
     store: &'db DB,
     epoch: ChainEpoch,
     rand: &'r R,
     base_fee: BigInt,
-    registered_actors: HashSet<Cid>,
+    /// Builtin actors, only copied when actor code is registered with the VM.
+    registered_actors: Cow<'static, ActorRegistry>,
     network_version_getter: N,
+    chain: NetworkChain,
+    price_schedule: &'r PriceSchedule,
     circ_supply_calc: &'r C,
+    tracing: bool,
     verifier: PhantomData<V>,
-    params: PhantomData<P>,
 }
 
-impl<'db, 'r, DB, R, N, C, V, P> VM<'db, 'r, DB, R, N, C, V, P>
+impl<'db, 'r, DB, R, N, C, V> VM<'db, 'r, DB, R, N, C, V>
 where
     DB: BlockStore,
     V: ProofVerifier,
-    P: NetworkParams,
     R: Rand,
     N: Fn(ChainEpoch) -> NetworkVersion,
     C: CircSupplyCalc,
@@ -79,34 +83,43 @@ where
         rand: &'r R,
         base_fee: BigInt,
         network_version_getter: N,
+        chain: NetworkChain,
+        price_schedule: &'r PriceSchedule,
         circ_supply_calc: &'r C,
     ) -> Result<Self, String> {
         let state = StateTree::new_from_root(store, root).map_err(|e| e.to_string())?;
-        let registered_actors = HashSet::new();
         Ok(VM {
             network_version_getter,
+            chain,
+            price_schedule,
             state,
             store,
             epoch,
             rand,
             base_fee,
-            registered_actors,
+            registered_actors: Cow::Borrowed(&*BUILTIN_ACTORS),
             circ_supply_calc,
+            tracing: false,
             verifier: PhantomData,
-            params: PhantomData,
         })
     }
 
-    /// Registers an actor that is not part of the set of default builtin actors by providing the code cid
-    pub fn register_actor(&mut self, code_cid: Cid) -> bool {
-        self.registered_actors.insert(code_cid)
+    /// Registers actor code that is not part of the set of default builtin actors under the
+    /// given code cid. Returns false if code was already registered under the cid.
+    pub fn register_actor(&mut self, code_cid: Cid, actor: BuiltinActor) -> bool {
+        self.registered_actors.to_mut().register(code_cid, actor)
     }
 
-    /// Gets registered actors that are not part of the set of default builtin actors
-    pub fn registered_actors(&self) -> &HashSet<Cid> {
+    /// Gets the actor code the VM can invoke, keyed by code cid.
+    pub fn registered_actors(&self) -> &ActorRegistry {
         &self.registered_actors
     }
 
+    /// Enables recording an execution trace of every message applied, returned in the `ApplyRet`.
+    pub fn enable_tracing(&mut self) {
+        self.tracing = true;
+    }
+
     /// Flush stores in VM and return state root.
     pub fn flush(&mut self) -> Result<Cid, Box<dyn StdError>> {
         self.state.flush()
@@ -121,7 +134,8 @@ where
         &self.state
     }
 
-    fn run_cron(
+    /// Applies the cron message, which is run at the end of every epoch including null rounds.
+    pub fn run_cron(
         &mut self,
         epoch: ChainEpoch,
         callback: Option<&mut impl FnMut(&Cid, &ChainMessage, &ApplyRet) -> Result<(), String>>,
@@ -153,24 +167,17 @@ where
     }
 
     /// Apply block messages from a Tipset.
+    /// Cron for the null rounds since the parent tipset must have already been run.
     /// Returns the receipts from the transactions.
     pub fn apply_block_messages(
         &mut self,
         messages: &[BlockMessages],
-        parent_epoch: ChainEpoch,
         epoch: ChainEpoch,
         mut callback: Option<impl FnMut(&Cid, &ChainMessage, &ApplyRet) -> Result<(), String>>,
     ) -> Result<Vec<MessageReceipt>, Box<dyn StdError>> {
         let mut receipts = Vec::new();
         let mut processed = HashSet::<Cid>::default();
 
-        for i in parent_epoch..epoch {
-            if i > parent_epoch {
-                self.run_cron(epoch, callback.as_mut())?;
-            }
-            self.epoch = i + 1;
-        }
-
         for block in messages.iter() {
             let mut penalty = Default::default();
             let mut gas_reward = Default::default();
@@ -250,18 +257,29 @@ where
     }
 
     pub fn apply_implicit_message(&mut self, msg: &UnsignedMessage) -> ApplyRet {
-        let (return_data, _, act_err) = self.send(msg, None);
-
-        ApplyRet {
-            msg_receipt: MessageReceipt {
-                return_data,
-                exit_code: if let Some(err) = &act_err {
-                    err.exit_code()
-                } else {
-                    ExitCode::Ok
-                },
-                gas_used: 0,
+        let start = Instant::now();
+        let (return_data, rt, act_err) = self.send(msg, None);
+        let execution_trace = rt.and_then(|mut rt| rt.take_execution_trace());
+
+        let msg_receipt = MessageReceipt {
+            return_data,
+            exit_code: if let Some(err) = &act_err {
+                err.exit_code()
+            } else {
+                ExitCode::Ok
             },
+            gas_used: 0,
+        };
+        ApplyRet {
+            execution_trace: execution_trace.map(|trace| {
+                with_receipt(
+                    trace,
+                    &msg_receipt,
+                    act_err.as_ref().map(|e| e.to_string()),
+                    start.elapsed(),
+                )
+            }),
+            msg_receipt,
             act_error: act_err,
             penalty: BigInt::zero(),
             miner_tip: BigInt::zero(),
@@ -271,9 +289,10 @@ where
     /// Applies the state transition for a single message
     /// Returns ApplyRet structure which contains the message receipt and some meta data.
     pub fn apply_message(&mut self, msg: &ChainMessage) -> Result<ApplyRet, String> {
+        let start = Instant::now();
         check_message(msg.message())?;
 
-        let pl = price_list_by_epoch(self.epoch());
+        let pl = self.price_schedule.price_list(self.epoch());
         let ser_msg = msg.marshal_cbor().map_err(|e| e.to_string())?;
         let msg_gas_cost = pl.on_chain_message(ser_msg.len());
         let cost_total = msg_gas_cost.total();
@@ -289,6 +308,7 @@ where
                     "Out of gas ({} > {})", cost_total, msg.gas_limit())),
                 penalty: &self.base_fee * cost_total,
                 miner_tip: BigInt::zero(),
+                execution_trace: None,
             });
         }
 
@@ -305,6 +325,7 @@ where
                     penalty: miner_penalty_amount,
                     act_error: Some(actor_error!(SysErrSenderInvalid; "Sender invalid")),
                     miner_tip: 0.into(),
+                    execution_trace: None,
                 });
             }
         };
@@ -319,6 +340,7 @@ where
                 penalty: miner_penalty_amount,
                 act_error: Some(actor_error!(SysErrSenderInvalid; "send not from account actor")),
                 miner_tip: 0.into(),
+                execution_trace: None,
             });
         };
 
@@ -333,6 +355,7 @@ where
                 act_error: Some(actor_error!(SysErrSenderStateInvalid;
                     "actor sequence invalid: {} != {}", msg.sequence(), from_act.sequence)),
                 miner_tip: 0.into(),
+                execution_trace: None,
             });
         };
 
@@ -348,6 +371,7 @@ where
                 act_error: Some(actor_error!(SysErrSenderStateInvalid;
                     "actor balance less than needed: {} < {}", from_act.balance, gas_cost)),
                 miner_tip: 0.into(),
+                execution_trace: None,
             });
         };
 
@@ -391,7 +415,7 @@ where
             }
         }
 
-        let gas_used = if let Some(mut rt) = rt {
+        let (gas_used, execution_trace) = if let Some(mut rt) = rt {
             if !ret_data.is_empty() {
                 if let Err(e) = rt.charge_gas(rt.price_list().on_chain_return_value(ret_data.len()))
                 {
@@ -399,11 +423,8 @@ where
                     ret_data = Serialized::default();
                 }
             }
-            if rt.gas_used() < 0 {
-                0
-            } else {
-                rt.gas_used()
-            }
+            let gas_used = if rt.gas_used() < 0 { 0 } else { rt.gas_used() };
+            (gas_used, rt.take_execution_trace())
         } else {
             return Err(format!("send returned None runtime: {:?}", act_err));
         };
@@ -464,12 +485,21 @@ where
         }
         self.state.clear_snapshot()?;
 
+        let msg_receipt = MessageReceipt {
+            return_data: ret_data,
+            exit_code: err_code,
+            gas_used,
+        };
         Ok(ApplyRet {
-            msg_receipt: MessageReceipt {
-                return_data: ret_data,
-                exit_code: err_code,
-                gas_used,
-            },
+            execution_trace: execution_trace.map(|trace| {
+                with_receipt(
+                    trace,
+                    &msg_receipt,
+                    act_err.as_ref().map(|e| e.to_string()),
+                    start.elapsed(),
+                )
+            }),
+            msg_receipt,
             penalty: miner_penalty,
             act_error: act_err,
             miner_tip,
@@ -484,11 +514,13 @@ where
         gas_cost: Option<GasCharge>,
     ) -> (
         Serialized,
-        Option<DefaultRuntime<'db, '_, DB, R, C, V, P>>,
+        Option<DefaultRuntime<'db, '_, DB, R, C, V>>,
         Option<ActorError>,
     ) {
         let res = DefaultRuntime::new(
             (self.network_version_getter)(self.epoch),
+            self.chain,
+            self.price_schedule,
             &mut self.state,
             self.store,
             0,
@@ -503,10 +535,15 @@ where
         );
 
         match res {
-            Ok(mut rt) => match vm_send(&mut rt, msg, gas_cost) {
-                Ok(ser) => (ser, Some(rt), None),
-                Err(actor_err) => (Serialized::default(), Some(rt), Some(actor_err)),
-            },
+            Ok(mut rt) => {
+                if self.tracing {
+                    rt.enable_tracing(msg);
+                }
+                match vm_send(&mut rt, msg, gas_cost) {
+                    Ok(ser) => (ser, Some(rt), None),
+                    Err(actor_err) => (Serialized::default(), Some(rt), Some(actor_err)),
+                }
+            }
             Err(e) => (Serialized::default(), None, Some(e)),
         }
     }
@@ -590,6 +627,21 @@ pub struct ApplyRet {
     pub act_error: Option<ActorError>,
     pub penalty: BigInt,
     pub miner_tip: BigInt,
+    /// Trace of the execution of the message, only recorded if tracing is enabled in the VM
+    pub execution_trace: Option<ExecutionTrace>,
+}
+
+/// Fills in the receipt, error and duration of the trace of a top level message.
+fn with_receipt(
+    mut trace: ExecutionTrace,
+    receipt: &MessageReceipt,
+    error: Option<String>,
+    duration: Duration,
+) -> ExecutionTrace {
+    trace.msg_rct = receipt.clone();
+    trace.error = error;
+    trace.duration = duration;
+    trace
 }
 
 /// Does some basic checks on the Message to see if the fields are valid.
@@ -0,0 +1,144 @@
+// Copyright 2020 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use actor::{
+    account, init, ActorRegistry, ACCOUNT_ACTOR_CODE_ID, INIT_ACTOR_ADDR, SYSTEM_ACTOR_ADDR,
+};
+use address::Address;
+use cid::Code::{Blake2b256, Identity};
+use clock::ChainEpoch;
+use crypto::DomainSeparationTag;
+use db::MemoryDB;
+use fil_types::{verifier::MockVerifier, NetworkChain, NetworkVersion};
+use interpreter::{vm_send, CircSupplyCalc, DefaultRuntime, PriceSchedule, Rand};
+use ipld_blockstore::BlockStore;
+use ipld_hamt::Hamt;
+use message::UnsignedMessage;
+use state_tree::StateTree;
+use std::error::Error as StdError;
+use vm::{ActorState, ExitCode, TokenAmount, METHOD_SEND};
+
+struct MockCircSupply;
+impl CircSupplyCalc for MockCircSupply {
+    fn get_supply<DB: BlockStore>(
+        &self,
+        _: ChainEpoch,
+        _: &StateTree<DB>,
+    ) -> Result<TokenAmount, Box<dyn StdError>> {
+        Ok(0.into())
+    }
+}
+
+struct MockRand;
+impl Rand for MockRand {
+    fn get_chain_randomness(
+        &self,
+        _: DomainSeparationTag,
+        _: ChainEpoch,
+        _: &[u8],
+    ) -> Result<[u8; 32], Box<dyn StdError>> {
+        Ok(*b"i_am_random_____i_am_random_____")
+    }
+    fn get_beacon_randomness(
+        &self,
+        _: DomainSeparationTag,
+        _: ChainEpoch,
+        _: &[u8],
+    ) -> Result<[u8; 32], Box<dyn StdError>> {
+        Ok(*b"i_am_random_____i_am_random_____")
+    }
+}
+
+#[test]
+fn trace_records_subcalls() {
+    let store = MemoryDB::default();
+    let mut state = StateTree::new(&store);
+
+    let e_cid = Hamt::<_, String>::new_with_bit_width(&store, 5)
+        .flush()
+        .unwrap();
+    let init_state = init::State::new(e_cid, "test".to_owned());
+    let init_cid = store.put(&init_state, Blake2b256).unwrap();
+    state
+        .set_actor(
+            &INIT_ACTOR_ADDR,
+            ActorState::new(ACCOUNT_ACTOR_CODE_ID.clone(), init_cid, 0.into(), 1),
+        )
+        .unwrap();
+
+    let sender = Address::new_id(100);
+    let sender_cid = store
+        .put(&account::State { address: sender }, Identity)
+        .unwrap();
+    let sender = state.register_new_address(&sender).unwrap();
+    state
+        .set_actor(
+            &sender,
+            ActorState::new(ACCOUNT_ACTOR_CODE_ID.clone(), sender_cid, 10000.into(), 0),
+        )
+        .unwrap();
+
+    // Sending to an address without an actor creates an account actor through a subcall
+    let receiver = Address::new_secp256k1(&[4; 65]).unwrap();
+    let message = UnsignedMessage::builder()
+        .to(receiver)
+        .from(sender)
+        .method_num(METHOD_SEND)
+        .value(1.into())
+        .gas_limit(10000000)
+        .build()
+        .unwrap();
+
+    let registered = ActorRegistry::builtin();
+    let mut runtime = DefaultRuntime::<_, _, _, MockVerifier>::new(
+        NetworkVersion::V0,
+        NetworkChain::Mainnet,
+        &PriceSchedule::mainnet(),
+        &mut state,
+        &store,
+        0,
+        &message,
+        0,
+        sender,
+        0,
+        0,
+        &MockRand,
+        &registered,
+        &MockCircSupply,
+    )
+    .unwrap();
+    runtime.enable_tracing(&message);
+    vm_send(&mut runtime, &message, None).unwrap();
+    let trace = runtime.take_execution_trace().unwrap();
+
+    assert_eq!(trace.msg, message);
+    assert!(trace
+        .gas_charges
+        .iter()
+        .any(|c| c.name == "on_create_actor"));
+
+    assert_eq!(trace.subcalls.len(), 1);
+    let subcall = &trace.subcalls[0];
+    assert_eq!(subcall.msg.from(), &*SYSTEM_ACTOR_ADDR);
+    assert_eq!(
+        subcall.msg.method_num(),
+        account::Method::Constructor as u64
+    );
+    assert_eq!(subcall.msg_rct.exit_code, ExitCode::Ok);
+    assert_eq!(subcall.error, None);
+    assert!(!subcall.gas_charges.is_empty());
+    assert!(subcall.subcalls.is_empty());
+
+    // Each charge is recorded once, in the trace of the call which made it
+    let traced: i64 = trace
+        .gas_charges
+        .iter()
+        .chain(subcall.gas_charges.iter())
+        .map(|c| c.total_gas)
+        .sum();
+    assert_eq!(traced, runtime.gas_used());
+    assert_eq!(
+        subcall.msg_rct.gas_used,
+        subcall.gas_charges.iter().map(|c| c.total_gas).sum::<i64>()
+    );
+}
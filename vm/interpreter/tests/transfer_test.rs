@@ -1,19 +1,18 @@
 // Copyright 2020 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use actor::{init, ACCOUNT_ACTOR_CODE_ID, INIT_ACTOR_ADDR};
+use actor::{init, ActorRegistry, ACCOUNT_ACTOR_CODE_ID, INIT_ACTOR_ADDR};
 use address::Address;
 use cid::Code::{Blake2b256, Identity};
 use clock::ChainEpoch;
 use crypto::DomainSeparationTag;
 use db::MemoryDB;
-use fil_types::{verifier::MockVerifier, NetworkVersion};
-use interpreter::{vm_send, CircSupplyCalc, DefaultRuntime, Rand};
+use fil_types::{verifier::MockVerifier, NetworkChain, NetworkVersion};
+use interpreter::{vm_send, CircSupplyCalc, DefaultRuntime, PriceSchedule, Rand};
 use ipld_blockstore::BlockStore;
 use ipld_hamt::Hamt;
 use message::UnsignedMessage;
 use state_tree::StateTree;
-use std::collections::HashSet;
 use std::error::Error as StdError;
 use vm::{ActorState, Serialized, TokenAmount};
 
@@ -126,10 +125,12 @@ fn transfer_test() {
         .build()
         .unwrap();
 
-    let registered = HashSet::new();
+    let registered = ActorRegistry::builtin();
 
     let mut runtime = DefaultRuntime::<_, _, _, MockVerifier>::new(
         NetworkVersion::V0,
+        NetworkChain::Mainnet,
+        &PriceSchedule::mainnet(),
         &mut state,
         &store,
         0,
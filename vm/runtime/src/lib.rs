@@ -11,7 +11,7 @@ use clock::ChainEpoch;
 use commcid::data_commitment_v1_to_cid;
 use crypto::{DomainSeparationTag, Signature};
 use fil_types::{
-    zero_piece_commitment, NetworkVersion, PaddedPieceSize, PieceInfo, Randomness,
+    zero_piece_commitment, NetworkChain, NetworkVersion, PaddedPieceSize, PieceInfo, Randomness,
     RegisteredSealProof, SealVerifyInfo, WindowPoStVerifyInfo,
 };
 use filecoin_proofs_api::seal::compute_comm_d;
@@ -29,6 +29,9 @@ pub trait Runtime<BS: BlockStore> {
     /// The network protocol version number at the current epoch.
     fn network_version(&self) -> NetworkVersion;
 
+    /// The network the VM is running on, which sets network specific actor policies.
+    fn network_chain(&self) -> NetworkChain;
+
     /// Information related to the current message being executed.
     fn message(&self) -> &dyn MessageInfo;
 